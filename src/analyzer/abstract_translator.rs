@@ -7,32 +7,45 @@ pub fn abstract_program<B:AbstractDomain>(prog: Program<LitInterval>) -> Program
     let abs_arcs = prog.arcs.into_iter().map(|(l1,cmd,l2)|{
         let abs_cmd = match cmd {
             Command::Assignment(x, a) => Command::Assignment(x, translate_aexpr(a)),
-            Command::Test(b) => Command::Test(translate_bexpr(b)),
+            Command::Test(kind, b) => Command::Test(kind, translate_bexpr(b)),
+            Command::Call(p, args) => Command::Call(p, args.into_iter().map(translate_aexpr).collect()),
+            Command::Return(x, p) => Command::Return(x, p),
+            Command::ArrayAssign(x, i, a, span) => Command::ArrayAssign(x, translate_aexpr(i), translate_aexpr(a), span),
+            Command::NewArray(x, n) => Command::NewArray(x, translate_aexpr(n)),
         };
         (l1,abs_cmd,l2)
     }).collect();
-    Program::new(abs_arcs,prog.widening_points)
+    Program { statements: prog.statements, ..Program::new(abs_arcs,prog.widening_points) }
 }
 fn translate_aexpr<B:AbstractDomain>(a: Aexpr<LitInterval>) -> Aexpr<B>{
     match a {
         Aexpr::Lit(n) => Aexpr::Lit(B::from(Interval::from(n))),
-        Aexpr::Var(x) => Aexpr::Var(x),
-        Aexpr::PreOp(op, x) => Aexpr::PreOp(op, x),
-        Aexpr::PostOp(op, x) => Aexpr::PostOp(op, x),
-        Aexpr::BinOp(op, a1, a2) => 
-            Aexpr::BinOp(op, Box::new(translate_aexpr(*a1)), Box::new(translate_aexpr(*a2))),
+        Aexpr::Var(x, span) => Aexpr::Var(x, span),
+        Aexpr::PreOp(op, x, span) => Aexpr::PreOp(op, x, span),
+        Aexpr::PostOp(op, x, span) => Aexpr::PostOp(op, x, span),
+        Aexpr::BinOp(op, a1, a2, span) => 
+            Aexpr::BinOp(op, Box::new(translate_aexpr(*a1)), Box::new(translate_aexpr(*a2)), span),
+        Aexpr::Call(p, args, span) => Aexpr::Call(p, args.into_iter().map(translate_aexpr).collect(), span),
+        Aexpr::Index(x, i, span) => Aexpr::Index(x, Box::new(translate_aexpr(*i)), span),
+        Aexpr::Len(x, span) => Aexpr::Len(x, span),
     }
 }
 fn translate_bexpr<B:AbstractDomain>(b: Bexpr<LitInterval>) -> Bexpr<B>{
     match b {
         Bexpr::True => Bexpr::True,
         Bexpr::False => Bexpr::False,
-        Bexpr::Equal(a1, a2) => 
-            Bexpr::Equal(Box::new(translate_aexpr(*a1)), Box::new(translate_aexpr(*a2))),
-        Bexpr::LessEq(a1, a2) => 
-            Bexpr::LessEq(Box::new(translate_aexpr(*a1)), Box::new(translate_aexpr(*a2))),
+        Bexpr::Equal(a1, a2, span) => 
+            Bexpr::Equal(Box::new(translate_aexpr(*a1)), Box::new(translate_aexpr(*a2)), span),
+        Bexpr::LessEq(a1, a2, span) => 
+            Bexpr::LessEq(Box::new(translate_aexpr(*a1)), Box::new(translate_aexpr(*a2)), span),
         Bexpr::Not(b) => Bexpr::Not(Box::new(translate_bexpr(*b))),
         Bexpr::And(b1, b2) => 
             Bexpr::And(Box::new(translate_bexpr(*b1)), Box::new(translate_bexpr(*b2))),
+        Bexpr::Or(b1, b2) => 
+            Bexpr::Or(Box::new(translate_bexpr(*b1)), Box::new(translate_bexpr(*b2))),
+        Bexpr::Less(a1, a2, span) => 
+            Bexpr::Less(Box::new(translate_aexpr(*a1)), Box::new(translate_aexpr(*a2)), span),
+        Bexpr::NotEqual(a1, a2, span) => 
+            Bexpr::NotEqual(Box::new(translate_aexpr(*a1)), Box::new(translate_aexpr(*a2)), span),
     }
 }
@@ -1,8 +1,8 @@
 use std::collections::BTreeSet;
 
-use crate::types::ast::{Aexpr, Bexpr, Operator, Var};
+use crate::types::{ast::{Aexpr, Bexpr, Operator, Var}, span::Span};
 
-use super::{analyzers::generic_analyzer::GenericAnalyzer, types::{analyzer::StaticAnalyzer, domain::{AbstractDomain, Interval}, state::AbstractState}};
+use super::{analyzers::generic_analyzer::GenericAnalyzer, arrays::{cell_var, length_var}, types::{analyzer::StaticAnalyzer, domain::{AbstractDomain, Interval}, state::AbstractState}};
 
 
 pub fn eval_bexpr<B: AbstractDomain, D: AbstractState<B>>(b: &Bexpr<B>, state: D) -> D {
@@ -21,12 +21,75 @@ pub fn eval_bexpr<B: AbstractDomain, D: AbstractState<B>>(b: &Bexpr<B>, state: D
     state
 }
 
+// Backward abstract test: the states of pre that satisfy b (and so lead to post).
+// A test with inc/dec changes the state, in that case pre is kept as it is
+pub fn backward_test<B: AbstractDomain, D: AbstractState<B>>(b: &Bexpr<B>, post: &D, pre: D) -> D {
+    if *post == D::bottom() {
+        return D::bottom()
+    }
+    match check_no_dup_b(b) {
+        Ok((_, ops)) if ops.is_empty() => eval_bexpr(b, pre.glb(post)),
+        _ => pre,
+    }
+}
+
+// Backward call: the state of the caller changes only for the side effects of the arguments
+pub fn backward_call<B: AbstractDomain, D: AbstractState<B>>(args: &[Aexpr<B>], post: &D, pre: D) -> D {
+    if *post == D::bottom() {
+        return D::bottom()
+    }
+    match args.iter().map(check_no_dup_a).collect::<Result<Vec<_>, _>>() {
+        Ok(checks) if checks.iter().all(|(_, ops)| ops.is_empty()) => pre.glb(post),
+        _ => pre,
+    }
+}
+
+// Backward of a command that evaluates the expressions and then gives to vars values that do not
+// depend on post (e.g. the smashed elements of an array after a weak update)
+pub fn backward_havoc<B: AbstractDomain, D: AbstractState<B>>(vars: &[Var], exprs: &[&Aexpr<B>], post: &D, pre: D) -> D {
+    if *post == D::bottom() {
+        return D::bottom()
+    }
+    match exprs.iter().map(|a| check_no_dup_a(a)).collect::<Result<Vec<_>, _>>() {
+        Ok(checks) if checks.iter().all(|(_, ops)| ops.is_empty()) => {
+            let mut post = post.clone();
+            for x in vars {
+                post.set(x.clone(), B::top());
+            }
+            pre.glb(&post)
+        },
+        _ => pre,
+    }
+}
+
+// Backward abstract assignment x := a: the states of pre where a evaluates to a value of x in post
+// (the other variables keep the values of post)
+pub fn backward_assign<B: AbstractDomain, D: AbstractState<B>>(x: &Var, a: &Aexpr<B>, post: &D, pre: D) -> D {
+    if *post == D::bottom() {
+        return D::bottom()
+    }
+    match check_no_dup_a(a) {
+        Ok((_, ops)) if ops.is_empty() => (),
+        _ => return pre,
+    }
+    let target = post.get(x);
+    let mut state = post.clone();
+    state.set(x.clone(), B::top());
+    let state = state.glb(&pre);
+
+    let eval_tree = eval_aexpr_tree(a, &state);
+    if eval_tree.get_domain().glb(target) == B::bottom() {
+        return D::bottom()
+    }
+    refine(&eval_tree, state, target)
+}
+
 // In the case where advance abstract test cannot be used it fallback to this
 fn eval_bexpr_dumb<B: AbstractDomain, D: AbstractState<B>> (b: &Bexpr<B>, state: D) -> D {
     match b {
         Bexpr::True => state,
         Bexpr::False => D::bottom(),
-        Bexpr::Equal(a1, a2) => {
+        Bexpr::Equal(a1, a2, _) => {
             let (d1, state) = GenericAnalyzer::eval_aexpr(a1, state);
             let (d2, state) = GenericAnalyzer::eval_aexpr(a2, state);
             if d1.glb(d2) == B::bottom() {
@@ -35,8 +98,8 @@ fn eval_bexpr_dumb<B: AbstractDomain, D: AbstractState<B>> (b: &Bexpr<B>, state:
                 state
             }
         },
-        Bexpr::LessEq(_, _) => state,
-        Bexpr::Not(_) => state,
+        Bexpr::LessEq(..) | Bexpr::Less(..) | Bexpr::NotEqual(..) => state,
+        Bexpr::Not(_) | Bexpr::Or(_, _) => state,
         Bexpr::And(b1, b2) => {
             let state = eval_bexpr_dumb(b1, state);
             let state = eval_bexpr_dumb(b2, state);
@@ -52,15 +115,20 @@ fn eval_bexpr_h<B: AbstractDomain, D: AbstractState<B>>(b: &Bexpr<B>, state: D,
         Bexpr::True => state,
         Bexpr::False if negated => state,
         Bexpr::False => D::bottom(),
-        Bexpr::Equal(a1, a2) => {
-            let domain = if !negated {
+        Bexpr::Equal(a1, a2, _) | Bexpr::NotEqual(a1, a2, _) => {
+            let equal = matches!(b, Bexpr::Equal(..)) != negated;
+            // != 0 is not representable in the convex domains, but it surely fails when a1 - a2 is exactly 0
+            if !equal && difference(a1, a2, &state) == B::from(0) {
+                return D::bottom()
+            }
+            let domain = if equal {
                 B::from(0) // == 0
             } else {
                 B::from(Interval::OpenLeft(-1)).lub(B::from(Interval::OpenRight(1))) // != 0
             };
             advanced_abstract_tests(a1, a2, state, domain)
         },
-        Bexpr::LessEq(a1, a2) => {
+        Bexpr::LessEq(a1, a2, _) => {
             let domain = if !negated {
                 B::from(Interval::OpenLeft(0)) // <= 0
             } else {
@@ -68,10 +136,19 @@ fn eval_bexpr_h<B: AbstractDomain, D: AbstractState<B>>(b: &Bexpr<B>, state: D,
             };
             advanced_abstract_tests(a1, a2, state, domain)
         },
-        Bexpr::And(b1, b2) => {
+        Bexpr::Less(a1, a2, _) => {
+            let domain = if !negated {
+                B::from(Interval::OpenLeft(-1)) // < 0
+            } else {
+                B::from(Interval::OpenRight(0)) // >= 0
+            };
+            advanced_abstract_tests(a1, a2, state, domain)
+        },
+        Bexpr::And(b1, b2) | Bexpr::Or(b1, b2) => {
             let state1 = eval_bexpr_h(b1, state.clone(), negated);
             let state2 = eval_bexpr_h(b2, state, negated);
-            if !negated {
+            // A negated or is an and of the negations, and vice versa
+            if matches!(b, Bexpr::And(..)) != negated {
                 state1.glb(&state2) // AND
             } else {
                 state1.lub(&state2) // OR
@@ -81,6 +158,12 @@ fn eval_bexpr_h<B: AbstractDomain, D: AbstractState<B>>(b: &Bexpr<B>, state: D,
     }
 }
 
+// Abstract value of a1 - a2
+fn difference<B: AbstractDomain, D: AbstractState<B>>(a1: &Aexpr<B>, a2: &Aexpr<B>, state: &D) -> B {
+    let a = Aexpr::BinOp(Operator::Sub, Box::new(a1.clone()), Box::new(a2.clone()), Span::default());
+    eval_aexpr_tree(&a, state).get_domain()
+}
+
 // Advanced test on: a1 - a2 \in domain
 fn advanced_abstract_tests<B: AbstractDomain, D: AbstractState<B>>(a1: &Aexpr<B>, a2: &Aexpr<B>, state: D, domain: B) -> D {
     // This logic worked only because in all the implemented abstract domains
@@ -90,8 +173,12 @@ fn advanced_abstract_tests<B: AbstractDomain, D: AbstractState<B>>(a1: &Aexpr<B>
     //     Aexpr::Lit(n) if n == &D::from(0) => (a1 as &Aexpr<D>).clone(),
     //     _ => Aexpr::BinOp(Operator::Sub, Box::new(a1.clone()), Box::new(a2.clone()))
     // };
-    let a = Aexpr::BinOp(Operator::Sub, Box::new(a1.clone()), Box::new(a2.clone()));
+    let a = Aexpr::BinOp(Operator::Sub, Box::new(a1.clone()), Box::new(a2.clone()), Span::default());
     let eval_tree = eval_aexpr_tree(&a, &state);
+    // refine changes only the variables, a test on the literals (like the bounds of a constant index) fails here
+    if eval_tree.get_domain().glb(domain) == B::bottom() {
+        return D::bottom()
+    }
     let state: D = refine(&eval_tree, state, domain);
     state
 }
@@ -115,9 +202,14 @@ impl<B:AbstractDomain> EvalTree<B> {
 fn eval_aexpr_tree<B: AbstractDomain, D: AbstractState<B>>(a: &Aexpr<B>, state: &D) -> EvalTree<B> {
     match a {
         Aexpr::Lit(n) => EvalTree::LeafNum(*n),
-        Aexpr::Var(x) => EvalTree::LeafVar(x.clone(), state.get(x)),
-        Aexpr::PreOp(_, x) | Aexpr::PostOp(_, x) => EvalTree::LeafVar(x.clone(), state.get(x)),
-        Aexpr::BinOp(op, a1, a2 ) => {
+        Aexpr::Var(x, _) => EvalTree::LeafVar(x.clone(), state.get(x)),
+        Aexpr::PreOp(_, x, _) | Aexpr::PostOp(_, x, _) => EvalTree::LeafVar(x.clone(), state.get(x)),
+        // The result of a call does not depend on the variables of the caller
+        Aexpr::Call(..) => EvalTree::LeafNum(B::top()),
+        // A single element cannot be refined, the others share the same abstract value
+        Aexpr::Index(x, _, _) => EvalTree::LeafNum(state.get(&cell_var(x))),
+        Aexpr::Len(x, _) => EvalTree::LeafVar(length_var(x), state.get(&length_var(x))),
+        Aexpr::BinOp(op, a1, a2, _) => {
             let t1 = eval_aexpr_tree(a1, state);
             let t2 = eval_aexpr_tree(a2, state);
             EvalTree::BinOp(
@@ -133,7 +225,7 @@ fn refine<B: AbstractDomain, D: AbstractState<B>>(tree: &EvalTree<B>, mut state:
     match tree {
         EvalTree::LeafNum(_) => state,
         EvalTree::LeafVar(x, _) => {
-            state.set(x.clone(), state.get(x).glb(target_domain));
+            state.refine(x, target_domain);
             state
         }
         EvalTree::BinOp(op, _, lhs, rhs) => {
@@ -154,13 +246,13 @@ fn refine<B: AbstractDomain, D: AbstractState<B>>(tree: &EvalTree<B>, mut state:
 fn eval_pre_b<B: AbstractDomain, D: AbstractState<B>>(b: &Bexpr<B>, state: D) -> D {
     match b{
         Bexpr::True | Bexpr::False => state,
-        Bexpr::Equal(a1, a2) | Bexpr::LessEq(a1, a2) => {
+        Bexpr::Equal(a1, a2, _) | Bexpr::LessEq(a1, a2, _) | Bexpr::Less(a1, a2, _) | Bexpr::NotEqual(a1, a2, _) => {
             let state = eval_pre_a(a1, state);
             let state = eval_pre_a(a2, state);
             state
         },
         Bexpr::Not(b) => eval_pre_b(b, state),
-        Bexpr::And(b1, b2) => {
+        Bexpr::And(b1, b2) | Bexpr::Or(b1, b2) => {
             let state = eval_pre_b(b1, state);
             let state = eval_pre_b(b2, state);
             state
@@ -169,12 +261,14 @@ fn eval_pre_b<B: AbstractDomain, D: AbstractState<B>>(b: &Bexpr<B>, state: D) ->
 }
 fn eval_pre_a<B: AbstractDomain, D: AbstractState<B>>(a: &Aexpr<B>, state: D) -> D {
     match a{
-        Aexpr::PreOp(_, _) => GenericAnalyzer::eval_aexpr(a, state).1,
-        Aexpr::BinOp(_, a1, a2) => {
+        Aexpr::PreOp(_, _, _) => GenericAnalyzer::eval_aexpr(a, state).1,
+        Aexpr::BinOp(_, a1, a2, _) => {
             let state = eval_pre_a(a1, state);
             let state = eval_pre_a(a2, state);
             state
         }
+        Aexpr::Call(_, args, _) => args.iter().fold(state, |state, a| eval_pre_a(a, state)),
+        Aexpr::Index(_, i, _) => eval_pre_a(i, state),
         _ => state
     }
 }
@@ -183,13 +277,13 @@ fn eval_pre_a<B: AbstractDomain, D: AbstractState<B>>(a: &Aexpr<B>, state: D) ->
 fn eval_post_b<B: AbstractDomain, D: AbstractState<B>>(b: &Bexpr<B>, state: D) -> D {
     match b{
         Bexpr::True | Bexpr::False => state,
-        Bexpr::Equal(a1, a2) | Bexpr::LessEq(a1, a2) => {
+        Bexpr::Equal(a1, a2, _) | Bexpr::LessEq(a1, a2, _) | Bexpr::Less(a1, a2, _) | Bexpr::NotEqual(a1, a2, _) => {
             let state = eval_post_a(a1, state);
             let state = eval_post_a(a2, state);
             state
         },
         Bexpr::Not(b) => eval_post_b(b, state),
-        Bexpr::And(b1, b2) => {
+        Bexpr::And(b1, b2) | Bexpr::Or(b1, b2) => {
             let state = eval_post_b(b1, state);
             let state = eval_post_b(b2, state);
             state
@@ -198,12 +292,14 @@ fn eval_post_b<B: AbstractDomain, D: AbstractState<B>>(b: &Bexpr<B>, state: D) -
 }
 fn eval_post_a<B: AbstractDomain, D: AbstractState<B>>(a: &Aexpr<B>, state: D) -> D {
     match a{
-        Aexpr::PostOp(_, _) => GenericAnalyzer::eval_aexpr(a, state).1,
-        Aexpr::BinOp(_, a1, a2) => {
+        Aexpr::PostOp(_, _, _) => GenericAnalyzer::eval_aexpr(a, state).1,
+        Aexpr::BinOp(_, a1, a2, _) => {
             let state = eval_post_a(a1, state);
             let state = eval_post_a(a2, state);
             state
         }
+        Aexpr::Call(_, args, _) => args.iter().fold(state, |state, a| eval_post_a(a, state)),
+        Aexpr::Index(_, i, _) => eval_post_a(i, state),
         _ => state
     }
 }
@@ -238,13 +334,13 @@ fn check_no_dup_b <B: AbstractDomain>(b: &Bexpr<B>) -> Result<(BTreeSet<Var>, BT
     match b {
         Bexpr::True => Ok((BTreeSet::new(), BTreeSet::new())),
         Bexpr::False => Ok((BTreeSet::new(), BTreeSet::new())),
-        Bexpr::Equal(a1, a2) | Bexpr::LessEq(a1, a2) => {
+        Bexpr::Equal(a1, a2, _) | Bexpr::LessEq(a1, a2, _) | Bexpr::Less(a1, a2, _) | Bexpr::NotEqual(a1, a2, _) => {
             let r1 = check_no_dup_a(a1)?;
             let r2 = check_no_dup_a(a2)?;            
             merge(r1,r2)      
         },
         Bexpr::Not(b) => check_no_dup_b(b),
-        Bexpr::And(b1, b2) => {
+        Bexpr::And(b1, b2) | Bexpr::Or(b1, b2) => {
             let r1 = check_no_dup_b(b1)?;
             let r2 = check_no_dup_b(b2)?;            
             merge(r1,r2)      
@@ -255,13 +351,17 @@ fn check_no_dup_b <B: AbstractDomain>(b: &Bexpr<B>) -> Result<(BTreeSet<Var>, BT
 fn check_no_dup_a <B: AbstractDomain>(a: &Aexpr<B>) -> Result<(BTreeSet<Var>, BTreeSet<Var>),Var> {
     match a {
         Aexpr::Lit(_) => Ok((BTreeSet::new(), BTreeSet::new())),
-        Aexpr::Var(x) => Ok((BTreeSet::from([x.clone()]), BTreeSet::new())),
-        Aexpr::PreOp(_, x) | Aexpr::PostOp(_, x) => Ok((BTreeSet::new(), BTreeSet::from([x.clone()]))),
-        Aexpr::BinOp(_, a1, a2) => {
+        Aexpr::Var(x, _) => Ok((BTreeSet::from([x.clone()]), BTreeSet::new())),
+        Aexpr::PreOp(_, x, _) | Aexpr::PostOp(_, x, _) => Ok((BTreeSet::new(), BTreeSet::from([x.clone()]))),
+        Aexpr::BinOp(_, a1, a2, _) => {
             let r1 = check_no_dup_a(a1)?;
             let r2 = check_no_dup_a(a2)?;            
             merge(r1,r2)            
         },
+        Aexpr::Call(_, args, _) => args.iter()
+            .try_fold((BTreeSet::new(), BTreeSet::new()), |r, a| merge(r, check_no_dup_a(a)?)),
+        Aexpr::Index(_, i, _) => check_no_dup_a(i),
+        Aexpr::Len(x, _) => Ok((BTreeSet::from([length_var(x)]), BTreeSet::new())),
     }
 }
 
@@ -284,3 +384,28 @@ fn merge(
 
     Ok((v1,op1))
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{analyzer::{domains::interval_domain::IntervalDomain, states::hashmap_state::HashMapState, test_utils::analyze_module, types::analyzer::IterationStrategy}, parser::{ast_parser::desugar, parse_string}, types::lit_interval::LitInterval};
+
+    // Exit states of the analysis of the program as it is and desugared
+    fn exit_states(source: &str) -> (String, String) {
+        let module = parse_string::<LitInterval>(source.to_string()).unwrap();
+        let exit = |module| {
+            let analysis = analyze_module::<IntervalDomain, HashMapState<IntervalDomain>>(module, IterationStrategy::WideningAndNarrowing { delay: 0 });
+            analysis.states[&(analysis.prog.labels_num - 1)].to_string()
+        };
+        (exit(module.clone()), exit(desugar(module)))
+    }
+
+    #[test]
+    fn refinement_of_or_less_and_not_equal() {
+        // The else branch has x in [3,8], w is refined by < and by the negated !=
+        let source = "x := [0,10];\nif x < 3 or 8 < x then {y := x;} else {y := x + 100;}\nw := [0,10];\nassume w < 4;\nassume not (w != 3);";
+        let expected = "{w: [3,3], x: [0,10], y: [0,108]}".to_string();
+        assert_eq!(exit_states(source), (expected.clone(), expected));
+        let (direct, desugared) = exit_states("x := 4;\nassume x != 4 or x < 4;");
+        assert_eq!((direct.as_str(), desugared.as_str()), ("⊥", "⊥"));
+    }
+}
@@ -0,0 +1,229 @@
+use std::{collections::{BTreeSet, HashMap}, fmt::Display};
+
+use serde_json::{json, Value};
+
+use crate::types::{ast::{Aexpr, Bexpr, Operator, Var}, span::Span};
+
+use super::{analyzers::generic_analyzer::GenericAnalyzer, arrays, types::{analyzer::StaticAnalyzer, domain::AbstractDomain, program::{Command, Label, Program}, state::AbstractState}};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlarmKind {
+    // The divisor may be 0
+    PossibleDivisionByZero,
+    // The divisor is always 0
+    DivisionByZero,
+    // The variable is not assigned on some path reaching the read
+    UninitializedRead(Var),
+    // The index may be outside the bounds of the array
+    PossibleIndexOutOfBounds(Var),
+    // The index is always outside the bounds of the array
+    IndexOutOfBounds(Var),
+}
+
+#[derive(Debug, Clone)]
+pub struct Alarm {
+    // Source label of the arc evaluating the expression
+    pub label: Label,
+    pub kind: AlarmKind,
+    // Location of the division, of the read or of the indexing
+    pub span: Span,
+}
+
+// Alarms raised by the commands leaving the reachable labels of the analysis result. initialized
+// are the variables with a value at the entry (the ones of the initial state)
+pub fn collect_alarms<B, S>(prog: &Program<B>, result: &HashMap<Label, S>, initialized: &BTreeSet<Var>) -> Vec<Alarm>
+    where B: AbstractDomain, S: AbstractState<B> {
+    let definitely_initialized = definitely_initialized(prog, initialized);
+    let mut alarms = vec![];
+    for (l, cmd, _) in &prog.arcs {
+        let state = &result[l];
+        if *state == S::bottom() {
+            continue;
+        }
+        let mut collector = AlarmCollector { label: *l, initialized: definitely_initialized[*l as usize].clone(), alarms: &mut alarms };
+        match cmd {
+            Command::Assignment(_, a) => { collector.aexpr::<B, S>(a, state.clone()); },
+            Command::Test(_, b) => collector.bexpr::<B, S>(b, state),
+            Command::Call(_, args) => {
+                args.iter().fold(state.clone(), |s, a| collector.aexpr::<B, S>(a, s).1);
+            },
+            Command::Return(..) => (),
+            Command::ArrayAssign(x, i, a, span) => {
+                collector.read(x, *span);
+                let (n, s) = collector.aexpr::<B, S>(i, state.clone());
+                let s = collector.bounds(x, i, n, s, *span);
+                collector.aexpr::<B, S>(a, s);
+            },
+            Command::NewArray(_, n) => { collector.aexpr::<B, S>(n, state.clone()); },
+        }
+    }
+    alarms.sort_by_key(|a| (a.span.start, a.label));
+    alarms.dedup_by(|a1, a2| a1.span == a2.span && a1.kind == a2.kind);
+    alarms
+}
+
+struct AlarmCollector<'a> {
+    label: Label,
+    // Variables assigned on every path, updated by the increments inside the expression
+    initialized: Option<BTreeSet<Var>>,
+    alarms: &'a mut Vec<Alarm>,
+}
+
+impl AlarmCollector<'_> {
+    fn read(&mut self, x: &Var, span: Span) {
+        if self.initialized.as_ref().is_some_and(|vars| !vars.contains(x)) {
+            self.alarms.push(Alarm { label: self.label, kind: AlarmKind::UninitializedRead(x.clone()), span });
+            // A single alarm for every read of x in the same expression
+            self.initialized.as_mut().unwrap().insert(x.clone());
+        }
+    }
+
+    // Same evaluation order and side effects of GenericAnalyzer::eval_aexpr
+    fn aexpr<B: AbstractDomain, S: AbstractState<B>>(&mut self, a: &Aexpr<B>, s: S) -> (B, S) {
+        match a {
+            Aexpr::Lit(_) => GenericAnalyzer::<B, S>::eval_aexpr(a, s),
+            Aexpr::Var(x, span) | Aexpr::PreOp(_, x, span) | Aexpr::PostOp(_, x, span) => {
+                self.read(x, *span);
+                GenericAnalyzer::<B, S>::eval_aexpr(a, s)
+            },
+            Aexpr::BinOp(op, a1, a2, span) => {
+                let (n1, s1) = self.aexpr(a1, s);
+                let (n2, s2) = self.aexpr(a2, s1);
+                if let Operator::Div = op {
+                    if n2 == B::from(0) {
+                        self.alarms.push(Alarm { label: self.label, kind: AlarmKind::DivisionByZero, span: *span });
+                    } else if n2.contains(0) {
+                        self.alarms.push(Alarm { label: self.label, kind: AlarmKind::PossibleDivisionByZero, span: *span });
+                    }
+                }
+                (B::abstract_operator(op, n1, n2), s2)
+            },
+            Aexpr::Call(_, args, _) => {
+                let s = args.iter().fold(s, |s, a| self.aexpr(a, s).1);
+                (B::top(), s)
+            },
+            Aexpr::Index(x, i, span) => {
+                self.read(x, *span);
+                let (n, s) = self.aexpr(i, s);
+                let s = self.bounds(x, i, n, s, *span);
+                (s.get(&arrays::cell_var(x)), s)
+            },
+            Aexpr::Len(x, span) => {
+                self.read(x, *span);
+                GenericAnalyzer::<B, S>::eval_aexpr(a, s)
+            },
+        }
+    }
+
+    // Alarm on the index i of x, whose value is n, then the state of the executions in bounds
+    fn bounds<B: AbstractDomain, S: AbstractState<B>>(&mut self, x: &Var, i: &Aexpr<B>, n: B, s: S, span: Span) -> S {
+        let in_bounds = arrays::in_bounds(x, i, n);
+        let out_of_bounds = Bexpr::Not(Box::new(in_bounds.clone()));
+        if GenericAnalyzer::<B, S>::eval_bexpr(&in_bounds, s.clone()) == S::bottom() {
+            self.alarms.push(Alarm { label: self.label, kind: AlarmKind::IndexOutOfBounds(x.clone()), span });
+        } else if GenericAnalyzer::<B, S>::eval_bexpr(&out_of_bounds, s.clone()) != S::bottom() {
+            self.alarms.push(Alarm { label: self.label, kind: AlarmKind::PossibleIndexOutOfBounds(x.clone()), span });
+        }
+        arrays::check_bounds(x, i, n, s)
+    }
+
+    fn bexpr<B: AbstractDomain, S: AbstractState<B>>(&mut self, b: &Bexpr<B>, s: &S) {
+        match b {
+            Bexpr::True | Bexpr::False => (),
+            Bexpr::Equal(a1, a2, _) | Bexpr::LessEq(a1, a2, _) | Bexpr::Less(a1, a2, _) | Bexpr::NotEqual(a1, a2, _) => {
+                let (_, s1) = self.aexpr(a1, s.clone());
+                self.aexpr(a2, s1);
+            },
+            Bexpr::Not(b) => self.bexpr(b, s),
+            Bexpr::And(b1, b2) | Bexpr::Or(b1, b2) => { self.bexpr(b1, s); self.bexpr(b2, s) },
+        }
+    }
+}
+
+// Forward must analysis of the assigned variables, None for the labels not reached by any path
+fn definitely_initialized<B: Clone>(prog: &Program<B>, initialized: &BTreeSet<Var>) -> Vec<Option<BTreeSet<Var>>> {
+    let mut vars: Vec<Option<BTreeSet<Var>>> = vec![None; prog.labels_num as usize];
+    vars[0] = Some(initialized.clone());
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (l1, cmd, l2) in &prog.arcs {
+            let Some(mut out) = vars[*l1 as usize].clone() else { continue };
+            if let Command::Assignment(x, _) | Command::Return(x, _) | Command::NewArray(x, _) = cmd {
+                out.insert(x.clone());
+            }
+            let new = match &vars[*l2 as usize] {
+                None => out,
+                Some(old) => old.intersection(&out).cloned().collect(),
+            };
+            if vars[*l2 as usize].as_ref() != Some(&new) {
+                vars[*l2 as usize] = Some(new);
+                changed = true;
+            }
+        }
+    }
+    vars
+}
+
+impl Display for AlarmKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlarmKind::PossibleDivisionByZero => write!(f, "possible division by zero"),
+            AlarmKind::DivisionByZero => write!(f, "division by zero"),
+            AlarmKind::UninitializedRead(x) => write!(f, "'{x}' may be read uninitialized"),
+            AlarmKind::PossibleIndexOutOfBounds(x) => write!(f, "index may be out of the bounds of '{x}'"),
+            AlarmKind::IndexOutOfBounds(x) => write!(f, "index always out of the bounds of '{x}'"),
+        }
+    }
+}
+
+impl Alarm {
+    // statement: source code of the statement raising the alarm
+    pub fn to_json(&self, statement: &str) -> Value {
+        let (kind, variable) = match &self.kind {
+            AlarmKind::PossibleDivisionByZero => ("possible-division-by-zero", None),
+            AlarmKind::DivisionByZero => ("division-by-zero", None),
+            AlarmKind::UninitializedRead(x) => ("uninitialized-read", Some(x)),
+            AlarmKind::PossibleIndexOutOfBounds(x) => ("possible-index-out-of-bounds", Some(x)),
+            AlarmKind::IndexOutOfBounds(x) => ("index-out-of-bounds", Some(x)),
+        };
+        let mut alarm = json!({
+            "kind": kind,
+            "label": self.label,
+            "line": self.span.start.0,
+            "column": self.span.start.1,
+            "statement": statement,
+        });
+        if let Some(x) = variable {
+            alarm["variable"] = json!(x);
+        }
+        alarm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::{domains::interval_domain::IntervalDomain, states::hashmap_state::HashMapState, test_utils::analyze, types::analyzer::IterationStrategy};
+
+    use super::*;
+
+    fn alarms(source: &str, initialized: &[&str]) -> Vec<(usize, AlarmKind)> {
+        let analysis = analyze::<IntervalDomain, HashMapState<IntervalDomain>>(source, IterationStrategy::WideningAndNarrowing { delay: 0 });
+        let initialized = initialized.iter().map(|x| x.to_string()).collect();
+        collect_alarms(&analysis.prog, &analysis.states, &initialized).into_iter().map(|a| (a.span.line(), a.kind)).collect()
+    }
+
+    #[test]
+    fn divisions_by_zero() {
+        let source = "x := [0,5];\ny := 10 / x;\nz := 10 / (x * 0);\nif 1 <= x then {w := 10 / x;} else {skip;}";
+        assert_eq!(alarms(source, &[]), [(2, AlarmKind::PossibleDivisionByZero), (3, AlarmKind::DivisionByZero)]);
+    }
+
+    #[test]
+    fn uninitialized_reads() {
+        let source = "if y <= 0 then {x := 1;} else {skip;}\nz := x + x;\nx := 2;\nz := x;";
+        assert_eq!(alarms(source, &["y"]), [(2, AlarmKind::UninitializedRead("x".to_string()))]);
+        assert_eq!(alarms(source, &[]), [(1, AlarmKind::UninitializedRead("y".to_string())), (2, AlarmKind::UninitializedRead("x".to_string()))]);
+    }
+}
@@ -1,12 +1,14 @@
 use crate::analyzer::types::program::{Command, Label};
 use crate::analyzer::types::{program::Program, state::AbstractState};
 use crate::analyzer::types::domain::AbstractDomain;
-use crate::analyzer::types::analyzer::{IterationStrategy, StaticAnalyzer};
+use crate::analyzer::types::analyzer::{IterationOrder, IterationStrategy, StaticAnalyzer};
+use crate::analyzer::wto::{flatten, weak_topological_order};
 use crate::types::ast::{PostOp, PreOp};
-use std::{collections::HashMap, marker::PhantomData};
+use std::{collections::{BTreeSet, HashMap}, marker::PhantomData};
+use iter_tools::Itertools;
 
 use crate::{types::ast::{Aexpr, Bexpr}, analyzer::printers::map_to_str};
-use crate::analyzer::advanced_tests;
+use crate::analyzer::{advanced_tests, arrays};
 pub struct GenericAnalyzer<D, B> {    
    domain: PhantomData<D>,
    abs_state: PhantomData<B>,
@@ -17,41 +19,53 @@ impl<B: AbstractDomain, D: AbstractState<B>> StaticAnalyzer<B,D> for GenericAnal
     fn eval_aexpr(a: &Aexpr<B>, mut s: D)-> (B, D) {
         match a {
             Aexpr::Lit(n) => (*n, s),
-            Aexpr::Var(x) => (s.get(x), s),
-            Aexpr::BinOp(op, a1, a2 ) => {
+            Aexpr::Var(x, _) => (s.get(x), s),
+            Aexpr::BinOp(op, a1, a2, _) => {
                 let (n1, s1) = Self::eval_aexpr(a1, s);
                 let (n2, s2) = Self::eval_aexpr(a2, s1);
                 let d = B::abstract_operator(op, n1, n2);
                 (d, s2)
             }
-            Aexpr::PreOp(PreOp::Inc, x) => {
+            Aexpr::PreOp(PreOp::Inc, x, _) => {
                 let d = s.get(x) + B::from(1);
                 s.set(x.to_string(), d);
                 (d, s)
             }
-            Aexpr::PreOp(PreOp::Dec, x) => {
+            Aexpr::PreOp(PreOp::Dec, x, _) => {
                 let d = s.get(x) - B::from(1);
                 s.set(x.to_string(), d);
                 (d, s)
             },
-            Aexpr::PostOp(PostOp::Inc, x) => {
+            Aexpr::PostOp(PostOp::Inc, x, _) => {
                 let d = s.get(x);
                 s.set(x.to_string(), d + B::from(1));
                 (d, s)
             },
-            Aexpr::PostOp(PostOp::Dec, x) =>{
+            Aexpr::PostOp(PostOp::Dec, x, _) =>{
                 let d = s.get(x);
                 s.set(x.to_string(), d - B::from(1));
                 (d, s)
             },
+            // The control flow graph has no calls in the expressions (see Command::Call), elsewhere
+            // only the arguments are evaluated and the result is unknown
+            Aexpr::Call(_, args, _) => {
+                let s = args.iter().fold(s, |s, a| Self::eval_aexpr(a, s).1);
+                (B::top(), s)
+            },
+            Aexpr::Index(x, i, _) => {
+                let (n, s) = Self::eval_aexpr(i, s);
+                let s = arrays::check_bounds(x, i, n, s);
+                (s.get(&arrays::cell_var(x)), s)
+            },
+            Aexpr::Len(x, _) => (s.get(&arrays::length_var(x)), s),
         }
     }
 
     fn eval_bexpr(b: &Bexpr<B>, s: D)-> D {
-        advanced_tests::eval_bexpr(b, s)
+        advanced_tests::eval_bexpr(b, s.assume(b))
     }
 
-    fn analyze(prog: Program<B>, init_state: D, iteration_strategy: IterationStrategy) -> HashMap<Label, D> {
+    fn analyze(prog: Program<B>, init_state: D, iteration_strategy: IterationStrategy, iteration_order: IterationOrder) -> HashMap<Label, D> {
         let mut all_state: HashMap<Label, D> = HashMap::new();
 
 
@@ -70,6 +84,10 @@ impl<B: AbstractDomain, D: AbstractState<B>> StaticAnalyzer<B,D> for GenericAnal
             println!("\nINITIAL STATES:\n{}\n", map_to_str(&all_state));
         }
 
+        if let IterationOrder::Worklist = iteration_order {
+            return Self::worklist(&prog, &init_state, all_state, iteration_strategy, print_iters_enabled);
+        }
+
         if let IterationStrategy::Simple = iteration_strategy {            
             let mut new_all_state = Self::make_iteration(&prog, &init_state, all_state.clone(), StepType::NormalStep);
             while new_all_state != all_state {
@@ -80,16 +98,21 @@ impl<B: AbstractDomain, D: AbstractState<B>> StaticAnalyzer<B,D> for GenericAnal
                 new_all_state = Self::make_iteration(&prog,&init_state,  all_state.clone(), StepType::NormalStep); 
             }
         }else {
-            let mut new_all_state = Self::make_iteration(&prog,&init_state,  all_state.clone(), StepType::WideningStep);
+            let delay = match iteration_strategy {
+                IterationStrategy::Widening { delay } | IterationStrategy::WideningAndNarrowing { delay } => delay,
+                IterationStrategy::Simple => 0,
+            };
+            let mut visits = HashMap::new();
+            let mut new_all_state = Self::make_iteration(&prog,&init_state,  all_state.clone(), StepType::WideningStep { delay, visits: &mut visits });
             while new_all_state != all_state {
                 all_state = new_all_state;
                 if print_iters_enabled {
                     println!("ITERATION (∇) {}:\n{:?}\n",iteration_num, map_to_str(&all_state)); iteration_num+=1;
                 }
-                new_all_state = Self::make_iteration(&prog,&init_state,  all_state.clone(), StepType::WideningStep); 
+                new_all_state = Self::make_iteration(&prog,&init_state,  all_state.clone(), StepType::WideningStep { delay, visits: &mut visits }); 
             }
 
-            if let IterationStrategy::WideningAndNarrowing = iteration_strategy {      
+            if let IterationStrategy::WideningAndNarrowing { .. } = iteration_strategy {      
                 let mut new_all_state = Self::make_iteration(&prog,&init_state,  all_state.clone(), StepType::NarrowingStep);                
                 while new_all_state != all_state {
                     all_state = new_all_state;
@@ -106,44 +129,159 @@ impl<B: AbstractDomain, D: AbstractState<B>> StaticAnalyzer<B,D> for GenericAnal
 
 
 
-enum StepType {
+enum StepType<'a> {
     NormalStep,
-    WideningStep,
+    // The first `delay` visits of every widening point (counted in `visits`) use the join
+    WideningStep { delay: u32, visits: &'a mut HashMap<Label, u32> },
     NarrowingStep
 }
 
 impl<D: AbstractDomain, B: AbstractState<D>> GenericAnalyzer<D,B>{
-    fn make_iteration(prog: &Program<D>, init_state: &B, states: HashMap<Label, B>, step_type: StepType) -> HashMap<Label, B>{
+    fn make_iteration(prog: &Program<D>, init_state: &B, states: HashMap<Label, B>, mut step_type: StepType) -> HashMap<Label, B>{
         let mut all_states: HashMap<Label, B> = HashMap::new();
         for i in 0..=(prog.labels_num-1) {
-            let arcs = prog.get_entering_arcs(i);
-            let mut new_state = if i == 0{
-                init_state.clone()
-            }else{
+            let new_state = Self::compute_label(prog, init_state, &states, i, &mut step_type);
+            all_states.insert(i, new_state);
+        };
+        all_states
+    }
+
+    // New abstract state of the label i from the states of its predecessors
+    fn compute_label(prog: &Program<D>, init_state: &B, states: &HashMap<Label, B>, i: Label, step_type: &mut StepType) -> B{
+        let arcs = prog.get_entering_arcs(i);
+        let mut new_state = if i == 0{
+            init_state.clone()
+        }else{
+            B::bottom()
+        };
+        for (l,cmd,_) in arcs {
+            match  states.get(l) {
+                Some(s) => new_state = new_state.lub(&Self::apply_cmd(cmd, s)),
+                None => panic!("Missing AbsState for label {l}"),
+            };
+        }
+
+        Self::apply_step(prog, states, i, new_state, step_type)
+    }
+
+    // Widening/narrowing of the new state of a widening point with the old one
+    fn apply_step(prog: &Program<D>, states: &HashMap<Label, B>, i: Label, new_state: B, step_type: &mut StepType) -> B{
+        if !prog.widening_points.contains(&i) {
+            return new_state;
+        }
+        let old_state = states
+            .get(&i)
+            .expect(&format!("Missing AbsState for label {i}"))
+            .clone();
+        match step_type {
+            StepType::NormalStep => new_state,
+            StepType::WideningStep { delay, visits } => {
+                let visit = visits.entry(i).or_insert(0);
+                *visit += 1;
+                if *visit <= *delay { old_state.lub(&new_state) } else { old_state.widening(new_state) }
+            },
+            StepType::NarrowingStep => old_state.narrowing(new_state),
+        }
+    }
+
+    // Backward analysis from the postcondition at the exit label: the state of every label is intersected
+    // with the forward result, the state at label 0 is a necessary precondition to reach the exit in post
+    pub fn backward_analyze(prog: &Program<D>, forward: &HashMap<Label, B>, post: B, iteration_strategy: IterationStrategy) -> HashMap<Label, B>{
+        let mut all_state: HashMap<Label, B> = (0..prog.labels_num).map(|i| (i, B::bottom())).collect();
+        let (delay, narrowing) = match iteration_strategy {
+            IterationStrategy::Simple => (None, false),
+            IterationStrategy::Widening { delay } => (Some(delay), false),
+            IterationStrategy::WideningAndNarrowing { delay } => (Some(delay), true),
+        };
+        let mut visits = HashMap::new();
+        let mut step_type = match delay {
+            Some(delay) => StepType::WideningStep { delay, visits: &mut visits },
+            None => StepType::NormalStep,
+        };
+        let mut new_all_state = Self::make_backward_iteration(prog, forward, &post, &all_state, &mut step_type);
+        while new_all_state != all_state {
+            all_state = new_all_state;
+            new_all_state = Self::make_backward_iteration(prog, forward, &post, &all_state, &mut step_type);
+        }
+        if narrowing {
+            let mut new_all_state = Self::make_backward_iteration(prog, forward, &post, &all_state, &mut StepType::NarrowingStep);
+            while new_all_state != all_state {
+                all_state = new_all_state;
+                new_all_state = Self::make_backward_iteration(prog, forward, &post, &all_state, &mut StepType::NarrowingStep);
+            }
+        }
+        all_state
+    }
+
+    fn make_backward_iteration(prog: &Program<D>, forward: &HashMap<Label, B>, post: &B, states: &HashMap<Label, B>, step_type: &mut StepType) -> HashMap<Label, B>{
+        let exit = prog.labels_num - 1;
+        (0..prog.labels_num).map(|i| {
+            let mut new_state = if i == exit {
+                post.clone().glb(&forward[&exit])
+            } else {
                 B::bottom()
             };
-            for (l,cmd,_) in arcs {
-                match  states.get(l) {
-                    Some(s) => new_state = new_state.lub(&Self::apply_cmd(cmd, s)),
-                    None => panic!("Missing AbsState for label {l}"),
+            for (_, cmd, l2) in prog.arcs.iter().filter(|(l1, _, _)| *l1 == i) {
+                let pre = match cmd {
+                    Command::Assignment(x, a) => advanced_tests::backward_assign(x, a, &states[l2], forward[&i].clone()),
+                    Command::Test(_, b) => advanced_tests::backward_test(b, &states[l2], forward[&i].clone()),
+                    Command::Call(_, args) => advanced_tests::backward_call(args, &states[l2], forward[&i].clone()),
+                    Command::Return(x, _) => advanced_tests::backward_assign(x, &Aexpr::Lit(D::top()), &states[l2], forward[&i].clone()),
+                    Command::ArrayAssign(x, index, a, _) =>
+                        advanced_tests::backward_havoc(&[arrays::cell_var(x)], &[index, a], &states[l2], forward[&i].clone()),
+                    Command::NewArray(x, n) =>
+                        advanced_tests::backward_havoc(&[arrays::cell_var(x), arrays::length_var(x)], &[n], &states[l2], forward[&i].clone()),
                 };
+                new_state = new_state.lub(&pre);
             }
+            (i, Self::apply_step(prog, states, i, new_state, step_type))
+        }).collect()
+    }
 
-            if prog.widening_points.contains(&i) {
-                let old_state = states
-                    .get(&i)
-                    .expect(&format!("Missing AbsState for label {i}"))
-                    .clone();
-                new_state = match step_type {
-                    StepType::NormalStep => new_state,
-                    StepType::WideningStep => old_state.widening(new_state),
-                    StepType::NarrowingStep => old_state.narrowing(new_state),
-                }
-            }
+    // Chaotic iteration: the labels are taken from the worklist following the weak topological order
+    // and only the successors of the labels that changed are recomputed
+    fn worklist(prog: &Program<D>, init_state: &B, mut states: HashMap<Label, B>, iteration_strategy: IterationStrategy, print_iters_enabled: bool) -> HashMap<Label, B>{
+        let order = weak_topological_order(prog);
+        if print_iters_enabled {
+            println!("WEAK TOPOLOGICAL ORDER: {}\n", order.iter().join(" "));
+        }
+        let position: HashMap<Label, usize> = flatten(&order).into_iter().enumerate().map(|(p, l)| (l, p)).collect();
+        let mut iteration_num = 1;
 
-            all_states.insert(i, new_state);
+        let (delay, narrowing) = match iteration_strategy {
+            IterationStrategy::Simple => (None, false),
+            IterationStrategy::Widening { delay } => (Some(delay), false),
+            IterationStrategy::WideningAndNarrowing { delay } => (Some(delay), true),
         };
-        all_states
+        let mut visits = HashMap::new();
+        let (mut step_type, symbol) = match delay {
+            Some(delay) => (StepType::WideningStep { delay, visits: &mut visits }, " (∇)"),
+            None => (StepType::NormalStep, ""),
+        };
+        Self::stabilize(prog, init_state, &mut states, &position, &mut step_type, symbol, print_iters_enabled, &mut iteration_num);
+        if narrowing {
+            Self::stabilize(prog, init_state, &mut states, &position, &mut StepType::NarrowingStep, " (Δ)", print_iters_enabled, &mut iteration_num);
+        }
+        states
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn stabilize(prog: &Program<D>, init_state: &B, states: &mut HashMap<Label, B>, position: &HashMap<Label, usize>,
+                 step_type: &mut StepType, symbol: &str, print_iters_enabled: bool, iteration_num: &mut u32) {
+        let mut worklist: BTreeSet<(usize, Label)> = position.iter().map(|(l, p)| (*p, *l)).collect();
+        while let Some((_, l)) = worklist.pop_first() {
+            let new_state = Self::compute_label(prog, init_state, states, l, step_type);
+            if new_state == states[&l] {
+                continue;
+            }
+            if print_iters_enabled {
+                println!("ITERATION{symbol} {iteration_num}, label {l}:\n{new_state}\n"); *iteration_num+=1;
+            }
+            states.insert(l, new_state);
+            for (_, _, next) in prog.arcs.iter().filter(|(l1, _, _)| *l1 == l) {
+                worklist.insert((position[next], *next));
+            }
+        }
     }
 
 
@@ -152,15 +290,91 @@ impl<D: AbstractDomain, B: AbstractState<D>> GenericAnalyzer<D,B>{
         match cmd {
             Command::Assignment(x, a) => {
                 let (aexpr_dom, mut s2) = Self::eval_aexpr(a, state);
-                s2.set(x.to_string(), aexpr_dom);
+                s2.assign(x.to_string(), a, aexpr_dom);
                 state = s2
             },
-            Command::Test(b) => {
+            Command::Test(_, b) => {
                 state = Self::eval_bexpr(b, state);
             },
+            Command::Call(_, args) => {
+                for a in args {
+                    state = Self::eval_aexpr(a, state).1;
+                }
+            },
+            // Without the summary of the procedure (see interprocedural::with_summaries) the result is unknown
+            Command::Return(x, _) => {
+                state.assign(x.to_string(), &Aexpr::Lit(D::top()), D::top());
+            },
+            Command::ArrayAssign(x, i, a, _) => {
+                let (n, s) = Self::eval_aexpr(i, state);
+                let s = arrays::check_bounds(x, i, n, s);
+                let (v, s) = Self::eval_aexpr(a, s);
+                state = arrays::write_element(x, v, s);
+            },
+            Command::NewArray(x, n) => {
+                state = arrays::new_array(x, n, state);
+            },
         }
         state
     }
 
 }
 
+
+#[cfg(test)]
+mod tests {
+    use crate::{analyzer::{domains::{extended_num::ExtendedNum, interval_domain::IntervalDomain}, states::hashmap_state::HashMapState, test_utils::analyze}, parser::parse_string};
+
+    use super::*;
+
+    type Interval = HashMapState<IntervalDomain>;
+
+    fn loop_invariant(source: &str, iteration_strategy: IterationStrategy) -> String {
+        let analysis = analyze::<IntervalDomain, Interval>(source, iteration_strategy);
+        analysis.states[&analysis.prog.widening_points[0]].to_string()
+    }
+
+    #[test]
+    fn delayed_widening() {
+        let source = "x := 0; while x <= 2 do x := x + 1;";
+        assert_eq!(loop_invariant(source, IterationStrategy::Widening { delay: 0 }), "{x: [0,+inf]}");
+        // Every label is recomputed at each round, so the head is stable after a few joins
+        assert_eq!(loop_invariant(source, IterationStrategy::Widening { delay: 20 }), "{x: [0,3]}");
+    }
+
+    fn both_orders(source: &str, strategy: IterationStrategy) -> (HashMap<Label, Interval>, HashMap<Label, Interval>) {
+        let prog = GenericAnalyzer::<IntervalDomain, Interval>::init(parse_string(source.to_string()).unwrap().main);
+        let round_robin = GenericAnalyzer::analyze(prog.clone(), Interval::top(), strategy, IterationOrder::RoundRobin);
+        let worklist = GenericAnalyzer::analyze(prog, Interval::top(), strategy, IterationOrder::Worklist);
+        (round_robin, worklist)
+    }
+
+    #[test]
+    fn worklist_and_round_robin_agree() {
+        let nested = "i := 0; s := 0; while i <= 10 do { j := 0; while j <= i do { s := s + j; j := j + 1; } i := i + 1; }";
+        for strategy in [IterationStrategy::Widening { delay: 0 }, IterationStrategy::WideningAndNarrowing { delay: 1 }] {
+            let (round_robin, worklist) = both_orders(nested, strategy);
+            assert_eq!(round_robin, worklist);
+        }
+        let (round_robin, worklist) = both_orders("x := 1; if x <= 0 then y := 1; else y := 2;", IterationStrategy::Simple);
+        assert_eq!(round_robin, worklist);
+    }
+
+    // Value of x in the necessary precondition at the entry to reach the exit in post
+    fn precondition(source: &str, post: &str) -> IntervalDomain {
+        let strategy = IterationStrategy::WideningAndNarrowing { delay: 0 };
+        let analysis = analyze::<IntervalDomain, Interval>(source, strategy);
+        let backward = GenericAnalyzer::backward_analyze(&analysis.prog, &analysis.states, post.parse().unwrap(), strategy);
+        backward[&0].get("x")
+    }
+
+    #[test]
+    fn necessary_preconditions() {
+        let n = |l, u| IntervalDomain::new(ExtendedNum::Num(l), ExtendedNum::Num(u));
+        let up_to = |u| IntervalDomain::new(ExtendedNum::NegInf, ExtendedNum::Num(u));
+        assert_eq!(precondition("y := x + 1; if y <= 0 then z := 1; else z := 2;", "z:[1,1]"), up_to(-1));
+        assert_eq!(precondition("y := x + 1; if y <= 0 then z := 1; else z := 2;", "z:[3,3]"), IntervalDomain::Bottom);
+        assert_eq!(precondition("while x <= 9 do x := x + 1;", "x:[10,10]"), up_to(10));
+        assert_eq!(precondition("assume 0 <= x and x <= 20; y := x * 2;", "y:[0,10]"), n(0, 5));
+    }
+}
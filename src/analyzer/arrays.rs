@@ -0,0 +1,73 @@
+use crate::types::{ast::{Aexpr, Bexpr, Var}, span::Span};
+
+use super::{analyzers::generic_analyzer::GenericAnalyzer, linear_expr::aexpr_side_effects, types::{analyzer::StaticAnalyzer, domain::{AbstractDomain, Interval}, state::AbstractState}};
+
+
+// Array smashing: every array a is abstracted by two variables of the abstract state, a[] with all
+// its elements joined in a single abstract value and len(a) with its length. The names are not valid
+// identifiers, so they cannot clash with the variables of the program
+pub fn cell_var(a: &str) -> Var {
+    format!("{a}[]")
+}
+
+pub fn length_var(a: &str) -> Var {
+    format!("len({a})")
+}
+
+// The array abstracted by the variable x, if x is one of the two variables of an array
+pub fn array_of(x: &str) -> Option<&str> {
+    x.strip_suffix("[]").or_else(|| x.strip_prefix("len(")?.strip_suffix(')'))
+}
+
+// Test 0 <= i and i < len(a) on the index i, whose value is n. An index with side effects
+// cannot be evaluated again, so it is replaced by its value
+pub fn in_bounds<B: AbstractDomain>(a: &str, i: &Aexpr<B>, n: B) -> Bexpr<B> {
+    let i = if aexpr_side_effects(i) { Aexpr::Lit(n) } else { i.clone() };
+    Bexpr::And(
+        Box::new(Bexpr::LessEq(Box::new(Aexpr::Lit(B::from(0))), Box::new(i.clone()), Span::default())),
+        Box::new(Bexpr::Less(Box::new(i), Box::new(Aexpr::Len(a.to_string(), Span::default())), Span::default())),
+    )
+}
+
+// The execution goes on only when the index is in bounds, the others stop with a runtime error
+pub fn check_bounds<B: AbstractDomain, S: AbstractState<B>>(a: &str, i: &Aexpr<B>, n: B, s: S) -> S {
+    GenericAnalyzer::<B, S>::eval_bexpr(&in_bounds(a, i, n), s)
+}
+
+// Weak update of the elements with v: a single element changes, so the old values are kept
+pub fn write_element<B: AbstractDomain, S: AbstractState<B>>(a: &str, v: B, mut s: S) -> S {
+    let cell = cell_var(a);
+    let elems = s.get(&cell).lub(v);
+    s.set(cell, elems);
+    s
+}
+
+// a := array(n), the old array is replaced by n zeros (only the executions with n >= 0 go on)
+pub fn new_array<B: AbstractDomain, S: AbstractState<B>>(a: &str, n: &Aexpr<B>, s: S) -> S {
+    let (len, mut s) = GenericAnalyzer::<B, S>::eval_aexpr(n, s);
+    let len_var = length_var(a);
+    s.assign(len_var.clone(), n, len);
+    s.refine(&len_var, B::from(Interval::OpenRight(0)));
+    s.set(cell_var(a), B::from(0));
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::analyzer::{alarms::{collect_alarms, AlarmKind}, domains::interval_domain::IntervalDomain, states::hashmap_state::HashMapState, test_utils::analyze, types::analyzer::IterationStrategy};
+
+    #[test]
+    fn smashed_arrays_and_bounds() {
+        let source = "a := array(3);\na[0] := 5;\nx := a[1] + len(a);\ni := [0,5];\na[i] := 7;\ny := a[i];\nz := a[4];";
+        let analysis = analyze::<IntervalDomain, HashMapState<IntervalDomain>>(source, IterationStrategy::Simple);
+        let states = (0..analysis.prog.labels_num).map(|l| analysis.states[&l].to_string()).collect::<Vec<_>>();
+        assert_eq!(states[3], "{a[]: [0,5], len(a): [3,3], x: [3,8]}");
+        // Only the executions with the index in bounds go on after the weak update
+        assert_eq!(states[6], "{a[]: [0,7], i: [0,2], len(a): [3,3], x: [3,8], y: [0,7]}");
+        assert_eq!(states[7], "⊥");
+        let alarms = collect_alarms(&analysis.prog, &analysis.states, &BTreeSet::new()).into_iter().map(|a| (a.span.line(), a.kind)).collect::<Vec<_>>();
+        assert_eq!(alarms, [(5, AlarmKind::PossibleIndexOutOfBounds("a".to_string())), (7, AlarmKind::IndexOutOfBounds("a".to_string()))]);
+    }
+}
@@ -0,0 +1,77 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::types::{ast::Bexpr, span::Span};
+
+use super::{analyzers::generic_analyzer::GenericAnalyzer, types::{analyzer::StaticAnalyzer, domain::AbstractDomain, program::{Command, Label, Program, TestKind}, state::AbstractState}};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AssertionStatus {
+    // Holds in every state reaching the assertion (also when it is unreachable)
+    Proved,
+    // Fails in every state reaching the assertion
+    Violated,
+    Unknown,
+}
+
+pub struct AssertionResult {
+    pub label: Label,
+    // Location of the assert statement
+    pub span: Span,
+    pub status: AssertionStatus,
+}
+
+// Classification of the assertions from the abstract state at their label: b is proved if the state
+// refined with not b is bottom and violated if the state refined with b is bottom
+pub fn check_assertions<B, S>(prog: &Program<B>, result: &HashMap<Label, S>) -> Vec<AssertionResult>
+    where B: AbstractDomain, S: AbstractState<B> {
+    let mut assertions = prog.arcs.iter()
+        .filter_map(|(l, cmd, _)| match cmd {
+            Command::Test(TestKind::Assert(span), b) => Some((*l, *span, b)),
+            _ => None,
+        })
+        .map(|(label, span, b)| {
+            let state = &result[&label];
+            let holds = GenericAnalyzer::<B, S>::eval_bexpr(b, state.clone()) != S::bottom();
+            let fails = GenericAnalyzer::<B, S>::eval_bexpr(&Bexpr::Not(Box::new(b.clone())), state.clone()) != S::bottom();
+            let status = match (holds, fails) {
+                (_, false) => AssertionStatus::Proved,
+                (false, true) => AssertionStatus::Violated,
+                (true, true) => AssertionStatus::Unknown,
+            };
+            AssertionResult { label, span, status }
+        })
+        .collect::<Vec<_>>();
+    assertions.sort_by_key(|a| (a.span.start, a.label));
+    assertions
+}
+
+impl Display for AssertionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssertionStatus::Proved => write!(f, "proved"),
+            AssertionStatus::Violated => write!(f, "violated"),
+            AssertionStatus::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::{domains::interval_domain::IntervalDomain, states::hashmap_state::HashMapState, test_utils::analyze, types::analyzer::IterationStrategy};
+
+    use super::*;
+
+    fn statuses(source: &str) -> Vec<(usize, AssertionStatus)> {
+        let analysis = analyze::<IntervalDomain, HashMapState<IntervalDomain>>(source, IterationStrategy::WideningAndNarrowing { delay: 0 });
+        check_assertions(&analysis.prog, &analysis.states).iter().map(|a| (a.span.line(), a.status)).collect()
+    }
+
+    #[test]
+    fn assertions_are_classified() {
+        let source = "assume 0 <= x and x <= 10;\nassert x <= 10;\nassert x <= 5;\nassert 11 <= x;";
+        assert_eq!(statuses(source), [(2, AssertionStatus::Proved), (3, AssertionStatus::Unknown), (4, AssertionStatus::Violated)]);
+        // The assertions after a failed assumption are never reached
+        assert_eq!(statuses("x := 1;\nassume x <= 0;\nassert x <= 0 and 0 <= x;"), [(3, AssertionStatus::Proved)]);
+    }
+}
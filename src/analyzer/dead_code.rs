@@ -0,0 +1,91 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::types::span::Span;
+
+use super::{analyzers::generic_analyzer::GenericAnalyzer, types::{analyzer::StaticAnalyzer, domain::AbstractDomain, program::{Command, Label, Program, TestKind}, state::AbstractState}};
+
+
+// Branches of the if/while statements that are never taken although the statement is reachable,
+// as (span of the statement, taken) like in TestKind::Guard:
+// - if: taken for the then branch, not taken for the else branch
+// - while: taken for a body that never runs, not taken for a loop that never terminates
+pub type DeadBranches = HashSet<(Span, bool)>;
+
+// Dead code of the program: the branches never taken and the statements never reached, like the
+// ones after a loop that never terminates (by the span of the statement)
+#[derive(Debug, Default)]
+pub struct DeadCode {
+    pub branches: DeadBranches,
+    pub unreachable: HashSet<Span>,
+}
+
+impl DeadCode {
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty() && self.unreachable.is_empty()
+    }
+}
+
+pub fn find_dead_code<B, S>(prog: &Program<B>, result: &HashMap<Label, S>) -> DeadCode
+    where B: AbstractDomain, S: AbstractState<B> {
+    DeadCode { branches: find_dead_branches(prog, result), unreachable: find_unreachable_statements(prog, result) }
+}
+
+fn find_dead_branches<B, S>(prog: &Program<B>, result: &HashMap<Label, S>) -> DeadBranches
+    where B: AbstractDomain, S: AbstractState<B> {
+    // The same statement can have many copies in the program (repeat-until duplicates its body),
+    // so a branch is dead only if it is dead in every reachable copy
+    let mut alive: HashMap<(Span, bool), bool> = HashMap::new();
+    for (l, cmd, _) in &prog.arcs {
+        let Command::Test(TestKind::Guard { stm, taken }, b) = cmd else { continue };
+        let state = &result[l];
+        if *state == S::bottom() {
+            continue;
+        }
+        let taken_here = GenericAnalyzer::<B, S>::eval_bexpr(b, state.clone()) != S::bottom();
+        *alive.entry((*stm, *taken)).or_default() |= taken_here;
+    }
+    alive.into_iter()
+        .filter(|(_, alive)| !alive)
+        .map(|(branch, _)| branch)
+        .collect()
+}
+
+// The statements whose entry is bottom, in every copy as for the branches
+fn find_unreachable_statements<B, S>(prog: &Program<B>, result: &HashMap<Label, S>) -> HashSet<Span>
+    where B: AbstractDomain, S: AbstractState<B> {
+    let mut reached: HashMap<Span, bool> = HashMap::new();
+    for stm in &prog.statements {
+        *reached.entry(stm.span).or_default() |= result.get(&stm.entry).is_some_and(|s| *s != S::bottom());
+    }
+    reached.into_iter()
+        .filter(|(_, reached)| !reached)
+        .map(|(span, _)| span)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::{domains::interval_domain::IntervalDomain, states::hashmap_state::HashMapState, test_utils::analyze, types::analyzer::IterationStrategy};
+
+    use super::*;
+
+    #[test]
+    fn dead_branches_and_unreachable_statements() {
+        let source = "x := 1;\nif x <= 0 then {y := 1;} else {y := 2;}\nwhile 0 <= x do {x := x + 1;}\nz := 3;";
+        let analysis = analyze::<IntervalDomain, HashMapState<IntervalDomain>>(source, IterationStrategy::WideningAndNarrowing { delay: 0 });
+        let dead = find_dead_code(&analysis.prog, &analysis.states);
+        let mut branches = dead.branches.iter().map(|(span, taken)| (span.line(), *taken)).collect::<Vec<_>>();
+        branches.sort();
+        assert_eq!(branches, [(2, true), (3, false)]);
+        let mut unreachable = dead.unreachable.iter().map(|span| (span.line(), span.start.1)).collect::<Vec<_>>();
+        unreachable.sort();
+        assert_eq!(unreachable, [(2, 17), (4, 1)]);
+    }
+
+    #[test]
+    fn live_program() {
+        let source = "x := [0,3];\nif x <= 1 then {y := 1;} else {y := 2;}\nwhile x <= 5 do {x := x + 1;}";
+        let analysis = analyze::<IntervalDomain, HashMapState<IntervalDomain>>(source, IterationStrategy::WideningAndNarrowing { delay: 0 });
+        assert!(find_dead_code(&analysis.prog, &analysis.states).is_empty());
+    }
+}
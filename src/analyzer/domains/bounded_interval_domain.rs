@@ -2,7 +2,9 @@ use std::{ cmp::{max, min, Ordering,}, fmt::Display, ops::{Add, Div, Mul, Sub},
 use iter_tools::Itertools;
 use once_cell::sync::OnceCell;
 
-use crate::{analyzer::types::domain::{AbstractDomain, Interval}, types::ast::Num};
+use crate::{analyzer::{thresholds::{is_widened_bound, threshold_above, threshold_below}, types::domain::{AbstractDomain, Interval}}, types::ast::Num};
+
+use serde_json::{json, Value};
 
 use super::extended_num::ExtendedNum;
 
@@ -146,6 +148,22 @@ impl AbstractDomain for BoundedIntervalDomain{
     fn bottom() -> Self { BoundedIntervalDomain::Bottom }
     fn top() -> Self { BoundedIntervalDomain::Top }
 
+    fn to_json(&self) -> Value {
+        match self {
+            BoundedIntervalDomain::Bottom => Value::Null,
+            BoundedIntervalDomain::Top => json!({"lower": "-inf", "upper": "inf"}),
+            BoundedIntervalDomain::Range(l, u) => json!({"lower": l.to_json(), "upper": u.to_json()}),
+        }
+    }
+
+    fn contains(&self, n: Num) -> bool {
+        match self {
+            BoundedIntervalDomain::Top => true,
+            BoundedIntervalDomain::Bottom => false,
+            BoundedIntervalDomain::Range(l, u) => *l <= ExtendedNum::Num(n) && ExtendedNum::Num(n) <= *u,
+        }
+    }
+
     fn lub(self, other: Self) -> Self {
         match (self,other) {
             (BoundedIntervalDomain::Top,_) | (_, BoundedIntervalDomain::Top) => BoundedIntervalDomain::Top,
@@ -178,8 +196,8 @@ impl AbstractDomain for BoundedIntervalDomain{
             (BoundedIntervalDomain::Top, _) | (_, BoundedIntervalDomain::Top) => BoundedIntervalDomain::Top,
 
             (BoundedIntervalDomain::Range(a, b), BoundedIntervalDomain::Range(c, d)) =>{
-                let l = if a<=c { a } else { ExtendedNum::NegInf };
-                let u = if b>=d { b } else { ExtendedNum::PosInf };
+                let l = if a<=c { a } else { threshold_below(c) };
+                let u = if b>=d { b } else { threshold_above(d) };
                 BoundedIntervalDomain::new(l,u)
             }
         }
@@ -191,8 +209,8 @@ impl AbstractDomain for BoundedIntervalDomain{
             (BoundedIntervalDomain::Top, x) | (x, BoundedIntervalDomain::Top) => x,
 
             (BoundedIntervalDomain::Range(a, b), BoundedIntervalDomain::Range(c, d)) =>{
-                let l = if a == ExtendedNum::NegInf { c } else { a };
-                let u = if b == ExtendedNum::PosInf { d } else { b };
+                let l = if is_widened_bound(a) { c } else { a };
+                let u = if is_widened_bound(b) { d } else { b };
                 BoundedIntervalDomain::new(l,u)
             }
         }
@@ -1,8 +1,10 @@
 use std::{cmp::Ordering, fmt::Display, ops::{Add, Div, Mul, Sub}, str::FromStr};
 
-use crate::{analyzer::types::domain::{AbstractDomain, Interval}, types::ast::Num};
+use serde_json::{json, Value};
 
-#[derive(Debug,PartialEq,Clone,Copy)]
+use crate::{analyzer::types::domain::{num_to_json, AbstractDomain, Interval}, types::ast::Num};
+
+#[derive(Debug,Clone,Copy)]
 pub enum CongruenceDomain{
     Bottom,
     Congruence {a: Num, b: Num},
@@ -61,6 +63,19 @@ impl Div for CongruenceDomain  {
     }
 }
 
+// aZ+b and aZ+b' are the same congruence when b = b' mod a (the representation is not normalized)
+impl PartialEq for CongruenceDomain{
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (CongruenceDomain::Bottom, CongruenceDomain::Bottom) => true,
+            (CongruenceDomain::Congruence { a:0, b:b1 }, CongruenceDomain::Congruence { a:0, b:b2 }) => b1 == b2,
+            (CongruenceDomain::Congruence { a:a1, b:b1 }, CongruenceDomain::Congruence { a:a2, b:b2 }) =>
+                a1.abs() == a2.abs() && *a1 != 0 && (b1 - b2) % a1 == 0,
+            _ => false,
+        }
+    }
+}
+
 impl PartialOrd for CongruenceDomain{
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         match (self, other) {
@@ -151,6 +166,24 @@ impl AbstractDomain for CongruenceDomain{
         Self::Congruence{a:1, b:0}
     }
 
+    // aZ+b as {"modulus": |a|, "remainder": b mod a}, a single value has modulus 0
+    fn to_json(&self) -> Value {
+        match self {
+            CongruenceDomain::Bottom => Value::Null,
+            CongruenceDomain::Congruence { a: 0, b } => json!({"modulus": 0, "remainder": num_to_json(*b)}),
+            CongruenceDomain::Congruence { a, b } => json!({"modulus": num_to_json(a.abs()), "remainder": num_to_json(b.rem_euclid(*a))}),
+        }
+    }
+
+    // aZ + b, with a = 0 the single value b
+    fn contains(&self, n: Num) -> bool {
+        match self {
+            CongruenceDomain::Bottom => false,
+            CongruenceDomain::Congruence { a: 0, b } => n == *b,
+            CongruenceDomain::Congruence { a, b } => (n - b) % a == 0,
+        }
+    }
+
     fn lub(self, other: Self) -> Self {
         match (self,other) {
             (CongruenceDomain::Bottom, cong) |
@@ -165,8 +198,11 @@ impl AbstractDomain for CongruenceDomain{
     fn glb(self, other: Self) -> Self {
         match (self,other) {
             (CongruenceDomain::Bottom, _) | (_, CongruenceDomain::Bottom)  => CongruenceDomain::Bottom,
-            (CongruenceDomain::Congruence { a:1, b:_ }, cong) | (cong, CongruenceDomain::Congruence { a:1, b:_ })  
+            (CongruenceDomain::Congruence { a:1, b:_ }, cong) | (cong, CongruenceDomain::Congruence { a:1, b:_ })
                 => cong,
+            // A single value (the modular inverse below is not defined for a = 0)
+            (single @ CongruenceDomain::Congruence { a: 0, b }, cong) | (cong, single @ CongruenceDomain::Congruence { a: 0, b })
+                => if cong.contains(b) { single } else { CongruenceDomain::Bottom },
             (CongruenceDomain::Congruence { a: a1, b: b1 }, CongruenceDomain::Congruence { a: a2, b: b2 }) => {
                     let gcd = extended_euclidean_algorithm(a1, a2).0;
 
@@ -1,6 +1,8 @@
 use std::{cmp::Ordering, fmt::Display, ops::{Add, Div, Mul, Sub}, str::FromStr};
 
-use crate::types::ast::Num;
+use serde_json::{json, Value};
+
+use crate::{analyzer::types::domain::num_to_json, types::ast::Num};
 
 #[derive(Debug,PartialEq,Eq,Clone, Copy)]
 pub enum ExtendedNum{
@@ -110,10 +112,9 @@ impl Div for ExtendedNum{
 
     fn div(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
-            (ExtendedNum::PosInf, ExtendedNum::PosInf) => panic!(),
-            (ExtendedNum::PosInf, ExtendedNum::NegInf) => panic!(),
-            (ExtendedNum::NegInf, ExtendedNum::PosInf) => panic!(),
-            (ExtendedNum::NegInf, ExtendedNum::NegInf) => panic!(),
+            // Not defined, in the interval division it is always dominated by the
+            // infinite quotient of the same bound with the finite end of the divisor
+            (ExtendedNum::PosInf | ExtendedNum::NegInf, ExtendedNum::PosInf | ExtendedNum::NegInf) => ExtendedNum::Num(0),
             (ExtendedNum::PosInf, ExtendedNum::Num(n)) => match n.cmp(&0) {
                 Ordering::Less => ExtendedNum::NegInf,
                 Ordering::Equal => ExtendedNum::PosInf,
@@ -124,16 +125,8 @@ impl Div for ExtendedNum{
                 Ordering::Equal => ExtendedNum::NegInf,
                 Ordering::Greater => ExtendedNum::NegInf,
             },
-            (ExtendedNum::Num(n), ExtendedNum::PosInf) => match n.cmp(&0) {
-                Ordering::Less => ExtendedNum::NegInf,
-                Ordering::Equal => ExtendedNum::Num(0),
-                Ordering::Greater => ExtendedNum::PosInf,
-            },
-            (ExtendedNum::Num(n), ExtendedNum::NegInf) => match n.cmp(&0) {
-                Ordering::Less => ExtendedNum::PosInf,
-                Ordering::Equal => ExtendedNum::Num(0),
-                Ordering::Greater => ExtendedNum::NegInf,
-            },
+            // The limit of n/d for d going to infinity
+            (ExtendedNum::Num(_), ExtendedNum::PosInf | ExtendedNum::NegInf) => ExtendedNum::Num(0),
             (ExtendedNum::Num(n), ExtendedNum::Num(0)) => match n.cmp(&0)  {
                 Ordering::Less => ExtendedNum::NegInf,
                 Ordering::Equal => panic!(),
@@ -153,4 +146,29 @@ impl Div for ExtendedNum{
 //             ExtendedNum::Num(n) => write!(f, "{n}"),
 //         }
 //     }
-// }
\ No newline at end of file
+// }
+impl ExtendedNum {
+    // The infinities are "inf" and "-inf"
+    pub fn to_json(self) -> Value {
+        match self {
+            ExtendedNum::PosInf => json!("inf"),
+            ExtendedNum::NegInf => json!("-inf"),
+            ExtendedNum::Num(n) => num_to_json(n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finite_divided_by_infinite_is_zero() {
+        for n in [-7, 0, 10] {
+            assert_eq!(ExtendedNum::Num(n) / ExtendedNum::PosInf, ExtendedNum::Num(0));
+            assert_eq!(ExtendedNum::Num(n) / ExtendedNum::NegInf, ExtendedNum::Num(0));
+        }
+        assert_eq!(ExtendedNum::PosInf / ExtendedNum::Num(-2), ExtendedNum::NegInf);
+        assert_eq!(ExtendedNum::Num(7) / ExtendedNum::Num(-2), ExtendedNum::Num(-3));
+    }
+}
@@ -2,6 +2,7 @@ use std::{cmp::Ordering, fmt::Display, ops::{Add, Div, Mul, Sub}, str::FromStr};
 
 use crate::{analyzer::types::domain::Interval, types::ast::Num};
 use crate::analyzer::types::domain::AbstractDomain;
+use serde_json::{json, Value};
 
 
 #[derive(Debug,PartialEq,Clone,Copy)]
@@ -97,10 +98,23 @@ impl AbstractDomain for ExtendedSignDomain{
         ExtendedSignDomain{positive:false, zero: false, negative: false }
     }
 
+    // The signs included in the value
+    fn to_json(&self) -> Value {
+        if *self == Self::bottom() {
+            Value::Null
+        } else {
+            json!({"negative": self.negative, "zero": self.zero, "positive": self.positive})
+        }
+    }
+
     fn top() -> Self {
         ExtendedSignDomain{positive:true, zero: true, negative: true }
     }
 
+    fn contains(&self, n: Num) -> bool {
+        (n > 0 && self.positive) || (n == 0 && self.zero) || (n < 0 && self.negative)
+    }
+
     fn lub(self, other: Self) -> Self {
         ExtendedSignDomain{
             positive: self.positive || other.positive,
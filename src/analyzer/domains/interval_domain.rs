@@ -2,9 +2,10 @@ use std::{cmp::{max, min, Ordering}, fmt::Display, ops::{Add, Div, Mul, Sub}, st
 use iter_tools::Itertools as _;
 
 use crate::{analyzer::types::domain::Interval, types::ast::Num};
-use crate::analyzer::types::domain::AbstractDomain;
-use super::extended_num::ExtendedNum;
+use crate::analyzer::{thresholds::{add_thresholds, is_widened_bound, threshold_above, threshold_below}, types::domain::AbstractDomain};
+use serde_json::{json, Value};
 
+use super::extended_num::ExtendedNum;
 
 #[derive(Debug,PartialEq,Clone, Copy)]
 pub enum IntervalDomain{
@@ -13,7 +14,7 @@ pub enum IntervalDomain{
     Bottom,
 }
 impl IntervalDomain {
-    fn new(lower: ExtendedNum, upper: ExtendedNum) -> Self{
+    pub fn new(lower: ExtendedNum, upper: ExtendedNum) -> Self{
         if lower == ExtendedNum::NegInf && upper == ExtendedNum::PosInf {
             IntervalDomain::Top
         } else if lower > upper {
@@ -99,9 +100,40 @@ impl FromStr for IntervalDomain{
 }
 
 impl AbstractDomain for IntervalDomain{
+    // "thresholds=0,10,100"
+    fn set_config(config_string: Option<String>) -> Result<(), String> {
+        let Some(conf) = config_string else { return Ok(()) };
+        let thresholds = match conf.trim().strip_prefix("thresholds=") {
+            Some(list) => list,
+            None => return Err(format!("Expected \"thresholds=<n>,<n>,...\", found {conf}")),
+        };
+        let thresholds = thresholds.split(',')
+            .filter(|t| !t.trim().is_empty())
+            .map(|t| t.trim().parse::<Num>().map_err(|_| format!("Invalid threshold: {t}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        add_thresholds(thresholds);
+        Ok(())
+    }
+
     fn bottom() -> Self { IntervalDomain::Bottom }
     fn top() -> Self { IntervalDomain::Top }
 
+    fn to_json(&self) -> Value {
+        match self {
+            IntervalDomain::Bottom => Value::Null,
+            IntervalDomain::Top => json!({"lower": "-inf", "upper": "inf"}),
+            IntervalDomain::Range(l, u) => json!({"lower": l.to_json(), "upper": u.to_json()}),
+        }
+    }
+
+    fn contains(&self, n: Num) -> bool {
+        match self {
+            IntervalDomain::Top => true,
+            IntervalDomain::Bottom => false,
+            IntervalDomain::Range(l, u) => *l <= ExtendedNum::Num(n) && ExtendedNum::Num(n) <= *u,
+        }
+    }
+
     fn lub(self, other: Self) -> Self {
         match (self,other) {
             (IntervalDomain::Top,_) | (_, IntervalDomain::Top) => IntervalDomain::Top,
@@ -133,14 +165,27 @@ impl AbstractDomain for IntervalDomain{
             (IntervalDomain::Top, _) | (_, IntervalDomain::Top) => IntervalDomain::Top,
 
             (IntervalDomain::Range(a, b), IntervalDomain::Range(c, d)) =>{
-                let l = if a<=c { a } else { ExtendedNum::NegInf };
-                let u = if b>=d { b } else { ExtendedNum::PosInf };
+                let l = if a<=c { a } else { threshold_below(c) };
+                let u = if b>=d { b } else { threshold_above(d) };
                 IntervalDomain::new(l,u)
             }
         }
     }
 
-    
+    // The bounds that can come from the widening (infinite or thresholds) are refined,
+    // every bound is refined a finite number of times since the thresholds are finite
+    fn narrowing(self, other:Self) -> Self {
+        match(self, other){
+            (IntervalDomain::Bottom, _) | (_, IntervalDomain::Bottom) => IntervalDomain::Bottom,
+            (IntervalDomain::Top, x) | (x, IntervalDomain::Top) => x,
+
+            (IntervalDomain::Range(a, b), IntervalDomain::Range(c, d)) =>{
+                let l = if is_widened_bound(a) { c } else { a };
+                let u = if is_widened_bound(b) { d } else { b };
+                IntervalDomain::new(l,u)
+            }
+        }
+    }
 }
 
 impl Add for IntervalDomain{
@@ -229,4 +274,50 @@ impl Div for IntervalDomain{
             }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::{states::hashmap_state::HashMapState, test_utils::analyze, types::analyzer::IterationStrategy};
+
+    use super::*;
+
+    fn range(a: ExtendedNum, b: ExtendedNum) -> IntervalDomain {
+        IntervalDomain::new(a, b)
+    }
+
+    #[test]
+    fn widening_to_thresholds_and_narrowing() {
+        let n = |l, u| range(ExtendedNum::Num(l), ExtendedNum::Num(u));
+        IntervalDomain::set_config(Some("thresholds=-10,100".to_string())).unwrap();
+        assert_eq!(n(0, 1).widening(n(0, 2)), n(0, 100));
+        assert_eq!(n(0, 100).widening(n(-1, 101)), range(ExtendedNum::Num(-10), ExtendedNum::PosInf));
+        // Only the bounds that can come from a widening are refined
+        assert_eq!(n(0, 100).narrowing(n(0, 41)), n(0, 41));
+        assert_eq!(n(0, 50).narrowing(n(0, 41)), n(0, 50));
+        assert_eq!(range(ExtendedNum::NegInf, ExtendedNum::Num(5)).narrowing(n(1, 3)), n(1, 5));
+        assert!(IntervalDomain::set_config(Some("0,10".to_string())).is_err());
+    }
+
+    #[test]
+    fn analysis_with_widening_and_narrowing() {
+        let source = "n := [0,100]; x := 3; while x <= n do x += 1; y := 10 / x;";
+        let analysis = analyze::<IntervalDomain, HashMapState<IntervalDomain>>(source, IterationStrategy::WideningAndNarrowing { delay: 0 });
+        let exit = &analysis.states[&(analysis.prog.labels_num - 1)];
+        assert_eq!(exit.to_string(), "{n: [0,100], x: [3,101], y: [0,3]}");
+    }
+
+    #[test]
+    fn parse_intervals() {
+        assert_eq!("[1,10]".parse(), Ok(range(ExtendedNum::Num(1), ExtendedNum::Num(10))));
+        assert_eq!("[-inf,10]".parse(), Ok(range(ExtendedNum::NegInf, ExtendedNum::Num(10))));
+        assert_eq!("[-inf,inf]".parse(), Ok(IntervalDomain::Top));
+    }
+
+    #[test]
+    fn division_by_unbounded_divisor() {
+        let ten = IntervalDomain::from(10);
+        assert_eq!(ten / range(ExtendedNum::Num(3), ExtendedNum::PosInf), range(ExtendedNum::Num(0), ExtendedNum::Num(3)));
+        assert_eq!(ten / range(ExtendedNum::NegInf, ExtendedNum::Num(-2)), range(ExtendedNum::Num(-5), ExtendedNum::Num(0)));
+        assert_eq!(IntervalDomain::from(-10) / IntervalDomain::Top, IntervalDomain::Top);
+    }
+}
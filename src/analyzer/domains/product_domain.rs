@@ -0,0 +1,231 @@
+use std::{cmp::Ordering, fmt::{Debug, Display}, marker::PhantomData, ops::{Add, Div, Mul, Sub}, str::FromStr};
+
+use crate::{analyzer::types::domain::{AbstractDomain, Interval}, types::ast::{Num, Operator}};
+
+use serde_json::{json, Value};
+
+use super::{congruence_domain::CongruenceDomain, extended_num::ExtendedNum, interval_domain::IntervalDomain};
+
+
+// Reduction operator of a product: each component is refined with the information of the other one
+pub trait Reduction<A, B>: Debug + Clone + Copy + PartialEq {
+    fn reduce(a: A, b: B) -> (A, B);
+}
+
+// Plain cartesian product
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoReduction;
+
+impl<A, B> Reduction<A, B> for NoReduction {
+    fn reduce(a: A, b: B) -> (A, B) {
+        (a, b)
+    }
+}
+
+
+// Product of the domains A and B, reduced with R after every operation except widening and narrowing
+// (to keep their termination guarantees); a bottom component makes the whole product bottom
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Product<A, B, R = NoReduction>(pub A, pub B, PhantomData<R>);
+
+impl<A: AbstractDomain, B: AbstractDomain, R: Reduction<A, B>> Product<A, B, R> {
+    pub fn new(a: A, b: B) -> Self {
+        let (a, b) = R::reduce(a, b);
+        Self::unreduced(a, b)
+    }
+    fn unreduced(a: A, b: B) -> Self {
+        if a == A::bottom() || b == B::bottom() {
+            Product(A::bottom(), B::bottom(), PhantomData)
+        } else {
+            Product(a, b, PhantomData)
+        }
+    }
+}
+
+impl<A: AbstractDomain, B: AbstractDomain, R: Reduction<A, B>> AbstractDomain for Product<A, B, R> {
+    // The configurations of the two domains separated by '*', e.g. "[0,10]*" (an empty part is no configuration)
+    fn set_config(config_string: Option<String>) -> Result<(), String> {
+        let (conf_a, conf_b) = match &config_string {
+            Some(conf) => match conf.split_once('*') {
+                Some((a, b)) => (Some(a), Some(b)),
+                None => return Err(format!("Expected \"<conf>*<conf>\", found {conf}")),
+            },
+            None => (None, None),
+        };
+        let some_non_empty = |s: Option<&str>| s.filter(|s| !s.is_empty()).map(str::to_string);
+        A::set_config(some_non_empty(conf_a))?;
+        B::set_config(some_non_empty(conf_b))
+    }
+
+    fn bottom() -> Self { Product(A::bottom(), B::bottom(), PhantomData) }
+    fn top() -> Self { Product(A::top(), B::top(), PhantomData) }
+
+    fn contains(&self, n: Num) -> bool {
+        self.0.contains(n) && self.1.contains(n)
+    }
+
+    // The pair of the components
+    fn to_json(&self) -> Value {
+        if self.0 == A::bottom() {
+            Value::Null
+        } else {
+            json!([self.0.to_json(), self.1.to_json()])
+        }
+    }
+
+    fn lub(self, other: Self) -> Self {
+        Self::new(self.0.lub(other.0), self.1.lub(other.1))
+    }
+    fn glb(self, other: Self) -> Self {
+        Self::new(self.0.glb(other.0), self.1.glb(other.1))
+    }
+
+    fn backward_abstract_operator(op: &Operator, lhs: Self, rhs: Self, res: Self) -> (Self, Self) {
+        let (lhs_a, rhs_a) = A::backward_abstract_operator(op, lhs.0, rhs.0, res.0);
+        let (lhs_b, rhs_b) = B::backward_abstract_operator(op, lhs.1, rhs.1, res.1);
+        (Self::new(lhs_a, lhs_b), Self::new(rhs_a, rhs_b))
+    }
+
+    fn widening(self, other: Self) -> Self {
+        Self::unreduced(self.0.widening(other.0), self.1.widening(other.1))
+    }
+    fn narrowing(self, other: Self) -> Self {
+        Self::unreduced(self.0.narrowing(other.0), self.1.narrowing(other.1))
+    }
+}
+
+impl<A: AbstractDomain, B: AbstractDomain, R: Reduction<A, B>> PartialOrd for Product<A, B, R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.0.partial_cmp(&other.0)?, self.1.partial_cmp(&other.1)?) {
+            (o1, o2) if o1 == o2 => Some(o1),
+            (Ordering::Equal, o) | (o, Ordering::Equal) => Some(o),
+            _ => None,
+        }
+    }
+}
+
+impl<A: AbstractDomain, B: AbstractDomain, R: Reduction<A, B>> From<Num> for Product<A, B, R> {
+    fn from(value: Num) -> Self {
+        Self::new(A::from(value), B::from(value))
+    }
+}
+impl<A: AbstractDomain, B: AbstractDomain, R: Reduction<A, B>> From<Interval> for Product<A, B, R> {
+    fn from(value: Interval) -> Self {
+        Self::new(A::from(value), B::from(value))
+    }
+}
+
+impl<A: AbstractDomain, B: AbstractDomain, R> Display for Product<A, B, R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 == A::bottom() {
+            write!(f, "⊥")
+        } else {
+            write!(f, "({}, {})", self.0, self.1)
+        }
+    }
+}
+
+impl<A: AbstractDomain, B: AbstractDomain, R: Reduction<A, B>> FromStr for Product<A, B, R> {
+    type Err = String;
+
+    // "(<a>, <b>)", the separator is the first comma outside of brackets
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Expected \"(<value>, <value>)\", found {s}");
+        let inner = s.trim().strip_prefix('(').and_then(|s| s.strip_suffix(')')).ok_or_else(err)?;
+        let mut depth = 0;
+        let comma = inner.char_indices().find(|(_, c)| {
+            match c {
+                '[' | '(' => depth += 1,
+                ']' | ')' => depth -= 1,
+                _ => (),
+            }
+            *c == ',' && depth == 0
+        }).ok_or_else(err)?.0;
+        let a = inner[..comma].trim().parse::<A>().map_err(|_| err())?;
+        let b = inner[comma + 1..].trim().parse::<B>().map_err(|_| err())?;
+        Ok(Self::new(a, b))
+    }
+}
+
+macro_rules! product_operator {
+    ($trait:ident, $fn:ident) => {
+        impl<A: AbstractDomain, B: AbstractDomain, R: Reduction<A, B>> $trait for Product<A, B, R> {
+            type Output = Self;
+            fn $fn(self, rhs: Self) -> Self::Output {
+                Self::new(self.0.$fn(rhs.0), self.1.$fn(rhs.1))
+            }
+        }
+    };
+}
+product_operator!(Add, add);
+product_operator!(Sub, sub);
+product_operator!(Mul, mul);
+product_operator!(Div, div);
+
+
+// Reduction between intervals and congruences: the bounds are moved to the closest values of
+// the congruence class and the singletons are propagated to both the components
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntervalCongruence;
+
+impl Reduction<IntervalDomain, CongruenceDomain> for IntervalCongruence {
+    fn reduce(i: IntervalDomain, c: CongruenceDomain) -> (IntervalDomain, CongruenceDomain) {
+        let (l, u) = match i {
+            IntervalDomain::Bottom => return (i, CongruenceDomain::Bottom),
+            IntervalDomain::Top => (ExtendedNum::NegInf, ExtendedNum::PosInf),
+            IntervalDomain::Range(l, u) => (l, u),
+        };
+        let (a, b) = match c {
+            CongruenceDomain::Bottom => return (IntervalDomain::Bottom, c),
+            CongruenceDomain::Congruence { a, b } => (a.abs(), b),
+        };
+        let (l, u) = if a == 0 {
+            (l.max(ExtendedNum::Num(b)), u.min(ExtendedNum::Num(b)))
+        } else {
+            let snap_up = |n: ExtendedNum| match n {
+                ExtendedNum::Num(n) => ExtendedNum::Num(n + (b - n).rem_euclid(a)),
+                inf => inf,
+            };
+            let snap_down = |n: ExtendedNum| match n {
+                ExtendedNum::Num(n) => ExtendedNum::Num(n - (n - b).rem_euclid(a)),
+                inf => inf,
+            };
+            (snap_up(l), snap_down(u))
+        };
+        match (l, u) {
+            _ if l > u => (IntervalDomain::Bottom, CongruenceDomain::Bottom),
+            (ExtendedNum::Num(n), ExtendedNum::Num(m)) if n == m =>
+                (IntervalDomain::from(n), CongruenceDomain::Congruence { a: 0, b: n }),
+            _ => (IntervalDomain::new(l, u), c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Reduced = Product<IntervalDomain, CongruenceDomain, IntervalCongruence>;
+    type Unreduced = Product<IntervalDomain, CongruenceDomain>;
+
+    fn reduced(s: &str) -> Reduced {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn interval_congruence_reduction() {
+        assert_eq!(reduced("([0,10], 3Z+1)").to_string(), "([1,10], 3ℤ+1)");
+        assert_eq!(reduced("([0,4], 4Z+1)").to_string(), "([1,1], 0ℤ+1)");
+        assert_eq!(reduced("([2,3], 4Z+1)"), Reduced::bottom());
+        assert_eq!("([0,10], 3Z+1)".parse::<Unreduced>().unwrap().to_string(), "([0,10], 3ℤ+1)");
+    }
+
+    #[test]
+    fn operations_are_reduced() {
+        // [0,10] ∩ 2ℤ+0 plus 1 is [1,11] ∩ 2ℤ+1, joined with the singleton 4 is [1,11] ∩ 1ℤ+0
+        let odd = reduced("([0,10], 2Z+0)") + reduced("([1,1], 0Z+1)");
+        assert_eq!(odd.to_string(), "([1,11], 2ℤ+1)");
+        assert_eq!(odd.lub(Reduced::from(4)).to_string(), "([1,11], 1ℤ+0)");
+        assert_eq!(odd.glb(reduced("([4,6], 1Z+0)")).to_string(), "([5,5], 0ℤ+5)");
+    }
+}
@@ -0,0 +1,187 @@
+use std::{cmp::Ordering, fmt::Display, ops::{Add, Mul, Neg, Sub}};
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed, Zero};
+
+use crate::types::ast::Num;
+
+// Exact rational number num/den, always kept normalized (den > 0 and gcd(num,den) = 1).
+// The Fourier-Motzkin combinations grow the coefficients quickly, so they are unbounded integers
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Rational {
+    num: BigInt,
+    den: BigInt,
+}
+
+impl Rational {
+    // None if den is 0
+    pub fn new(num: BigInt, den: BigInt) -> Option<Self> {
+        if den.is_zero() {
+            return None
+        }
+        let g = gcd(&num, &den);
+        let (num, den) = (num / &g, den / &g);
+        Some(if den.is_negative() { Rational { num: -num, den: -den } } else { Rational { num, den } })
+    }
+    pub fn num(&self) -> &BigInt {
+        &self.num
+    }
+    pub fn den(&self) -> &BigInt {
+        &self.den
+    }
+    pub fn is_zero(&self) -> bool {
+        self.num.is_zero()
+    }
+    pub fn signum(&self) -> Num {
+        match self.num.sign() {
+            num_bigint::Sign::Minus => -1,
+            num_bigint::Sign::NoSign => 0,
+            num_bigint::Sign::Plus => 1,
+        }
+    }
+    pub fn abs(&self) -> Self {
+        Rational { num: self.num.abs(), den: self.den.clone() }
+    }
+    pub fn floor(&self) -> BigInt {
+        // The division truncates towards 0 and den > 0
+        let q = &self.num / &self.den;
+        if self.num.is_negative() && &q * &self.den != self.num { q - 1 } else { q }
+    }
+    pub fn ceil(&self) -> BigInt {
+        -(-self).floor()
+    }
+    // None if rhs is 0
+    pub fn checked_div(&self, rhs: &Rational) -> Option<Rational> {
+        Rational::new(&self.num * &rhs.den, &self.den * &rhs.num)
+    }
+}
+
+impl From<Num> for Rational {
+    fn from(value: Num) -> Self {
+        Rational { num: BigInt::from(value), den: BigInt::one() }
+    }
+}
+impl From<BigInt> for Rational {
+    fn from(value: BigInt) -> Self {
+        Rational { num: value, den: BigInt::one() }
+    }
+}
+
+impl Display for Rational {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.den.is_one() {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.num * &other.den).cmp(&(&other.num * &self.den))
+    }
+}
+
+impl Add for &Rational {
+    type Output = Rational;
+    fn add(self, rhs: Self) -> Self::Output {
+        Rational::new(&self.num * &rhs.den + &rhs.num * &self.den, &self.den * &rhs.den).unwrap()
+    }
+}
+impl Sub for &Rational {
+    type Output = Rational;
+    fn sub(self, rhs: Self) -> Self::Output {
+        self + &(-rhs)
+    }
+}
+impl Mul for &Rational {
+    type Output = Rational;
+    fn mul(self, rhs: Self) -> Self::Output {
+        Rational::new(&self.num * &rhs.num, &self.den * &rhs.den).unwrap()
+    }
+}
+impl Neg for &Rational {
+    type Output = Rational;
+    fn neg(self) -> Self::Output {
+        Rational { num: -&self.num, den: self.den.clone() }
+    }
+}
+
+// Owned operands delegate to the references
+macro_rules! owned_op {
+    ($trait:ident, $method:ident) => {
+        impl $trait for Rational {
+            type Output = Rational;
+            fn $method(self, rhs: Self) -> Self::Output {
+                (&self).$method(&rhs)
+            }
+        }
+        impl $trait<&Rational> for Rational {
+            type Output = Rational;
+            fn $method(self, rhs: &Rational) -> Self::Output {
+                (&self).$method(rhs)
+            }
+        }
+    };
+}
+owned_op!(Add, add);
+owned_op!(Sub, sub);
+owned_op!(Mul, mul);
+
+impl Neg for Rational {
+    type Output = Rational;
+    fn neg(self) -> Self::Output {
+        Rational { num: -self.num, den: self.den }
+    }
+}
+
+// Always non-negative, gcd(0,0) = 1 to keep the division safe
+pub fn gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while !b.is_zero() {
+        let r = &a % &b;
+        (a, b) = (b, r);
+    }
+    if a.is_zero() { BigInt::one() } else { a }
+}
+pub fn lcm(a: &BigInt, b: &BigInt) -> BigInt {
+    (a / gcd(a, b) * b).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ratio(num: Num, den: Num) -> Rational {
+        Rational::new(BigInt::from(num), BigInt::from(den)).unwrap()
+    }
+
+    #[test]
+    fn normalized_representation() {
+        let r = ratio(4, -6);
+        assert_eq!((r.num(), r.den()), (&BigInt::from(-2), &BigInt::from(3)));
+        assert_eq!(ratio(0, -5), Rational::from(0));
+        assert_eq!(ratio(3, 6), ratio(-1, -2));
+        assert_eq!(Rational::new(BigInt::from(1), BigInt::from(0)), None);
+        assert_eq!(ratio(1, 2).checked_div(&Rational::from(0)), None);
+    }
+
+    #[test]
+    fn arithmetic_and_rounding() {
+        assert_eq!(ratio(1, 2) + ratio(1, 3), ratio(5, 6));
+        assert_eq!(ratio(1, 2) - ratio(1, 2), Rational::from(0));
+        assert_eq!(ratio(2, 3) * ratio(3, 4), ratio(1, 2));
+        assert_eq!((ratio(-7, 2).floor(), ratio(-7, 2).ceil()), (BigInt::from(-4), BigInt::from(-3)));
+        assert_eq!((ratio(7, 2).floor(), ratio(7, 2).ceil()), (BigInt::from(3), BigInt::from(4)));
+        assert!(ratio(-1, 2) < ratio(1, 3));
+        // No overflow on products out of the range of Num
+        let big = Rational::from(Num::MAX) * Rational::from(Num::MAX);
+        assert_eq!(big.floor(), BigInt::from(Num::MAX) * BigInt::from(Num::MAX));
+    }
+}
@@ -1,6 +1,7 @@
 use std::{cmp::Ordering, fmt::Display, ops::{Add, Div, Mul, Sub}, str::FromStr};
 use crate::{analyzer::types::domain::Interval, types::ast::Num};
 use crate::analyzer::types::domain::AbstractDomain;
+use serde_json::{json, Value};
 
 
 #[derive(Debug,PartialEq,Clone,Copy)]
@@ -78,10 +79,30 @@ impl AbstractDomain for SignDomain{
         SignDomain::Bottom
     }
 
+    fn to_json(&self) -> Value {
+        match self {
+            SignDomain::Bottom => Value::Null,
+            SignDomain::Top => json!("top"),
+            SignDomain::Positive => json!("positive"),
+            SignDomain::Zero => json!("zero"),
+            SignDomain::Negative => json!("negative"),
+        }
+    }
+
     fn top() -> Self {
         SignDomain::Top
     }
 
+    fn contains(&self, n: Num) -> bool {
+        match self {
+            SignDomain::Top => true,
+            SignDomain::Bottom => false,
+            SignDomain::Positive => n > 0,
+            SignDomain::Zero => n == 0,
+            SignDomain::Negative => n < 0,
+        }
+    }
+
     fn lub(self, other: Self) -> Self {
         match (self, other) {
             (SignDomain::Bottom, s2) => s2,
@@ -0,0 +1,86 @@
+use std::{collections::HashMap, fmt::{Display, Write}};
+
+use iter_tools::Itertools;
+
+use super::types::program::{Command, Label, Program};
+
+
+// Abstract state shown in a node of the graph
+pub struct LabelAnnotation {
+    pub state: String,
+    pub bottom: bool,
+}
+
+// Graphviz description of the control flow graph: assignments are solid edges and tests dashed ones,
+// the widening points are filled and the bottom labels greyed out. Without annotation a node shows only its label
+pub fn program_to_dot<B: Display + Clone>(prog: &Program<B>, annotations: &HashMap<Label, LabelAnnotation>) -> String {
+    let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n    edge [fontname=\"monospace\"];\n");
+    let exit = prog.labels_num - 1;
+    for l in 0..prog.labels_num {
+        let name = match l {
+            0 => format!("{l} (entry)"),
+            _ if l == exit => format!("{l} (exit)"),
+            _ => l.to_string(),
+        };
+        let mut attrs = vec![];
+        match annotations.get(&l) {
+            Some(a) => {
+                attrs.push(format!("label=\"{name}\\n{}\"", escape(&a.state)));
+                if a.bottom {
+                    attrs.push("color=grey, fontcolor=grey".to_string());
+                }
+            },
+            None => attrs.push(format!("label=\"{name}\"")),
+        }
+        if prog.widening_points.contains(&l) {
+            attrs.push("style=filled, fillcolor=lightblue".to_string());
+        }
+        writeln!(dot, "    {l} [{}];", attrs.join(", ")).unwrap();
+    }
+    for (l1, cmd, l2) in &prog.arcs {
+        let attrs = match cmd {
+            Command::Assignment(x, a) => format!("label=\"{}\"", escape(&format!("{x} := {a}"))),
+            Command::Test(_, b) => format!("label=\"{}\", style=dashed", escape(&b.to_string())),
+            Command::Call(p, args) => format!("label=\"{}\", style=bold", escape(&format!("call {p}({})", args.iter().join(", ")))),
+            Command::Return(x, p) => format!("label=\"{}\", style=bold", escape(&format!("{x} := return {p}"))),
+            Command::ArrayAssign(x, i, a, _) => format!("label=\"{}\"", escape(&format!("{x}[{i}] := {a}"))),
+            Command::NewArray(x, n) => format!("label=\"{}\"", escape(&format!("{x} := array({n})"))),
+        };
+        writeln!(dot, "    {l1} -> {l2} [{attrs}];").unwrap();
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::parse_string, types::lit_interval::LitInterval};
+
+    use super::*;
+
+    #[test]
+    fn graph_of_a_loop() {
+        let prog = Program::from(parse_string::<LitInterval>("x := 0;\nwhile x <= 2 do {x := x + 1;}".to_string()).unwrap().main);
+        let annotations = HashMap::from([
+            (1, LabelAnnotation { state: "{x: [0,3]}".to_string(), bottom: false }),
+            (2, LabelAnnotation { state: "bottom \"x\"".to_string(), bottom: true }),
+        ]);
+        assert_eq!(program_to_dot(&prog, &annotations), r#"digraph cfg {
+    node [shape=box, fontname="monospace"];
+    edge [fontname="monospace"];
+    0 [label="0 (entry)"];
+    1 [label="1\n{x: [0,3]}", style=filled, fillcolor=lightblue];
+    2 [label="2\nbottom \"x\"", color=grey, fontcolor=grey];
+    3 [label="3 (exit)"];
+    1 -> 2 [label="(x <= 2)", style=dashed];
+    1 -> 3 [label="(not (x <= 2))", style=dashed];
+    2 -> 1 [label="x := (x + 1)"];
+    0 -> 1 [label="x := 0"];
+}
+"#);
+    }
+}
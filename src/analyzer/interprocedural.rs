@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use crate::types::{ast::{Aexpr, Procedure, Var}, lit_interval::LitInterval};
+
+use super::{analyzers::generic_analyzer::GenericAnalyzer, types::{analyzer::{IterationOrder, IterationStrategy, StaticAnalyzer}, domain::AbstractDomain, program::{Command, Label, Program}, state::AbstractState}};
+
+
+// Context-insensitive interprocedural analysis: every procedure has its own control flow graph and
+// a single summary shared by all its calls. The input of the summary is the join of the arguments
+// of the calls, the output the value of the result at the exit of the procedure analyzed from the input
+pub struct ProcedureAnalysis<B: Clone, S> {
+    pub name: Var,
+    pub params: Vec<Var>,
+    pub result: Var,
+    pub prog: Program<B>,
+    pub input: S,
+    pub output: B,
+    // Analysis of the body from the input
+    pub states: HashMap<Label, S>,
+}
+
+impl<B: AbstractDomain, S: AbstractState<B>> ProcedureAnalysis<B, S> {
+    pub fn new(procedure: Procedure<LitInterval>) -> Self {
+        let prog = GenericAnalyzer::<B, S>::init(procedure.body);
+        let states = (0..prog.labels_num).map(|l| (l, S::bottom())).collect();
+        ProcedureAnalysis {
+            name: procedure.name,
+            params: procedure.params,
+            result: procedure.result,
+            prog,
+            input: S::bottom(),
+            output: B::bottom(),
+            states,
+        }
+    }
+}
+
+// Analysis of the main program and of the procedures, repeated until the summaries are stable (with
+// the widening strategies the summaries are widened too, so that a recursion cannot grow them forever)
+pub fn analyze_program<B, S>(main: &Program<B>, procedures: &mut [ProcedureAnalysis<B, S>], init_state: S,
+                             iteration_strategy: IterationStrategy, iteration_order: IterationOrder) -> HashMap<Label, S>
+    where B: AbstractDomain, S: AbstractState<B> {
+    let widening = !matches!(iteration_strategy, IterationStrategy::Simple);
+    loop {
+        let result = GenericAnalyzer::analyze(with_summaries(main, procedures), init_state.clone(), iteration_strategy, iteration_order);
+        // The procedures never called keep the bottom states
+        let states = procedures.iter()
+            .map(|p| match p.input == S::bottom() {
+                true => p.states.clone(),
+                false => GenericAnalyzer::analyze(with_summaries(&p.prog, procedures), p.input.clone(), iteration_strategy, iteration_order),
+            })
+            .collect::<Vec<_>>();
+
+        let mut inputs = HashMap::new();
+        call_inputs(main, &result, procedures, &mut inputs);
+        for (p, states) in procedures.iter().zip(&states) {
+            call_inputs(&p.prog, states, procedures, &mut inputs);
+        }
+
+        let mut changed = false;
+        for (p, states) in procedures.iter_mut().zip(states) {
+            let input = p.input.clone().lub(&inputs.remove(&p.name).unwrap_or(S::bottom()));
+            let output = p.output.lub(states[&(p.prog.labels_num - 1)].get(&p.result));
+            let (input, output) = match widening {
+                true => (p.input.clone().widening(input), p.output.widening(output)),
+                false => (input, output),
+            };
+            changed |= input != p.input || output != p.output;
+            (p.input, p.output, p.states) = (input, output, states);
+        }
+        if !changed {
+            return result
+        }
+    }
+}
+
+// The Return arcs become assignments of the output of the summary of the called procedure
+pub fn with_summaries<B, S>(prog: &Program<B>, procedures: &[ProcedureAnalysis<B, S>]) -> Program<B>
+    where B: AbstractDomain, S: AbstractState<B> {
+    let mut prog = prog.clone();
+    for (_, cmd, _) in prog.arcs.iter_mut() {
+        if let Command::Return(x, p) = cmd {
+            let output = procedures.iter().find(|q| q.name == *p).map_or(B::top(), |q| q.output);
+            *cmd = Command::Assignment(x.clone(), Aexpr::Lit(output));
+        }
+    }
+    prog
+}
+
+// Join of the states passed by the reachable calls of prog, by procedure: only the parameters are bound
+fn call_inputs<B, S>(prog: &Program<B>, states: &HashMap<Label, S>, procedures: &[ProcedureAnalysis<B, S>], inputs: &mut HashMap<Var, S>)
+    where B: AbstractDomain, S: AbstractState<B> {
+    for (l, cmd, _) in &prog.arcs {
+        let Command::Call(p, args) = cmd else { continue };
+        let Some(procedure) = procedures.iter().find(|q| q.name == *p) else { continue };
+        let mut state = states[l].clone();
+        if state == S::bottom() {
+            continue;
+        }
+        let mut input = S::top();
+        for (x, a) in procedure.params.iter().zip(args) {
+            let (v, s) = GenericAnalyzer::<B, S>::eval_aexpr(a, state);
+            state = s;
+            input.set(x.clone(), v);
+        }
+        let input = match inputs.remove(p) {
+            Some(old) => old.lub(&input),
+            None => input,
+        };
+        inputs.insert(p.clone(), input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::{domains::interval_domain::IntervalDomain, states::hashmap_state::HashMapState, test_utils::{analyze, Analysis}};
+
+    use super::*;
+
+    type Interval = HashMapState<IntervalDomain>;
+
+    fn summaries(analysis: &Analysis<IntervalDomain, Interval>) -> Vec<String> {
+        analysis.procedures.iter().map(|p| format!("{}: {} -> {}", p.name, p.input, p.output)).collect()
+    }
+
+    fn final_state(analysis: &Analysis<IntervalDomain, Interval>) -> String {
+        analysis.states[&(analysis.prog.labels_num - 1)].to_string()
+    }
+
+    #[test]
+    fn summaries_join_every_call() {
+        let source = "proc inc(n) returns r {\n    r := n + 1;\n}\nproc unused(n) returns r {\n    r := n;\n}\nx := inc(1);\ny := inc(5);";
+        let analysis = analyze(source, IterationStrategy::Simple);
+        assert_eq!(summaries(&analysis), ["inc: {n: [1,5]} -> [2,6]", "unused: ⊥ -> ⊥"]);
+        assert_eq!(final_state(&analysis), "{x: [2,6], y: [2,6]}");
+    }
+
+    #[test]
+    fn recursive_summaries_are_widened() {
+        let source = "proc fact(n) returns r {\n    if n <= 0 then {r := 1;} else {r := n * fact(n - 1);}\n}\nx := fact(5);";
+        let analysis = analyze(source, IterationStrategy::WideningAndNarrowing { delay: 0 });
+        assert_eq!(summaries(&analysis), ["fact: {n: [-inf,5]} -> [1,+inf]"]);
+        assert_eq!(final_state(&analysis), "{x: [1,+inf]}");
+    }
+}
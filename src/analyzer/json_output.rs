@@ -0,0 +1,120 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde_json::{json, Value};
+
+use crate::types::lit_interval::LitInterval;
+
+use super::types::{domain::AbstractDomain, program::{Arc, Command, Label, Program, TestKind}, state::AbstractState};
+
+
+// JSON document of the analysis result (the schema is documented in the README). The arcs are taken
+// from the concrete program to print the literals of the source, loop_heads are the labels of the loop invariants
+pub fn analysis_to_json<B, S>(concrete: &Program<LitInterval>, prog: &Program<B>, loop_heads: &[Label], result: &HashMap<Label, S>) -> Value
+    where B: AbstractDomain, S: AbstractState<B> {
+    let exit = prog.labels_num - 1;
+    let mut loop_heads = loop_heads.to_vec();
+    loop_heads.sort();
+    json!({
+        "labels": prog.labels_num,
+        "entry": 0,
+        "exit": exit,
+        "widening_points": prog.widening_points,
+        "arcs": concrete.arcs.iter().map(arc_to_json).collect::<Vec<_>>(),
+        "states": (0..prog.labels_num).map(|l| result[&l].to_json()).collect::<Vec<_>>(),
+        "loop_invariants": loop_heads.iter()
+            .map(|l| json!({"label": l, "state": result[l].to_json()}))
+            .collect::<Vec<_>>(),
+        "final_invariant": result[&exit].to_json(),
+    })
+}
+
+fn arc_to_json<B: Display>((from, cmd, to): &Arc<B>) -> Value {
+    match cmd {
+        Command::Assignment(x, a) => json!({
+            "from": from,
+            "to": to,
+            "kind": "assignment",
+            "variable": x,
+            "expression": a.to_string(),
+        }),
+        Command::Test(kind, b) => json!({
+            "from": from,
+            "to": to,
+            "kind": match kind {
+                TestKind::Guard { .. } => "guard",
+                TestKind::Assert(_) => "assert",
+                TestKind::Assume => "assume",
+            },
+            "condition": b.to_string(),
+        }),
+        Command::Call(p, args) => json!({
+            "from": from,
+            "to": to,
+            "kind": "call",
+            "procedure": p,
+            "arguments": args.iter().map(|a| a.to_string()).collect::<Vec<_>>(),
+        }),
+        Command::Return(x, p) => json!({
+            "from": from,
+            "to": to,
+            "kind": "return",
+            "variable": x,
+            "procedure": p,
+        }),
+        Command::ArrayAssign(x, i, a, _) => json!({
+            "from": from,
+            "to": to,
+            "kind": "array-assignment",
+            "variable": x,
+            "index": i.to_string(),
+            "expression": a.to_string(),
+        }),
+        Command::NewArray(x, n) => json!({
+            "from": from,
+            "to": to,
+            "kind": "new-array",
+            "variable": x,
+            "length": n.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::{domains::interval_domain::IntervalDomain, states::hashmap_state::HashMapState, test_utils::analyze, types::analyzer::IterationStrategy};
+
+    use super::*;
+
+    fn analysis_json(source: &str, loop_heads: &[Label]) -> Value {
+        let analysis = analyze::<IntervalDomain, HashMapState<IntervalDomain>>(source, IterationStrategy::WideningAndNarrowing { delay: 20 });
+        analysis_to_json(&analysis.concrete_prog, &analysis.prog, loop_heads, &analysis.states)
+    }
+
+    #[test]
+    fn schema_of_the_analysis() {
+        let x = |l: i32, u: i32| json!({"x": {"lower": l, "upper": u}});
+        assert_eq!(analysis_json("x := 0;\nwhile x <= 2 do {x := x + 1;}\nassert 3 <= x;", &[1]), json!({
+            "labels": 5,
+            "entry": 0,
+            "exit": 4,
+            "widening_points": [1],
+            "arcs": [
+                {"from": 3, "to": 4, "kind": "assert", "condition": "(3 <= x)"},
+                {"from": 1, "to": 2, "kind": "guard", "condition": "(x <= 2)"},
+                {"from": 1, "to": 3, "kind": "guard", "condition": "(not (x <= 2))"},
+                {"from": 2, "to": 1, "kind": "assignment", "variable": "x", "expression": "(x + 1)"},
+                {"from": 0, "to": 1, "kind": "assignment", "variable": "x", "expression": "0"},
+            ],
+            "states": [{}, x(0, 3), x(0, 2), x(3, 3), x(3, 3)],
+            "loop_invariants": [{"label": 1, "state": x(0, 3)}],
+            "final_invariant": x(3, 3),
+        }));
+    }
+
+    #[test]
+    fn unbounded_and_unreachable_states() {
+        let json = analysis_json("x := y;\nassume x <= 0;\nassume 1 <= x;", &[]);
+        assert_eq!(json["states"][1], json!({"x": {"lower": "-inf", "upper": "inf"}}));
+        assert_eq!(json["final_invariant"], Value::Null);
+    }
+}
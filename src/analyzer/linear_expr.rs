@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+
+use crate::types::ast::{Aexpr, Bexpr, Num, Operator, Var};
+
+use super::{arrays::length_var, domains::{extended_num::ExtendedNum, interval_domain::IntervalDomain}, types::state::AbstractState};
+
+
+// Linear expression: sum(coeffs[x] * x) + c   where c ∈ [constant.0, constant.1]
+// (the constant is an interval to support non-deterministic literals like [0,1])
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearExpr {
+    pub coeffs: BTreeMap<Var, Num>,
+    pub constant: (Num, Num),
+}
+
+impl LinearExpr {
+    pub fn constant(l: Num, u: Num) -> Self {
+        LinearExpr { coeffs: BTreeMap::new(), constant: (l, u) }
+    }
+    pub fn var(x: &str) -> Self {
+        LinearExpr { coeffs: BTreeMap::from([(x.to_string(), 1)]), constant: (0, 0) }
+    }
+
+    // Linearization of an arithmetic expression, None if not linear, with side effects or overflowing
+    pub fn from_aexpr(a: &Aexpr<IntervalDomain>) -> Option<Self> {
+        match a {
+            Aexpr::Lit(IntervalDomain::Range(ExtendedNum::Num(l), ExtendedNum::Num(u))) =>
+                Some(Self::constant(*l, *u)),
+            Aexpr::Lit(_) => None,
+            Aexpr::Var(x, _) => Some(Self::var(x)),
+            Aexpr::PreOp(_, _, _) | Aexpr::PostOp(_, _, _) | Aexpr::Call(..) | Aexpr::Index(..) => None,
+            // The length of an array is a variable of the state (see arrays::length_var)
+            Aexpr::Len(x, _) => Some(Self::var(&length_var(x))),
+            Aexpr::BinOp(Operator::Add, a1, a2, _) =>
+                Self::from_aexpr(a1)?.add(Self::from_aexpr(a2)?),
+            Aexpr::BinOp(Operator::Sub, a1, a2, _) =>
+                Self::from_aexpr(a1)?.add(Self::from_aexpr(a2)?.neg()?),
+            Aexpr::BinOp(Operator::Mul, a1, a2, _) => {
+                let (l1, l2) = (Self::from_aexpr(a1)?, Self::from_aexpr(a2)?);
+                match (l1.as_num(), l2.as_num()) {
+                    (Some(k), _) => l2.scale(k),
+                    (_, Some(k)) => l1.scale(k),
+                    (None, None) => None,
+                }
+            },
+            Aexpr::BinOp(Operator::Div, _, _, _) => None,
+        }
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.coeffs.is_empty()
+    }
+    // The expression as a single number (if it is a deterministic constant)
+    pub fn as_num(&self) -> Option<Num> {
+        if self.is_constant() && self.constant.0 == self.constant.1 {
+            Some(self.constant.0)
+        } else {
+            None
+        }
+    }
+
+    // The operations are None if a coefficient or the constant overflows
+    pub fn add(mut self, other: Self) -> Option<Self> {
+        for (x, k) in other.coeffs {
+            let c = self.coeffs.get(&x).copied().unwrap_or(0).checked_add(k)?;
+            if c == 0 {
+                self.coeffs.remove(&x);
+            } else {
+                self.coeffs.insert(x, c);
+            }
+        }
+        self.constant = (self.constant.0.checked_add(other.constant.0)?, self.constant.1.checked_add(other.constant.1)?);
+        Some(self)
+    }
+    pub fn neg(self) -> Option<Self> {
+        self.scale(-1)
+    }
+    pub fn scale(self, k: Num) -> Option<Self> {
+        if k == 0 {
+            return Some(Self::constant(0, 0));
+        }
+        let (l, u) = (self.constant.0.checked_mul(k)?, self.constant.1.checked_mul(k)?);
+        Some(LinearExpr {
+            coeffs: self.coeffs.into_iter().map(|(x, c)| Some((x, c.checked_mul(k)?))).collect::<Option<_>>()?,
+            constant: if k > 0 { (l, u) } else { (u, l) },
+        })
+    }
+}
+
+
+// States that can represent linear constraints
+pub trait LinearConstraints {
+    // Add the constraint: expr <= 0 (for at least one value of the constant)
+    fn add_constraint(&mut self, expr: &LinearExpr);
+
+    // A constraint that overflowed while being built is dropped, which is sound
+    fn add_checked(&mut self, expr: Option<LinearExpr>) {
+        if let Some(e) = expr {
+            self.add_constraint(&e);
+        }
+    }
+}
+
+
+// Relational abstract test of b, only the linear (in)equalities are considered
+// the others are soundly ignored
+pub fn assume_bexpr<S>(state: S, b: &Bexpr<IntervalDomain>) -> S
+    where S: AbstractState<IntervalDomain> + LinearConstraints {
+    if has_side_effects(b) {
+        return state
+    }
+    assume_bexpr_h(state, b, false)
+}
+
+fn assume_bexpr_h<S>(mut state: S, b: &Bexpr<IntervalDomain>, negated: bool) -> S
+    where S: AbstractState<IntervalDomain> + LinearConstraints {
+    match b {
+        Bexpr::True if negated => S::bottom(),
+        Bexpr::True => state,
+        Bexpr::False if negated => state,
+        Bexpr::False => S::bottom(),
+        Bexpr::Equal(a1, a2, _) | Bexpr::NotEqual(a1, a2, _) => {
+            let Some(e) = diff(a1, a2) else { return state };
+            if matches!(b, Bexpr::Equal(..)) != negated {
+                // a1 - a2 <= 0  and  a2 - a1 <= 0
+                state.add_constraint(&e);
+                state.add_checked(e.neg());
+                state
+            } else {
+                // a1 - a2 <= -1  or  a2 - a1 <= -1
+                let mut s1 = state.clone();
+                s1.add_checked(e.clone().add(LinearExpr::constant(1, 1)));
+                state.add_checked(e.neg().and_then(|e| e.add(LinearExpr::constant(1, 1))));
+                s1.lub(&state)
+            }
+        },
+        Bexpr::LessEq(a1, a2, _) => {
+            let Some(e) = diff(a1, a2) else { return state };
+            if !negated {
+                // a1 - a2 <= 0
+                state.add_constraint(&e);
+            } else {
+                // a2 - a1 <= -1
+                state.add_checked(e.neg().and_then(|e| e.add(LinearExpr::constant(1, 1))));
+            }
+            state
+        },
+        Bexpr::Less(a1, a2, _) => {
+            let Some(e) = diff(a1, a2) else { return state };
+            if !negated {
+                // a1 - a2 <= -1
+                state.add_checked(e.add(LinearExpr::constant(1, 1)));
+            } else {
+                // a2 - a1 <= 0
+                state.add_checked(e.neg());
+            }
+            state
+        },
+        Bexpr::Not(b) => assume_bexpr_h(state, b, !negated),
+        // A negated or is an and of the negations, and vice versa
+        Bexpr::And(b1, b2) | Bexpr::Or(b1, b2) => {
+            if matches!(b, Bexpr::And(..)) != negated {
+                let state = assume_bexpr_h(state, b1, negated);
+                assume_bexpr_h(state, b2, negated)
+            } else {
+                let s1 = assume_bexpr_h(state.clone(), b1, negated);
+                let s2 = assume_bexpr_h(state, b2, negated);
+                s1.lub(&s2)
+            }
+        },
+    }
+}
+
+fn diff(a1: &Aexpr<IntervalDomain>, a2: &Aexpr<IntervalDomain>) -> Option<LinearExpr> {
+    LinearExpr::from_aexpr(a1)?.add(LinearExpr::from_aexpr(a2)?.neg()?)
+}
+
+pub fn aexpr_side_effects<N>(a: &Aexpr<N>) -> bool {
+    match a {
+        Aexpr::Lit(_) | Aexpr::Var(_, _) | Aexpr::Len(_, _) => false,
+        Aexpr::PreOp(_, _, _) | Aexpr::PostOp(_, _, _) => true,
+        Aexpr::BinOp(_, a1, a2, _) => aexpr_side_effects(a1) || aexpr_side_effects(a2),
+        Aexpr::Call(_, args, _) => args.iter().any(aexpr_side_effects),
+        Aexpr::Index(_, i, _) => aexpr_side_effects(i),
+    }
+}
+
+fn has_side_effects<N>(b: &Bexpr<N>) -> bool {
+    match b {
+        Bexpr::True | Bexpr::False => false,
+        Bexpr::Equal(a1, a2, _) | Bexpr::LessEq(a1, a2, _) | Bexpr::Less(a1, a2, _) | Bexpr::NotEqual(a1, a2, _) =>
+            aexpr_side_effects(a1) || aexpr_side_effects(a2),
+        Bexpr::Not(b) => has_side_effects(b),
+        Bexpr::And(b1, b2) | Bexpr::Or(b1, b2) => has_side_effects(b1) || has_side_effects(b2),
+    }
+}
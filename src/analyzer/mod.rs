@@ -6,6 +6,8 @@ pub mod domains{
     pub mod bounded_interval_domain;
     pub mod extended_sign_domain;
     pub mod congruence_domain;
+    pub mod product_domain;
+    pub mod rational;
 }
 pub mod analyzers {
     pub mod generic_analyzer;
@@ -18,9 +20,24 @@ pub mod types {
 }
 pub mod states {
     pub mod hashmap_state;
+    pub mod octagon_state;
+    pub mod polyhedra_state;
 }
 pub mod abstract_translator;
 pub mod printers;
+pub mod linear_expr;
+pub mod soundness;
+pub mod assertions;
+pub mod alarms;
+pub mod arrays;
+pub mod dead_code;
+pub mod interprocedural;
+pub mod json_output;
+pub mod dot;
+pub mod thresholds;
+pub mod wto;
 mod advanced_tests;
+#[cfg(test)]
+mod test_utils;
 
 
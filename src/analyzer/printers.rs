@@ -1,9 +1,8 @@
 use std::{fmt::Display, collections::HashMap};
 use iter_tools::Itertools;
-use regex::Regex;
 use crate::types::ast::{NumLiteral, Statement};
 
-use super::types::program::Label;
+use super::{dead_code::DeadCode, types::program::{Label, StatementLabels}};
 
 
 pub fn map_to_str<B: Display>(map: &HashMap<Label,B>) -> String{
@@ -11,15 +10,195 @@ pub fn map_to_str<B: Display>(map: &HashMap<Label,B>) -> String{
     format!("{{{str}}}")
 }
 
+// Print the program with the source line of each statement and the loops marked with
+// the number of their invariant
 pub fn print_stm_with_inv<N: NumLiteral>(stm: Statement<N>) -> String{
-    let str = stm.to_string();
-    let re = Regex::new(r"^ *while( |\()").unwrap();
+    let lines = stm_lines(&stm);
+    let width = lines.iter().filter_map(|(l,_,_)|*l).max().unwrap_or(0).to_string().len();
     let mut inv_num = 0;
-    
-    str
-        .lines()
-        .fold(String::new(), |a,s| {
-            if re.is_match(s) { inv_num +=1; format!("{a}i{inv_num}  > {s}\n") }
-            else { a+"    > "+s+"\n" }
-        } )   
-}
\ No newline at end of file
+
+    lines
+        .into_iter()
+        .fold(String::new(), |a,(line, is_loop, s)| {
+            let line = line.map(|l|l.to_string()).unwrap_or_default();
+            if is_loop { inv_num +=1; format!("{a}{line:>width$} i{inv_num}  > {s}\n") }
+            else { format!("{a}{line:>width$}     > {s}\n") }
+        } )
+}
+
+// Lines of the pretty printed statement (same layout of Display) as (source line, is loop head, text)
+fn stm_lines<N: NumLiteral>(stm: &Statement<N>) -> Vec<(Option<usize>, bool, String)> {
+    let tab = |lines: Vec<(Option<usize>, bool, String)>| lines
+        .into_iter()
+        .map(|(l, is_loop, s)|(l, is_loop, format!("    {s}")));
+    match stm {
+        Statement::Assign(_, _, span) | Statement::Skip(span) |
+        Statement::Assert(_, span) | Statement::Assume(_, span) |
+        Statement::ArrayAssign(_, _, _, span) | Statement::NewArray(_, _, span) =>
+            vec![(Some(span.line()), false, stm.to_string())],
+        Statement::Compose(s1, s2) =>
+            [stm_lines(s1), stm_lines(s2)].concat(),
+        Statement::IfThenElse(b, s1, s2, span) =>
+            std::iter::once((Some(span.line()), false, format!("if {b} then {{")))
+                .chain(tab(stm_lines(s1)))
+                .chain(std::iter::once((None, false, "} else {".to_string())))
+                .chain(tab(stm_lines(s2)))
+                .chain(std::iter::once((None, false, "}".to_string())))
+                .collect(),
+        Statement::While(b, s, span) =>
+            std::iter::once((Some(span.line()), true, format!("while {b} do {{")))
+                .chain(tab(stm_lines(s)))
+                .chain(std::iter::once((None, false, "}".to_string())))
+                .collect(),
+    }
+}
+
+// Print the program like print_stm_with_inv, marking with "!" the if/while statements with a dead
+// branch (explained at the end of the line) and with "✗" the statements that never run
+pub fn print_stm_with_dead_code<N: NumLiteral>(stm: Statement<N>, dead: &DeadCode) -> String{
+    let lines = dead_code_lines(&stm, dead, false);
+    let width = lines.iter().filter_map(|(l,_,_)|*l).max().unwrap_or(0).to_string().len();
+
+    lines
+        .into_iter()
+        .fold(String::new(), |a,(line, marker, s)| {
+            let line = line.map(|l|l.to_string()).unwrap_or_default();
+            format!("{a}{line:>width$} {marker:<3} > {s}\n")
+        } )
+}
+
+fn dead_code_lines<N: NumLiteral>(stm: &Statement<N>, dead: &DeadCode, is_dead: bool) -> Vec<(Option<usize>, &'static str, String)> {
+    let tab = |lines: Vec<(Option<usize>, &'static str, String)>| lines
+        .into_iter()
+        .map(|(l, marker, s)|(l, marker, format!("    {s}")));
+    // A composition has the span of its first statement
+    let is_dead = is_dead || !matches!(stm, Statement::Compose(..)) && dead.unreachable.contains(&stm.span());
+    let dead_marker = if is_dead { "✗" } else { "" };
+    // Marker and explanation of the head of an if/while
+    let head = |span, then_msg: &str, else_msg: &str| {
+        let msgs = [(true, then_msg), (false, else_msg)].into_iter()
+            .filter(|(taken, _)| !is_dead && dead.branches.contains(&(span, *taken)))
+            .map(|(_, msg)| msg)
+            .join(", ");
+        if msgs.is_empty() { (dead_marker, String::new()) } else { ("!", format!("  // {msgs}")) }
+    };
+    match stm {
+        Statement::Assign(_, _, span) | Statement::Skip(span) |
+        Statement::Assert(_, span) | Statement::Assume(_, span) |
+        Statement::ArrayAssign(_, _, _, span) | Statement::NewArray(_, _, span) =>
+            vec![(Some(span.line()), dead_marker, stm.to_string())],
+        Statement::Compose(s1, s2) =>
+            [dead_code_lines(s1, dead, is_dead), dead_code_lines(s2, dead, is_dead)].concat(),
+        Statement::IfThenElse(b, s1, s2, span) => {
+            let (marker, msg) = head(*span, "the then branch is unreachable", "the else branch is unreachable");
+            std::iter::once((Some(span.line()), marker, format!("if {b} then {{{msg}")))
+                .chain(tab(dead_code_lines(s1, dead, is_dead || dead.branches.contains(&(*span, true)))))
+                .chain(std::iter::once((None, "", "} else {".to_string())))
+                .chain(tab(dead_code_lines(s2, dead, is_dead || dead.branches.contains(&(*span, false)))))
+                .chain(std::iter::once((None, "", "}".to_string())))
+                .collect()
+        },
+        Statement::While(b, s, span) => {
+            let (marker, msg) = head(*span, "the body never runs", "the loop never terminates");
+            std::iter::once((Some(span.line()), marker, format!("while {b} do {{{msg}")))
+                .chain(tab(dead_code_lines(s, dead, is_dead || dead.branches.contains(&(*span, true)))))
+                .chain(std::iter::once((None, "", "}".to_string())))
+                .collect()
+        },
+    }
+}
+
+// Floyd-style proof outline: the program with the abstract state before and after every statement,
+// statements are the labels produced by stm_to_program (in the same pre-order of the AST)
+pub fn print_stm_with_states<N: NumLiteral, S: Display>(stm: Statement<N>, statements: &[StatementLabels], states: &HashMap<Label, S>) -> String{
+    let mut labels = statements.iter();
+    let mut last = None;
+    let lines = outline_lines(&stm, &mut labels, states, &mut last, None);
+    let width = lines.iter().filter_map(|(l,_)|*l).max().unwrap_or(0).to_string().len();
+
+    lines
+        .into_iter()
+        .fold(String::new(), |a,(line, s)| {
+            let line = line.map(|l|l.to_string()).unwrap_or_default();
+            format!("{a}{line:>width$} > {s}\n")
+        } )
+}
+
+// last is the label of the last printed state, not repeated by the next statement.
+// hidden is the exit of the enclosing if, shared by the last statements of its branches: its state is
+// the join of the branches, so it is printed only once after the closing "}"
+fn outline_lines<'a, N: NumLiteral, S: Display>(
+    stm: &Statement<N>,
+    labels: &mut impl Iterator<Item = &'a StatementLabels>,
+    states: &HashMap<Label, S>,
+    last: &mut Option<Label>,
+    hidden: Option<Label>
+) -> Vec<(Option<usize>, String)> {
+    let tab = |lines: Vec<(Option<usize>, String)>| lines
+        .into_iter()
+        .map(|(l, s)|(l, format!("    {s}")));
+    let state = |label: Label, last: &mut Option<Label>| {
+        if *last == Some(label) {
+            vec![]
+        } else {
+            *last = Some(label);
+            vec![(None, states[&label].to_string())]
+        }
+    };
+    if let Statement::Compose(s1, s2) = stm {
+        return [outline_lines(s1, labels, states, last, hidden), outline_lines(s2, labels, states, last, hidden)].concat();
+    }
+    let StatementLabels { entry, exit, .. } = *labels.next().expect("statement without labels");
+    // A branch made of a skip starts at the exit of the if too
+    let pre = if hidden == Some(entry) { vec![] } else { state(entry, last) };
+    let body = match stm {
+        Statement::Compose(..) => unreachable!(),
+        Statement::Assign(_, _, span) | Statement::Skip(span) |
+        Statement::Assert(_, span) | Statement::Assume(_, span) |
+        Statement::ArrayAssign(_, _, _, span) | Statement::NewArray(_, _, span) =>
+            vec![(Some(span.line()), stm.to_string())],
+        Statement::IfThenElse(b, s1, s2, span) => {
+            *last = None;
+            let then_lines = outline_lines(s1, labels, states, last, Some(exit));
+            *last = None;
+            let else_lines = outline_lines(s2, labels, states, last, Some(exit));
+            *last = None;
+            std::iter::once((Some(span.line()), format!("if {b} then {{")))
+                .chain(tab(then_lines))
+                .chain(std::iter::once((None, "} else {".to_string())))
+                .chain(tab(else_lines))
+                .chain(std::iter::once((None, "}".to_string())))
+                .collect()
+        },
+        Statement::While(b, s, span) => {
+            *last = None;
+            let body_lines = outline_lines(s, labels, states, last, None);
+            *last = None;
+            std::iter::once((Some(span.line()), format!("while {b} do {{")))
+                .chain(tab(body_lines))
+                .chain(std::iter::once((None, "}".to_string())))
+                .collect()
+        },
+    };
+    let post = if hidden == Some(exit) { vec![] } else { state(exit, last) };
+    [pre, body, post].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analyzer::{analyzers::generic_analyzer::GenericAnalyzer, domains::interval_domain::IntervalDomain, states::hashmap_state::HashMapState, types::{analyzer::{IterationOrder, IterationStrategy, StaticAnalyzer}, program::ProgramInterface, state::AbstractState}}, parser::parse_string, types::lit_interval::LitInterval};
+
+    #[test]
+    fn outline_prints_the_join_only_after_the_if() {
+        let ast = parse_string::<LitInterval>("x := 0; if x <= -1 then y := 1; else y := 2;".to_string()).unwrap().main;
+        let prog = GenericAnalyzer::<IntervalDomain, HashMapState<IntervalDomain>>::init(ast.clone());
+        let states = GenericAnalyzer::analyze(prog.clone(), HashMapState::top(), IterationStrategy::Simple, IterationOrder::RoundRobin);
+        let outline = print_stm_with_states(ast, prog.get_statements(), &states);
+        let lines = outline.lines().map(str::trim).collect_vec();
+        assert_eq!(lines, [
+            "> ⊤", "1 > x:= 0;", "> {x: [0,0]}", "1 > if (x <= (0 - 1)) then {", ">     ⊥", "1 >     y:= 1;",
+            "> } else {", ">     {x: [0,0]}", "1 >     y:= 2;", "> }", "> {x: [0,0], y: [2,2]}",
+        ]);
+    }
+}
@@ -0,0 +1,323 @@
+use std::{collections::{BTreeSet, HashMap}, fmt::Display};
+
+use iter_tools::Itertools;
+use num_traits::ToPrimitive;
+
+use crate::{interpreter::{int_semantics::IntSemantics, interpreter::{eval_aexpr, eval_bexpr, eval_statement, procedure_table, Context, Procedures}, types::{Int, State, Value}}, types::{ast::{Aexpr, Bexpr, Num, Procedure, Statement, Var}, lit_interval::LitInterval, span::Span}};
+
+use super::{arrays, types::{domain::AbstractDomain, program::{Command, Label, Program}, state::AbstractState}};
+
+
+// Cross-check of the analysis results with concrete executions: every concrete state reached
+// at a label must be contained in the abstract state computed for that label
+
+pub struct Counterexample {
+    pub label: Label,
+    // How the label was reached (the command of the last arc)
+    pub command: String,
+    pub init_state: State<Value>,
+    pub state: State<Value>,
+    pub abstract_state: String,
+}
+
+pub struct SoundnessReport {
+    pub runs: u64,
+    pub checked_states: u64,
+    // At most one counterexample for each label
+    pub counterexamples: Vec<Counterexample>,
+}
+
+#[derive(Debug)]
+pub struct SoundnessCheck {
+    pub runs: u64,
+    pub range: LitInterval,
+    pub max_steps: u64,
+    pub seed: u64,
+}
+
+// Only the labels of the main program are checked, the calls are executed by the interpreter
+pub fn check_soundness<B, S>(prog: &Program<LitInterval>, procedures: &[Procedure<LitInterval>], result: &HashMap<Label, S>, check: &SoundnessCheck) -> SoundnessReport
+    where B: AbstractDomain, S: AbstractState<B> {
+    let (vars, array_vars) = program_vars(prog);
+    let mut rng = SplitMix64(check.seed);
+    let mut report = SoundnessReport { runs: check.runs, checked_states: 0, counterexamples: vec![] };
+
+    for _ in 0..check.runs {
+        // The arrays get a random length up to 10, with the elements in the range
+        let init_state: State<Value> = vars.iter()
+            .map(|x| match array_vars.contains(x) {
+                true => {
+                    let len = rng.next_in(LitInterval(0, 10));
+                    (x.clone(), Value::Array((0..len).map(|_| Int::from(rng.next_in(check.range))).collect()))
+                },
+                false => (x.clone(), Value::Int(Int::from(rng.next_in(check.range)))),
+            })
+            .collect();
+        let procedures = instantiate_procedures(procedures, &mut rng);
+        for (label, command, state) in concrete_run(prog, procedures, init_state.clone(), check.max_steps, &mut rng) {
+            report.checked_states += 1;
+            if report.counterexamples.iter().any(|c| c.label == label) {
+                continue;
+            }
+            let abstract_state = &result[&label];
+            if !abstract_views(&state).iter().all(|s| abstract_state.contains(s)) {
+                report.counterexamples.push(Counterexample {
+                    label, command, state,
+                    init_state: init_state.clone(),
+                    abstract_state: abstract_state.to_string(),
+                });
+            }
+        }
+    }
+    report.counterexamples.sort_by_key(|c| c.label);
+    report
+}
+
+// Execution of the control flow graph from label 0, returning the states reached at every label
+// with the command that led there; the run stops at the exit label, on a runtime error or after max_steps
+fn concrete_run(prog: &Program<LitInterval>, procedures: Procedures, init_state: State<Value>, max_steps: u64, rng: &mut SplitMix64) -> Vec<(Label, String, State<Value>)> {
+    // The analysis works on the mathematical integers, the checked semantics stops at the first overflow
+    let mut ctx = Context::new(IntSemantics::Checked, Some(max_steps), None).with_procedures(procedures);
+    let mut states = vec![(0, "entry".to_string(), init_state.clone())];
+    let (mut label, mut state) = (0, init_state);
+    // Result of the call waiting for its Return arc
+    let mut returned = None;
+
+    for _ in 0..max_steps {
+        let mut next = None;
+        // The tests leaving a label are mutually exclusive, the first that holds is taken
+        for (_, cmd, l2) in prog.arcs.iter().filter(|(l1, _, _)| *l1 == label) {
+            let mut s = state.clone();
+            let taken = match cmd {
+                Command::Assignment(x, a) => eval_aexpr(&instantiate_aexpr(a, rng), &mut s, &mut ctx)
+                    .map(|n| { s.insert(x.clone(), Value::Int(n)); true }),
+                Command::Test(_, b) => eval_bexpr(&instantiate_bexpr(b, rng), &mut s, &mut ctx),
+                Command::Call(p, args) => {
+                    let call = Aexpr::Call(p.clone(), args.iter().map(|a| instantiate_aexpr(a, rng)).collect(), Span::default());
+                    eval_aexpr(&call, &mut s, &mut ctx).map(|n| { returned = Some(n); true })
+                },
+                Command::Return(x, _) => {
+                    s.insert(x.clone(), Value::Int(returned.take().expect("return without a call")));
+                    Ok(true)
+                },
+                Command::ArrayAssign(x, i, a, span) => {
+                    let stm = Statement::ArrayAssign(x.clone(), Box::new(instantiate_aexpr(i, rng)), Box::new(instantiate_aexpr(a, rng)), *span);
+                    eval_statement(&stm, state.clone(), &mut ctx).map(|s2| { s = s2; true })
+                },
+                Command::NewArray(x, n) => {
+                    let stm = Statement::NewArray(x.clone(), Box::new(instantiate_aexpr(n, rng)), Span::default());
+                    eval_statement(&stm, state.clone(), &mut ctx).map(|s2| { s = s2; true })
+                },
+            };
+            match taken {
+                Ok(true) => {
+                    next = Some((*l2, command_to_string(cmd), s));
+                    break;
+                },
+                Ok(false) => (),
+                Err(_) => return states,
+            }
+        }
+        let Some((l2, cmd, s)) = next else { break };
+        states.push((l2, cmd, s.clone()));
+        (label, state) = (l2, s);
+    }
+    states
+}
+
+// States of the variables of the analysis for a concrete state: an array is abstracted by its length
+// and by its elements, one in each state (the values are fitted in i128 by the interpreter)
+fn abstract_views(state: &State<Value>) -> Vec<State<Num>> {
+    let mut base = State::new();
+    for (x, v) in state {
+        match v {
+            Value::Int(n) => { n.to_i128().map(|n| base.insert(x.clone(), n)); },
+            Value::Array(elems) => { base.insert(arrays::length_var(x), elems.len() as Num); },
+        }
+    }
+    let views = state.iter()
+        .filter_map(|(x, v)| match v { Value::Array(elems) => Some((x, elems)), Value::Int(_) => None })
+        .flat_map(|(x, elems)| elems.iter().filter_map(|n| n.to_i128()).map(|n| {
+            let mut s = base.clone();
+            s.insert(arrays::cell_var(x), n);
+            s
+        }))
+        .collect::<Vec<_>>();
+    match views.is_empty() {
+        true => vec![base],
+        false => views,
+    }
+}
+
+// Every literal interval [l,u] is replaced by a random value in it
+fn instantiate_aexpr(a: &Aexpr<LitInterval>, rng: &mut SplitMix64) -> Aexpr<Num> {
+    match a {
+        Aexpr::Lit(n) => Aexpr::Lit(rng.next_in(*n)),
+        Aexpr::Var(x, span) => Aexpr::Var(x.clone(), *span),
+        Aexpr::PreOp(op, x, span) => Aexpr::PreOp(op.clone(), x.clone(), *span),
+        Aexpr::PostOp(op, x, span) => Aexpr::PostOp(op.clone(), x.clone(), *span),
+        Aexpr::BinOp(op, a1, a2, span) =>
+            Aexpr::BinOp(*op, Box::new(instantiate_aexpr(a1, rng)), Box::new(instantiate_aexpr(a2, rng)), *span),
+        Aexpr::Call(p, args, span) => Aexpr::Call(p.clone(), args.iter().map(|a| instantiate_aexpr(a, rng)).collect(), *span),
+        Aexpr::Index(x, i, span) => Aexpr::Index(x.clone(), Box::new(instantiate_aexpr(i, rng)), *span),
+        Aexpr::Len(x, span) => Aexpr::Len(x.clone(), *span),
+    }
+}
+fn instantiate_bexpr(b: &Bexpr<LitInterval>, rng: &mut SplitMix64) -> Bexpr<Num> {
+    match b {
+        Bexpr::True => Bexpr::True,
+        Bexpr::False => Bexpr::False,
+        Bexpr::Equal(a1, a2, span) =>
+            Bexpr::Equal(Box::new(instantiate_aexpr(a1, rng)), Box::new(instantiate_aexpr(a2, rng)), *span),
+        Bexpr::LessEq(a1, a2, span) =>
+            Bexpr::LessEq(Box::new(instantiate_aexpr(a1, rng)), Box::new(instantiate_aexpr(a2, rng)), *span),
+        Bexpr::Not(b) => Bexpr::Not(Box::new(instantiate_bexpr(b, rng))),
+        Bexpr::And(b1, b2) =>
+            Bexpr::And(Box::new(instantiate_bexpr(b1, rng)), Box::new(instantiate_bexpr(b2, rng))),
+        Bexpr::Or(b1, b2) =>
+            Bexpr::Or(Box::new(instantiate_bexpr(b1, rng)), Box::new(instantiate_bexpr(b2, rng))),
+        Bexpr::Less(a1, a2, span) =>
+            Bexpr::Less(Box::new(instantiate_aexpr(a1, rng)), Box::new(instantiate_aexpr(a2, rng)), *span),
+        Bexpr::NotEqual(a1, a2, span) =>
+            Bexpr::NotEqual(Box::new(instantiate_aexpr(a1, rng)), Box::new(instantiate_aexpr(a2, rng)), *span),
+    }
+}
+
+// The literals of the procedures are instantiated once for the whole run
+fn instantiate_procedures(procedures: &[Procedure<LitInterval>], rng: &mut SplitMix64) -> Procedures {
+    fn instantiate_statement(stm: &Statement<LitInterval>, rng: &mut SplitMix64) -> Statement<Num> {
+        match stm {
+            Statement::Assign(x, a, span) => Statement::Assign(x.clone(), Box::new(instantiate_aexpr(a, rng)), *span),
+            Statement::ArrayAssign(x, i, a, span) =>
+                Statement::ArrayAssign(x.clone(), Box::new(instantiate_aexpr(i, rng)), Box::new(instantiate_aexpr(a, rng)), *span),
+            Statement::NewArray(x, n, span) => Statement::NewArray(x.clone(), Box::new(instantiate_aexpr(n, rng)), *span),
+            Statement::Skip(span) => Statement::Skip(*span),
+            Statement::Compose(s1, s2) =>
+                Statement::Compose(Box::new(instantiate_statement(s1, rng)), Box::new(instantiate_statement(s2, rng))),
+            Statement::IfThenElse(b, s1, s2, span) => Statement::IfThenElse(
+                Box::new(instantiate_bexpr(b, rng)),
+                Box::new(instantiate_statement(s1, rng)),
+                Box::new(instantiate_statement(s2, rng)),
+                *span
+            ),
+            Statement::While(b, s, span) =>
+                Statement::While(Box::new(instantiate_bexpr(b, rng)), Box::new(instantiate_statement(s, rng)), *span),
+            Statement::Assert(b, span) => Statement::Assert(Box::new(instantiate_bexpr(b, rng)), *span),
+            Statement::Assume(b, span) => Statement::Assume(Box::new(instantiate_bexpr(b, rng)), *span),
+        }
+    }
+    procedure_table(procedures.iter()
+        .map(|p| Procedure {
+            name: p.name.clone(),
+            params: p.params.clone(),
+            result: p.result.clone(),
+            body: instantiate_statement(&p.body, rng),
+            span: p.span,
+        })
+        .collect())
+}
+
+fn command_to_string<N: Display>(cmd: &Command<N>) -> String {
+    match cmd {
+        Command::Assignment(x, a) => format!("{x} := {a}"),
+        Command::Test(_, b) => format!("test {b}"),
+        Command::Call(p, args) => format!("call {p}({})", args.iter().join(", ")),
+        Command::Return(x, p) => format!("{x} := return {p}"),
+        Command::ArrayAssign(x, i, a, _) => format!("{x}[{i}] := {a}"),
+        Command::NewArray(x, n) => format!("{x} := array({n})"),
+    }
+}
+
+// Variables of the program and the ones among them used as arrays
+fn program_vars<N: Clone>(prog: &Program<N>) -> (BTreeSet<Var>, BTreeSet<Var>) {
+    type Vars = (BTreeSet<Var>, BTreeSet<Var>);
+    fn aexpr_vars<N>(a: &Aexpr<N>, vars: &mut Vars) {
+        match a {
+            Aexpr::Lit(_) => (),
+            Aexpr::Var(x, _) | Aexpr::PreOp(_, x, _) | Aexpr::PostOp(_, x, _) => { vars.0.insert(x.clone()); },
+            Aexpr::BinOp(_, a1, a2, _) => { aexpr_vars(a1, vars); aexpr_vars(a2, vars) },
+            Aexpr::Call(_, args, _) => args.iter().for_each(|a| aexpr_vars(a, vars)),
+            Aexpr::Index(x, i, _) => { array_var(x, vars); aexpr_vars(i, vars) },
+            Aexpr::Len(x, _) => array_var(x, vars),
+        }
+    }
+    fn bexpr_vars<N>(b: &Bexpr<N>, vars: &mut Vars) {
+        match b {
+            Bexpr::True | Bexpr::False => (),
+            Bexpr::Equal(a1, a2, _) | Bexpr::LessEq(a1, a2, _) | Bexpr::Less(a1, a2, _) | Bexpr::NotEqual(a1, a2, _) =>
+                { aexpr_vars(a1, vars); aexpr_vars(a2, vars) },
+            Bexpr::Not(b) => bexpr_vars(b, vars),
+            Bexpr::And(b1, b2) | Bexpr::Or(b1, b2) => { bexpr_vars(b1, vars); bexpr_vars(b2, vars) },
+        }
+    }
+    fn array_var(x: &Var, vars: &mut Vars) {
+        vars.0.insert(x.clone());
+        vars.1.insert(x.clone());
+    }
+    let mut vars = (BTreeSet::new(), BTreeSet::new());
+    for (_, cmd, _) in &prog.arcs {
+        match cmd {
+            Command::Assignment(x, a) => { vars.0.insert(x.clone()); aexpr_vars(a, &mut vars) },
+            Command::Test(_, b) => bexpr_vars(b, &mut vars),
+            Command::Call(_, args) => args.iter().for_each(|a| aexpr_vars(a, &mut vars)),
+            Command::Return(x, _) => { vars.0.insert(x.clone()); },
+            Command::ArrayAssign(x, i, a, _) => { array_var(x, &mut vars); aexpr_vars(i, &mut vars); aexpr_vars(a, &mut vars) },
+            Command::NewArray(x, n) => { array_var(x, &mut vars); aexpr_vars(n, &mut vars) },
+        }
+    }
+    vars
+}
+
+impl Display for Counterexample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |s: &State<Value>| format!("{{{}}}", s.iter().sorted_by_key(|(x, _)| *x).map(|(x, v)| format!("{x}: {v}")).join(", "));
+        writeln!(f, "label {} (after {}):", self.label, self.command)?;
+        writeln!(f, "  concrete state: {}", show(&self.state))?;
+        writeln!(f, "  abstract state: {}", self.abstract_state)?;
+        write!(f, "  initial state:  {}", show(&self.init_state))
+    }
+}
+
+
+// Small deterministic generator (SplitMix64), the seed makes the runs reproducible
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+    fn next_in(&mut self, LitInterval(l, u): LitInterval) -> Num {
+        let width = u.saturating_sub(l).saturating_add(1).max(1) as u128;
+        l + (self.next() as u128 % width) as Num
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::{domains::interval_domain::IntervalDomain, states::hashmap_state::HashMapState, test_utils::analyze, types::analyzer::IterationStrategy};
+
+    use super::*;
+
+    const CHECK: SoundnessCheck = SoundnessCheck { runs: 50, range: LitInterval(-20, 20), max_steps: 1000, seed: 7 };
+
+    #[test]
+    fn sound_and_unsound_results() {
+        let source = "x := [0,10]; while x <= 20 do x := x + 3;";
+        let analysis = analyze::<IntervalDomain, HashMapState<IntervalDomain>>(source, IterationStrategy::Widening { delay: 0 });
+        let report = check_soundness(&analysis.concrete_prog, &[], &analysis.states, &CHECK);
+        assert!(report.checked_states > CHECK.runs);
+        assert!(report.counterexamples.is_empty());
+
+        // The loop head claims x <= 5, reached with x = 6 at least from the initial values x >= 3
+        let mut states = analysis.states;
+        let head = analysis.prog.widening_points[0];
+        states.insert(head, "x:[0,5]".parse().unwrap());
+        let report = check_soundness(&analysis.concrete_prog, &[], &states, &CHECK);
+        assert_eq!(report.counterexamples.iter().map(|c| c.label).collect_vec(), [head]);
+        assert!(matches!(report.counterexamples[0].state["x"], Value::Int(ref x) if *x > Int::from(5)));
+    }
+}
@@ -1,6 +1,7 @@
 use std::{cmp::Ordering, collections::HashSet, fmt::Display, str::FromStr};
 
 use iter_tools::Itertools;
+use serde_json::Value;
 
 use crate::{analyzer::types::{domain::AbstractDomain, state::AbstractState}, interpreter::types::State};
 
@@ -15,6 +16,13 @@ impl<B: AbstractDomain> AbstractState<B> for HashMapState<B>  {
     }
     fn top() -> Self{
         HashMapState(Some(State::new()))
+    }
+    // The value of every variable, the missing ones are top
+    fn to_json(&self) -> Value {
+        match &self.0 {
+            Some(s) => Value::Object(s.iter().sorted_by_key(|(k,_)|*k).map(|(k,v)|(k.clone(), v.to_json())).collect()),
+            None => Value::Null,
+        }
     }    
     fn lub(self, other: &Self) -> Self { 
         // print!("LUB {:?}, {:?} -> ",self,other);
@@ -0,0 +1,558 @@
+use std::{cmp::{min, Ordering}, fmt::Display, str::FromStr};
+
+use iter_tools::Itertools;
+use serde_json::{json, Value};
+
+use crate::{analyzer::{domains::{extended_num::ExtendedNum, interval_domain::IntervalDomain}, linear_expr::{assume_bexpr, LinearConstraints, LinearExpr}, types::{domain::AbstractDomain, state::AbstractState}}, interpreter::types::State, types::ast::{Aexpr, Bexpr, Num, Var}};
+
+use super::hashmap_state::HashMapState;
+
+
+// Name of the temporary variable used during assignments (not a valid identifier)
+const TMP_VAR: &str = "#tmp";
+
+// Octagon represented by a difference bound matrix (DBM) on 2n signed variables:
+//   V(2i) = +x_i,  V(2i+1) = -x_i
+// the entry m[i][j] bounds V(j) - V(i) <= m[i][j], so for example
+//   x_i - x_j <= m[2j][2i]    x_i + x_j <= m[2j+1][2i]    x_i <= m[2i+1][2i] / 2
+#[derive(Debug, Clone)]
+pub struct Dbm {
+    vars: Vec<Var>,
+    m: Vec<Vec<ExtendedNum>>,
+}
+
+// None is bottom, the DBM is always kept closed except for widened states
+#[derive(Debug, Clone)]
+pub struct OctagonState(pub Option<Dbm>);
+
+fn bar(i: usize) -> usize {
+    i ^ 1
+}
+fn half_floor(n: ExtendedNum) -> ExtendedNum {
+    match n {
+        ExtendedNum::Num(n) => ExtendedNum::Num(n.div_euclid(2)),
+        inf => inf,
+    }
+}
+fn neg(n: ExtendedNum) -> ExtendedNum {
+    ExtendedNum::Num(0) - n
+}
+// The entries are upper bounds, so on overflow they are relaxed to +inf (or to the least number)
+fn bound_add(a: ExtendedNum, b: ExtendedNum) -> ExtendedNum {
+    match (a, b) {
+        (ExtendedNum::Num(a), ExtendedNum::Num(b)) => match a.checked_add(b) {
+            Some(c) => ExtendedNum::Num(c),
+            None if a > 0 => ExtendedNum::PosInf,
+            None => ExtendedNum::Num(Num::MIN),
+        },
+        (a, b) => a + b,
+    }
+}
+
+impl Dbm {
+    fn new() -> Self {
+        Dbm { vars: Vec::new(), m: Vec::new() }
+    }
+
+    fn index(&self, x: &str) -> Option<usize> {
+        self.vars.iter().position(|v| v == x)
+    }
+    fn add_var(&mut self, x: &str) -> usize {
+        if let Some(i) = self.index(x) {
+            return i;
+        }
+        let n = 2 * self.vars.len();
+        for row in self.m.iter_mut() {
+            row.append(&mut vec![ExtendedNum::PosInf; 2]);
+        }
+        for i in [n, n + 1] {
+            let mut row = vec![ExtendedNum::PosInf; n + 2];
+            row[i] = ExtendedNum::Num(0);
+            self.m.push(row);
+        }
+        self.vars.push(x.to_string());
+        self.vars.len() - 1
+    }
+    fn remove_var(&mut self, x: &str) {
+        if let Some(v) = self.index(x) {
+            self.m.remove(2 * v + 1);
+            self.m.remove(2 * v);
+            for row in self.m.iter_mut() {
+                row.remove(2 * v + 1);
+                row.remove(2 * v);
+            }
+            self.vars.remove(v);
+        }
+    }
+    fn rename_var(&mut self, from: &str, to: &str) {
+        if let Some(v) = self.index(from) {
+            self.vars[v] = to.to_string();
+        }
+    }
+    // Extend both the DBMs to the same variables in the same order
+    fn align(&mut self, other: &mut Dbm) {
+        for x in other.vars.clone() {
+            self.add_var(&x);
+        }
+        for x in self.vars.clone() {
+            other.add_var(&x);
+        }
+        let perm = self.vars.iter().map(|x| other.index(x).unwrap()).collect_vec();
+        let idx = |i: usize| 2 * perm[i / 2] + i % 2;
+        let n = 2 * self.vars.len();
+        other.m = (0..n).map(|i| (0..n).map(|j| other.m[idx(i)][idx(j)]).collect()).collect();
+        other.vars = self.vars.clone();
+    }
+
+    // Add the octagonal constraint V(i) - V(j) <= c (and its coherent one)
+    fn add_bound(&mut self, i: usize, j: usize, c: ExtendedNum) {
+        self.m[j][i] = min(self.m[j][i], c);
+        self.m[bar(i)][bar(j)] = min(self.m[bar(i)][bar(j)], c);
+    }
+    fn forget(&mut self, v: usize) {
+        for k in 0..self.m.len() {
+            for i in [2 * v, 2 * v + 1] {
+                if k != i {
+                    self.m[i][k] = ExtendedNum::PosInf;
+                    self.m[k][i] = ExtendedNum::PosInf;
+                }
+            }
+        }
+    }
+    fn interval(&self, v: usize) -> IntervalDomain {
+        let upper = half_floor(self.m[2 * v + 1][2 * v]);
+        let lower = neg(half_floor(self.m[2 * v][2 * v + 1]));
+        IntervalDomain::new(lower, upper)
+    }
+
+    // Tight closure for integer octagons (Bagnara et al.), false if the octagon is empty
+    fn close(&mut self) -> bool {
+        let n = self.m.len();
+        for k in 0..n {
+            for i in 0..n {
+                if self.m[i][k] == ExtendedNum::PosInf { continue; }
+                for j in 0..n {
+                    let c = bound_add(self.m[i][k], self.m[k][j]);
+                    if c < self.m[i][j] {
+                        self.m[i][j] = c;
+                    }
+                }
+            }
+        }
+        if (0..n).any(|i| self.m[i][i] < ExtendedNum::Num(0)) {
+            return false;
+        }
+        // tightening: 2x <= c  =>  2x <= 2*floor(c/2)
+        for i in 0..n {
+            let c = half_floor(self.m[i][bar(i)]);
+            self.m[i][bar(i)] = bound_add(c, c);
+        }
+        if (0..n).any(|i| bound_add(self.m[i][bar(i)], self.m[bar(i)][i]) < ExtendedNum::Num(0)) {
+            return false;
+        }
+        // strengthening: V(j) - V(i) <= (V(j) - V(-j))/2 + (V(-i) - V(i))/2
+        for i in 0..n {
+            for j in 0..n {
+                let c = bound_add(half_floor(self.m[i][bar(i)]), half_floor(self.m[bar(j)][j]));
+                if c < self.m[i][j] {
+                    self.m[i][j] = c;
+                }
+            }
+        }
+        for i in 0..n {
+            self.m[i][i] = ExtendedNum::Num(0);
+        }
+        true
+    }
+
+    fn pointwise(mut self, mut other: Dbm, f: impl Fn(ExtendedNum, ExtendedNum) -> ExtendedNum) -> Dbm {
+        self.align(&mut other);
+        for (row1, row2) in self.m.iter_mut().zip(other.m) {
+            for (c1, c2) in row1.iter_mut().zip(row2) {
+                *c1 = f(*c1, c2);
+            }
+        }
+        self
+    }
+
+    // Entry-wise comparison of aligned DBMs
+    fn compare(&self, other: &Dbm) -> Option<Ordering> {
+        let (mut d1, mut d2) = (self.clone(), other.clone());
+        d1.align(&mut d2);
+        let mut ord = Ordering::Equal;
+        for (row1, row2) in d1.m.iter().zip(d2.m.iter()) {
+            for (c1, c2) in row1.iter().zip(row2) {
+                match (c1.cmp(c2), ord) {
+                    (Ordering::Equal, _) => (),
+                    (o, Ordering::Equal) => ord = o,
+                    (o, ord) if o == ord => (),
+                    _ => return None,
+                }
+            }
+        }
+        Some(ord)
+    }
+}
+
+impl OctagonState {
+    // Close the DBM, becoming bottom if it is empty
+    fn closed(self) -> Self {
+        match self.0 {
+            Some(mut dbm) => if dbm.close() { OctagonState(Some(dbm)) } else { OctagonState(None) },
+            None => OctagonState(None),
+        }
+    }
+
+    fn get_interval(&self, k: &str) -> IntervalDomain {
+        match &self.0 {
+            Some(dbm) => match dbm.index(k) {
+                Some(v) => dbm.interval(v),
+                None => IntervalDomain::Top,
+            },
+            None => IntervalDomain::Bottom,
+        }
+    }
+
+    // Lower bound of a linear expression using only the non-relational information,
+    // None if it is -inf or does not fit in a number
+    fn lower_linear(&self, e: &LinearExpr) -> Option<Num> {
+        e.coeffs.iter().try_fold(e.constant.0, |acc, (x, k)| {
+            let IntervalDomain::Range(l, u) = self.get_interval(x) else { return None };
+            match if *k > 0 { l } else { u } {
+                ExtendedNum::Num(b) => acc.checked_add(k.checked_mul(b)?),
+                _ => None,
+            }
+        })
+    }
+
+    // Add the constraint: e <= 0 where e has only unit coefficients on one or two variables
+    fn add_octagonal(dbm: &mut Dbm, e: &LinearExpr) {
+        let c = e.constant.0.checked_neg().map_or(ExtendedNum::PosInf, ExtendedNum::Num);
+        let terms = e.coeffs.iter().map(|(x, k)| {
+            let v = dbm.add_var(x);
+            if *k > 0 { 2 * v } else { 2 * v + 1 }
+        }).collect_vec();
+        match terms[..] {
+            // ±x <= c  <=>  ±x - (∓x) <= 2c
+            [i] => dbm.add_bound(i, bar(i), bound_add(c, c)),
+            // ±x ±y <= c  <=>  ±x - (∓y) <= c
+            [i, j] => dbm.add_bound(i, bar(j), c),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl LinearConstraints for OctagonState {
+    fn add_constraint(&mut self, e: &LinearExpr) {
+        if self.0.is_none() {
+            return;
+        }
+        if e.is_constant() {
+            if e.constant.0 > 0 {
+                self.0 = None;
+            }
+            return;
+        }
+        let mut constraints = Vec::new();
+        if e.coeffs.len() <= 2 && e.coeffs.values().all(|k| k.abs() == 1) {
+            constraints.push(e.clone());
+        } else {
+            // Non-octagonal constraint: keep one or two terms and bound the rest with intervals
+            //   t + rest <= 0  =>  t + lower(rest) <= 0
+            let rest_lower = |keep: &[&Var]| {
+                let mut rest = e.clone();
+                for x in keep {
+                    rest.coeffs.remove(*x);
+                }
+                self.lower_linear(&rest)
+            };
+            for (x, k) in e.coeffs.iter() {
+                // k*x <= -l  =>  sign(k)*x <= floor(-l/|k|)
+                let bound = rest_lower(&[x]).and_then(|l| Some(l.checked_neg()?.div_euclid(k.checked_abs()?)));
+                if let Some(b) = bound {
+                    constraints.extend(LinearExpr::var(x).scale(k.signum()).and_then(|t| t.add(LinearExpr::constant(-b, -b))));
+                }
+            }
+            for ((x1, k1), (x2, k2)) in e.coeffs.iter().tuple_combinations() {
+                if k1.abs() != 1 || k2.abs() != 1 {
+                    continue;
+                }
+                if let Some(l) = rest_lower(&[x1, x2]) {
+                    let c = LinearExpr::var(x1).scale(*k1).zip(LinearExpr::var(x2).scale(*k2)).and_then(|(t1, t2)| t1.add(t2));
+                    constraints.extend(c.and_then(|c| c.add(LinearExpr::constant(l, l))));
+                }
+            }
+        }
+        if let Some(dbm) = &mut self.0 {
+            for c in constraints {
+                Self::add_octagonal(dbm, &c);
+            }
+        }
+        *self = self.clone().closed();
+    }
+}
+
+impl AbstractState<IntervalDomain> for OctagonState {
+    // {"variables": {x: interval}, "constraints": ["x-y <= c", ...]}
+    fn to_json(&self) -> Value {
+        let Some(dbm) = &self.0 else { return Value::Null };
+        let (intervals, constraints) = dbm.describe();
+        let variables = intervals.into_iter().map(|(x, itv)| (x.clone(), itv.to_json())).collect::<serde_json::Map<_, _>>();
+        json!({"variables": variables, "constraints": constraints})
+    }
+    fn bottom() -> Self {
+        OctagonState(None)
+    }
+    fn top() -> Self {
+        OctagonState(Some(Dbm::new()))
+    }
+
+    fn lub(self, other: &Self) -> Self {
+        match (self.0, &other.0) {
+            (Some(d1), Some(d2)) => OctagonState(Some(d1.pointwise(d2.clone(), |c1, c2| c1.max(c2)))),
+            (Some(d), None) => OctagonState(Some(d)),
+            (None, d) => OctagonState(d.clone()),
+        }
+    }
+    fn glb(self, other: &Self) -> Self {
+        match (self.0, &other.0) {
+            (Some(d1), Some(d2)) => OctagonState(Some(d1.pointwise(d2.clone(), |c1, c2| c1.min(c2)))).closed(),
+            (_, _) => OctagonState(None),
+        }
+    }
+
+    fn get(&self, k: &str) -> IntervalDomain {
+        self.get_interval(k)
+    }
+    // Every bound V(j) - V(i) <= m[i][j] on the variables of the state must hold
+    fn contains(&self, state: &State<Num>) -> bool {
+        let Some(dbm) = &self.0 else { return false };
+        let value = |i: usize| state.get(&dbm.vars[i / 2]).map(|n| if i.is_multiple_of(2) { *n } else { -*n });
+        (0..dbm.m.len()).cartesian_product(0..dbm.m.len()).all(|(i, j)| match (value(i), value(j)) {
+            // A difference out of the numbers is only bounded by +inf when it is positive
+            (Some(vi), Some(vj)) => vj.checked_sub(vi)
+                .map_or(vj < vi || dbm.m[i][j] == ExtendedNum::PosInf, |d| ExtendedNum::Num(d) <= dbm.m[i][j]),
+            _ => true,
+        })
+    }
+    fn set(&mut self, k: String, v: IntervalDomain) {
+        if v == IntervalDomain::Bottom {
+            self.0 = None;
+        }
+        if let Some(dbm) = &mut self.0 {
+            let x = dbm.add_var(&k);
+            dbm.forget(x);
+        }
+        self.refine(&k, v);
+    }
+
+    fn widening(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            (None, s) | (s, None) => OctagonState(s),
+            // Not closed after widening, otherwise termination is not guaranteed
+            (Some(d1), Some(d2)) => OctagonState(Some(
+                d1.pointwise(d2, |c1, c2| if c2 <= c1 { c1 } else { ExtendedNum::PosInf })
+            )),
+        }
+    }
+    fn narrowing(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            (None, _) | (_, None) => OctagonState(None),
+            (Some(d1), Some(d2)) => OctagonState(Some(
+                d1.pointwise(d2, |c1, c2| if c1 == ExtendedNum::PosInf { c2 } else { c1 })
+            )).closed(),
+        }
+    }
+
+    fn assign(&mut self, k: String, a: &Aexpr<IntervalDomain>, v: IntervalDomain) {
+        let Some(e) = LinearExpr::from_aexpr(a) else {
+            return self.set(k, v)
+        };
+        if self.0.is_none() {
+            return;
+        }
+        // tmp := a, then forget k and rename tmp as k
+        let tmp = LinearExpr::var(TMP_VAR);
+        let (Some(c1), Some(c2)) = (
+            e.clone().neg().and_then(|ne| tmp.clone().add(ne)),  // tmp - a <= 0
+            tmp.neg().and_then(|nt| e.add(nt)),                  // a - tmp <= 0
+        ) else {
+            return self.set(k, v)
+        };
+        self.add_constraint(&c1);
+        self.add_constraint(&c2);
+        self.refine(TMP_VAR, v);
+        if let Some(dbm) = &mut self.0 {
+            dbm.remove_var(&k);
+            dbm.rename_var(TMP_VAR, &k);
+        }
+    }
+    fn refine(&mut self, k: &str, v: IntervalDomain) {
+        match v {
+            IntervalDomain::Bottom => self.0 = None,
+            IntervalDomain::Top => (),
+            IntervalDomain::Range(l, u) => {
+                if let ExtendedNum::Num(u) = u {
+                    self.add_checked(u.checked_neg().and_then(|c| LinearExpr::var(k).add(LinearExpr::constant(c, c))));
+                }
+                if let ExtendedNum::Num(l) = l {
+                    self.add_checked(LinearExpr::var(k).neg().and_then(|e| e.add(LinearExpr::constant(l, l))));
+                }
+            }
+        }
+    }
+    fn assume(self, b: &Bexpr<IntervalDomain>) -> Self {
+        assume_bexpr(self, b)
+    }
+}
+
+impl PartialEq for OctagonState {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+impl PartialOrd for OctagonState {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.clone().closed().0, other.clone().closed().0) {
+            (None, None) => Some(Ordering::Equal),
+            (None, Some(_)) => Some(Ordering::Less),
+            (Some(_), None) => Some(Ordering::Greater),
+            (Some(d1), Some(d2)) => d1.compare(&d2),
+        }
+    }
+}
+
+impl FromStr for OctagonState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let state = s.parse::<HashMapState<IntervalDomain>>()?;
+        let mut oct = OctagonState::top();
+        for (x, v) in state.0.unwrap_or_default() {
+            oct.refine(&x, v);
+        }
+        Ok(oct)
+    }
+}
+
+impl Dbm {
+    // The interval of every variable (sorted by name) and the relational constraints not implied by them
+    fn describe(&self) -> (Vec<(&Var, IntervalDomain)>, Vec<String>) {
+        let dbm = self;
+        let mut constraints = vec![];
+        let vars = dbm.vars.iter().enumerate().sorted_by_key(|(_, x)| *x).collect_vec();
+        let intervals = vars.iter().map(|(v, x)| (*x, dbm.interval(*v))).collect_vec();
+        for ((v1, x1), (v2, x2)) in vars.iter().tuple_combinations() {
+            let (i, j) = (2 * v1, 2 * v2);
+            // (constraint, a, b) for V(b) - V(a) <= m[a][b]
+            let bounds = [
+                (format!("{x1}-{x2}"), j, i),
+                (format!("{x2}-{x1}"), i, j),
+                (format!("{x1}+{x2}"), j + 1, i),
+                (format!("-{x1}-{x2}"), j, i + 1),
+            ];
+            for (lhs, a, b) in bounds {
+                let c = dbm.m[a][b];
+                // Skip the constraints implied by the variable intervals
+                let implied = half_floor(dbm.m[a][bar(a)]) + half_floor(dbm.m[bar(b)][b]) <= c;
+                if c != ExtendedNum::PosInf && !implied {
+                    constraints.push(format!("{lhs} <= {c}"));
+                }
+            }
+        }
+        (intervals, constraints)
+    }
+}
+
+impl Display for OctagonState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(dbm) = &self.0 else {
+            return write!(f, "⊥")
+        };
+        let (intervals, relational) = dbm.describe();
+        let constraints = intervals.into_iter()
+            .filter(|(_, itv)| *itv != IntervalDomain::Top)
+            .map(|(x, itv)| format!("{x}: {itv}"))
+            .chain(relational)
+            .collect_vec();
+        if constraints.is_empty() {
+            write!(f, "⊤")
+        } else {
+            write!(f, "{{{}}}", constraints.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The constraint sum(k*x) <= c
+    fn leq(terms: &[(&str, Num)], c: Num) -> LinearExpr {
+        LinearExpr {
+            coeffs: terms.iter().map(|(x, k)| (x.to_string(), *k)).collect(),
+            constant: (-c, -c),
+        }
+    }
+    fn octagon(constraints: &[LinearExpr]) -> OctagonState {
+        let mut s = OctagonState::top();
+        for c in constraints {
+            s.add_constraint(c);
+        }
+        s
+    }
+    fn range(l: Num, u: Num) -> IntervalDomain {
+        IntervalDomain::new(ExtendedNum::Num(l), ExtendedNum::Num(u))
+    }
+
+    #[test]
+    fn closure_propagates_bounds_and_relations() {
+        let s = octagon(&[leq(&[("x", 1), ("y", -1)], 0), leq(&[("y", 1), ("z", -1)], -1), leq(&[("z", 1)], 3)]);
+        assert_eq!(s.get("x"), IntervalDomain::new(ExtendedNum::NegInf, ExtendedNum::Num(2)));
+        assert_eq!(s.to_string(), "{x: [-inf,2], y: [-inf,2], z: [-inf,3], x-y <= 0, x-z <= -1, y-z <= -1}");
+    }
+
+    #[test]
+    fn tight_closure_detects_integer_emptiness() {
+        // x = y and x + y = 3 has only rational solutions
+        let s = octagon(&[
+            leq(&[("x", 1), ("y", -1)], 0), leq(&[("x", -1), ("y", 1)], 0),
+            leq(&[("x", 1), ("y", 1)], 3), leq(&[("x", -1), ("y", -1)], -3),
+        ]);
+        assert!(s.0.is_none());
+        // 2x <= 3 is tightened to 2x <= 2
+        let s = octagon(&[leq(&[("x", 1), ("y", 1)], 3), leq(&[("x", 1), ("y", -1)], 0)]);
+        assert_eq!(s.0.as_ref().unwrap().m[1][0], ExtendedNum::Num(2));
+        assert_eq!(s.get("x"), IntervalDomain::new(ExtendedNum::NegInf, ExtendedNum::Num(1)));
+    }
+
+    #[test]
+    fn join_keeps_common_relations() {
+        let s1 = octagon(&[leq(&[("x", 1)], 0), leq(&[("x", -1)], 0), leq(&[("x", 1), ("y", -1)], 0), leq(&[("x", -1), ("y", 1)], 0)]);
+        let s2 = octagon(&[leq(&[("x", 1)], 2), leq(&[("x", -1)], -2), leq(&[("x", 1), ("y", -1)], 0), leq(&[("x", -1), ("y", 1)], 0)]);
+        let s = s1.clone().lub(&s2);
+        assert_eq!(s.get("y"), range(0, 2));
+        assert_eq!(s.to_string(), "{x: [0,2], y: [0,2], x-y <= 0, y-x <= 0}");
+        assert!(s1 <= s && s2 <= s);
+    }
+
+    #[test]
+    fn widening_drops_unstable_bounds() {
+        let s1 = octagon(&[leq(&[("x", 1)], 0), leq(&[("x", -1)], 0), leq(&[("x", 1), ("y", -1)], 0), leq(&[("x", -1), ("y", 1)], 0)]);
+        let s2 = octagon(&[leq(&[("x", 1)], 1), leq(&[("x", -1)], 0), leq(&[("x", 1), ("y", -1)], 0), leq(&[("x", -1), ("y", 1)], 0)]);
+        let s = s1.clone().widening(s1.lub(&s2));
+        assert_eq!(s.get("x"), IntervalDomain::new(ExtendedNum::Num(0), ExtendedNum::PosInf));
+        assert_eq!(s.clone().closed().to_string(), "{x: [0,+inf], y: [0,+inf], x-y <= 0, y-x <= 0}");
+        assert!(s2 <= s);
+    }
+
+    #[test]
+    fn overflowing_bounds_are_relaxed() {
+        let s = octagon(&[
+            leq(&[("x", 1)], Num::MAX), leq(&[("x", -1)], -Num::MAX),
+            leq(&[("y", 1)], -Num::MAX), leq(&[("y", -1)], Num::MAX),
+        ]);
+        assert_eq!(s.get("x").to_string(), "[85070591730234615865843651857942052864,+inf]");
+        assert!(s.contains(&State::from([("x".to_string(), Num::MAX), ("y".to_string(), -Num::MAX)])));
+    }
+}
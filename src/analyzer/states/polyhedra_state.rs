@@ -0,0 +1,616 @@
+use std::{cmp::Ordering, collections::{BTreeMap, BTreeSet}, fmt::Display, str::FromStr};
+
+use iter_tools::Itertools;
+use num_bigint::BigInt;
+use num_traits::{One, ToPrimitive, Zero};
+use serde_json::{json, Value};
+
+use crate::{analyzer::{domains::{extended_num::ExtendedNum, interval_domain::IntervalDomain, rational::{gcd, lcm, Rational}}, linear_expr::{assume_bexpr, LinearConstraints, LinearExpr}, types::{domain::AbstractDomain, state::AbstractState}}, interpreter::types::State, types::ast::{Aexpr, Bexpr, Num, Var}};
+
+use super::hashmap_state::HashMapState;
+
+
+// Auxiliary variables (not valid identifiers) used for projections, they are not integer valued
+const AUX_PREFIX: char = '#';
+const TMP_VAR: &str = "#tmp";
+const LAMBDA_VAR: &str = "#lambda";
+
+type Coeffs = BTreeMap<Var, Rational>;
+
+// Convex polyhedron in constraint representation: every entry (a, b) is the constraint  a·x <= b
+// (the coefficients are normalized so that parallel constraints share the same key)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyhedron {
+    constraints: BTreeMap<Coeffs, Rational>,
+}
+
+// None is bottom
+#[derive(Debug, Clone)]
+pub struct PolyhedraState(pub Option<Polyhedron>);
+
+enum Maximum {
+    Empty,
+    Unbounded,
+    Value(Rational),
+}
+
+fn is_aux(x: &str) -> bool {
+    x.starts_with(AUX_PREFIX)
+}
+fn aux_copy(x: &str) -> Var {
+    format!("{AUX_PREFIX}{x}")
+}
+fn scale(coeffs: &Coeffs, k: &Rational) -> Coeffs {
+    coeffs.iter().map(|(x, c)| (x.clone(), c * k)).collect()
+}
+fn sum(c1: &Coeffs, c2: &Coeffs) -> Coeffs {
+    let mut res = c1.clone();
+    for (x, c) in c2 {
+        let v = res.get(x).cloned().unwrap_or(Rational::from(0)) + c;
+        res.insert(x.clone(), v);
+    }
+    res
+}
+enum Normalized {
+    Trivial,
+    Infeasible,
+    Constraint(Coeffs, Rational),
+}
+
+// Scale the constraint to coprime integer coefficients
+fn normalize(coeffs: Coeffs, bound: Rational) -> Normalized {
+    let coeffs: Coeffs = coeffs.into_iter().filter(|(_, k)| !k.is_zero()).collect();
+    if coeffs.is_empty() {
+        return if bound >= Rational::from(0) { Normalized::Trivial } else { Normalized::Infeasible };
+    }
+    let den = coeffs.values().fold(BigInt::one(), |acc, k| lcm(&acc, k.den()));
+    let num_gcd = coeffs.values().fold(BigInt::zero(), |acc, k| gcd(&acc, &(k.num() * (&den / k.den()))));
+    let Some(factor) = Rational::new(den, num_gcd) else { return Normalized::Trivial };
+    let coeffs = scale(&coeffs, &factor);
+    let mut bound = bound * &factor;
+    // Integer tightening, sound only if all the variables are integer valued
+    if !coeffs.keys().any(|x| is_aux(x)) {
+        bound = Rational::from(bound.floor());
+    }
+    Normalized::Constraint(coeffs, bound)
+}
+
+fn from_linear(e: &LinearExpr) -> Coeffs {
+    e.coeffs.iter().map(|(x, k)| (x.clone(), Rational::from(*k))).collect()
+}
+
+impl Polyhedron {
+    fn new() -> Self {
+        Polyhedron { constraints: BTreeMap::new() }
+    }
+
+    // Add the constraint coeffs·x <= bound, false if it is trivially unsatisfiable
+    fn add(&mut self, coeffs: Coeffs, bound: Rational) -> bool {
+        match normalize(coeffs, bound) {
+            Normalized::Trivial => true,
+            Normalized::Infeasible => false,
+            Normalized::Constraint(coeffs, bound) => {
+                let entry = self.constraints.entry(coeffs).or_insert(bound.clone());
+                if bound < *entry {
+                    *entry = bound;
+                }
+                true
+            }
+        }
+    }
+
+    fn eliminate(&self, x: &str) -> Option<Polyhedron> {
+        self.project(|y| y != x)
+    }
+
+    // Fourier-Motzkin projection on the variables satisfying keep, None if the polyhedron is empty.
+    // Every constraint keeps the set of the original constraints it derives from, by Chernikov's rule
+    // after k eliminations the constraints derived from more than k+1 original ones are redundant
+    fn project(&self, keep: impl Fn(&str) -> bool) -> Option<Polyhedron> {
+        let mut rows: BTreeMap<Coeffs, (Rational, BTreeSet<usize>)> = self.constraints.iter()
+            .enumerate()
+            .map(|(i, (c, b))| (c.clone(), (b.clone(), BTreeSet::from([i]))))
+            .collect();
+        let mut eliminated = 0;
+        loop {
+            // Eliminate first the variable producing less constraints
+            let vars: BTreeSet<&Var> = rows.keys().flat_map(|c| c.keys()).collect();
+            let to_eliminate = vars.into_iter()
+                .filter(|x| !keep(x))
+                .min_by_key(|x| {
+                    let pos = rows.keys().filter(|c| c.get(*x).is_some_and(|k| k.signum() > 0)).count();
+                    let neg = rows.keys().filter(|c| c.get(*x).is_some_and(|k| k.signum() < 0)).count();
+                    pos * neg
+                })
+                .cloned();
+            let Some(x) = to_eliminate else {
+                return Some(Polyhedron { constraints: rows.into_iter().map(|(c, (b, _))| (c, b)).collect() });
+            };
+            eliminated += 1;
+
+            let mut new_rows = BTreeMap::new();
+            let mut insert = |c: Coeffs, b: Rational, h: BTreeSet<usize>| {
+                match normalize(c, b) {
+                    Normalized::Trivial => true,
+                    Normalized::Infeasible => false,
+                    Normalized::Constraint(c, b) => {
+                        let entry = new_rows.entry(c).or_insert((b.clone(), h.clone()));
+                        if (&b, h.len()) < (&entry.0, entry.1.len()) {
+                            *entry = (b, h);
+                        }
+                        true
+                    }
+                }
+            };
+            let (mut pos, mut neg) = (vec![], vec![]);
+            for (coeffs, (b, h)) in rows.iter() {
+                match coeffs.get(&x).map(|k| k.signum()) {
+                    Some(1) => pos.push((coeffs, b, h)),
+                    Some(-1) => neg.push((coeffs, b, h)),
+                    _ => if !insert(coeffs.clone(), b.clone(), h.clone()) { return None },
+                }
+            }
+            for ((c1, b1, h1), (c2, b2, h2)) in pos.iter().cartesian_product(neg.iter()) {
+                let h: BTreeSet<usize> = h1.union(h2).copied().collect();
+                if h.len() > eliminated + 1 {
+                    continue;
+                }
+                // |k2| * c1 + k1 * c2 has a zero coefficient for x
+                let (k1, k2) = (&c1[&x], c2[&x].abs());
+                let coeffs = sum(&scale(c1, &k2), &scale(c2, k1));
+                if !insert(coeffs, *b1 * &k2 + *b2 * k1, h) {
+                    return None;
+                }
+            }
+            rows = new_rows;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.project(|_| false).is_none()
+    }
+
+    // Maximum of coeffs·x on the polyhedron
+    fn maximize(&self, coeffs: &Coeffs) -> Maximum {
+        let mut p = self.clone();
+        let t = LinearExpr::var(TMP_VAR);
+        let t = from_linear(&t);
+        p.add(sum(&t, &scale(coeffs, &Rational::from(-1))), Rational::from(0));
+        p.add(sum(coeffs, &scale(&t, &Rational::from(-1))), Rational::from(0));
+        match p.project(|x| x == TMP_VAR) {
+            None => Maximum::Empty,
+            Some(p) => p.constraints.iter()
+                .filter(|(c, _)| c.get(TMP_VAR).is_some_and(|k| k.signum() > 0))
+                .filter_map(|(c, b)| b.checked_div(&c[TMP_VAR]))
+                .min()
+                .map_or(Maximum::Unbounded, Maximum::Value),
+        }
+    }
+
+    fn entails(&self, coeffs: &Coeffs, bound: &Rational) -> bool {
+        match self.maximize(coeffs) {
+            Maximum::Empty => true,
+            Maximum::Unbounded => false,
+            Maximum::Value(max) => max <= *bound,
+        }
+    }
+
+    // Remove the redundant constraints
+    fn minimize(mut self) -> Self {
+        let keys = self.constraints.keys().cloned().collect_vec();
+        for coeffs in keys {
+            let bound = self.constraints.remove(&coeffs).unwrap();
+            if !self.entails(&coeffs, &bound) {
+                self.constraints.insert(coeffs, bound);
+            }
+        }
+        self
+    }
+
+    fn interval(&self, x: &str) -> IntervalDomain {
+        let coeffs = Coeffs::from([(x.to_string(), Rational::from(1))]);
+        let upper = match self.maximize(&coeffs) {
+            Maximum::Empty => return IntervalDomain::Bottom,
+            Maximum::Unbounded => ExtendedNum::PosInf,
+            // A bound out of the range of Num is soundly dropped
+            Maximum::Value(v) => v.floor().to_i128().map_or(ExtendedNum::PosInf, ExtendedNum::Num),
+        };
+        let lower = match self.maximize(&scale(&coeffs, &Rational::from(-1))) {
+            Maximum::Empty => return IntervalDomain::Bottom,
+            Maximum::Unbounded => ExtendedNum::NegInf,
+            Maximum::Value(v) => (-v).ceil().to_i128().map_or(ExtendedNum::NegInf, ExtendedNum::Num),
+        };
+        IntervalDomain::new(lower, upper)
+    }
+
+    // The bounds x <= u and -x <= -l on every variable, when they are finite
+    fn implied_bounds(&self) -> Vec<(Coeffs, Rational)> {
+        let vars: BTreeSet<&Var> = self.constraints.keys().flat_map(|c| c.keys()).collect();
+        vars.into_iter()
+            .cartesian_product([1, -1])
+            .filter_map(|(x, k)| {
+                let coeffs = Coeffs::from([(x.clone(), Rational::from(k))]);
+                match self.maximize(&coeffs) {
+                    Maximum::Value(b) => Some((coeffs, b)),
+                    Maximum::Empty | Maximum::Unbounded => None,
+                }
+            })
+            .collect()
+    }
+
+    fn rename(self, from: &str, to: &str) -> Self {
+        let mut res = Polyhedron::new();
+        for (mut coeffs, b) in self.constraints {
+            if let Some(k) = coeffs.remove(from) {
+                coeffs.insert(to.to_string(), k);
+            }
+            res.add(coeffs, b);
+        }
+        res
+    }
+
+    // Closed convex hull, computed as the projection on x of (Benoy, King and Mesnard):
+    //   x = y1 + y2,  A1 y1 <= λ b1,  A2 y2 <= (1-λ) b2,  0 <= λ <= 1
+    fn hull(&self, other: &Polyhedron) -> Polyhedron {
+        let lambda = Coeffs::from([(LAMBDA_VAR.to_string(), Rational::from(1))]);
+        let mut p = Polyhedron::new();
+        for (coeffs, b) in self.constraints.iter() {
+            // A1 y1 - λ b1 <= 0
+            let y1 = coeffs.iter().map(|(x, k)| (aux_copy(x), k.clone())).collect();
+            p.add(sum(&y1, &scale(&lambda, &-b)), Rational::from(0));
+        }
+        for (coeffs, b) in other.constraints.iter() {
+            // A2 (x - y1) + λ b2 <= b2
+            let y1: Coeffs = coeffs.iter().map(|(x, k)| (aux_copy(x), -k)).collect();
+            p.add(sum(&sum(coeffs, &y1), &scale(&lambda, b)), b.clone());
+        }
+        p.add(scale(&lambda, &Rational::from(-1)), Rational::from(0));
+        p.add(lambda, Rational::from(1));
+        p.project(|x| !is_aux(x)).unwrap_or_else(Polyhedron::new).minimize()
+    }
+}
+
+impl PolyhedraState {
+    // Becomes bottom if the polyhedron is empty
+    fn checked(self) -> Self {
+        match self.0 {
+            Some(p) if p.is_empty() => PolyhedraState(None),
+            s => PolyhedraState(s),
+        }
+    }
+}
+
+impl LinearConstraints for PolyhedraState {
+    fn add_constraint(&mut self, e: &LinearExpr) {
+        if let Some(p) = &mut self.0 {
+            // coeffs·x + l <= 0
+            if !p.add(from_linear(e), -Rational::from(e.constant.0)) {
+                self.0 = None;
+                return;
+            }
+        }
+        *self = self.clone().checked();
+    }
+}
+
+impl AbstractState<IntervalDomain> for PolyhedraState {
+    // {"variables": {x: interval}, "constraints": ["2x + 3y <= 10", ...]}
+    fn to_json(&self) -> Value {
+        let Some(p) = &self.0 else { return Value::Null };
+        let variables = p.constraints.keys()
+            .flat_map(|coeffs| coeffs.keys())
+            .filter(|x| !x.starts_with(AUX_PREFIX))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|x| (x.clone(), p.interval(x).to_json()))
+            .collect::<serde_json::Map<_, _>>();
+        json!({"variables": variables, "constraints": p.describe()})
+    }
+    fn bottom() -> Self {
+        PolyhedraState(None)
+    }
+    fn top() -> Self {
+        PolyhedraState(Some(Polyhedron::new()))
+    }
+
+    fn lub(self, other: &Self) -> Self {
+        match (self.0, &other.0) {
+            (Some(p1), Some(p2)) => PolyhedraState(Some(p1.hull(p2))),
+            (Some(p), None) => PolyhedraState(Some(p)),
+            (None, p) => PolyhedraState(p.clone()),
+        }
+    }
+    fn glb(self, other: &Self) -> Self {
+        match (self.0, &other.0) {
+            (Some(mut p1), Some(p2)) => {
+                for (coeffs, b) in p2.constraints.iter() {
+                    if !p1.add(coeffs.clone(), b.clone()) {
+                        return PolyhedraState(None);
+                    }
+                }
+                PolyhedraState(Some(p1)).checked()
+            },
+            (_, _) => PolyhedraState(None),
+        }
+    }
+
+    fn get(&self, k: &str) -> IntervalDomain {
+        match &self.0 {
+            Some(p) => p.interval(k),
+            None => IntervalDomain::Bottom,
+        }
+    }
+    // Every constraint on the variables of the state must hold
+    fn contains(&self, state: &State<Num>) -> bool {
+        let Some(p) = &self.0 else { return false };
+        p.constraints.iter().all(|(coeffs, bound)| {
+            let values = coeffs.iter()
+                .map(|(x, k)| state.get(x).map(|n| k * &Rational::from(*n)))
+                .collect::<Option<Vec<_>>>();
+            match values {
+                Some(values) => values.into_iter().fold(Rational::from(0), |acc, v| acc + v) <= *bound,
+                None => true,
+            }
+        })
+    }
+    fn set(&mut self, k: String, v: IntervalDomain) {
+        if let Some(p) = &self.0 {
+            self.0 = p.eliminate(&k);
+        }
+        self.refine(&k, v);
+    }
+
+    // Standard widening (Halbwachs): the constraints of the old polyhedron still satisfied by the new one,
+    // plus the new constraints that can replace an old one without changing the old polyhedron.
+    // The old constraints include the implied bounds of the variables, which are not in the minimized
+    // form but survive when the new polyhedron entails them too
+    fn widening(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            (None, s) | (s, None) => PolyhedraState(s),
+            (Some(p1), Some(p2)) => {
+                let p1 = p1.minimize();
+                let mut res = Polyhedron::new();
+                for (coeffs, b) in p1.constraints.clone().into_iter().chain(p1.implied_bounds()) {
+                    if p2.entails(&coeffs, &b) {
+                        res.add(coeffs, b);
+                    }
+                }
+                for (coeffs, b) in p2.minimize().constraints {
+                    let mutually_redundant = p1.entails(&coeffs, &b) && p1.constraints.iter().any(|(c, cb)| {
+                        let mut swapped = p1.clone();
+                        swapped.constraints.remove(c);
+                        swapped.add(coeffs.clone(), b.clone());
+                        swapped.entails(c, cb)
+                    });
+                    if mutually_redundant {
+                        res.add(coeffs, b);
+                    }
+                }
+                PolyhedraState(Some(res))
+            }
+        }
+    }
+    // Narrowing: refine only the directions in which the old polyhedron is unbounded
+    fn narrowing(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            (None, _) | (_, None) => PolyhedraState(None),
+            (Some(p1), Some(p2)) => {
+                let mut p = p1.clone();
+                for (coeffs, b) in p2.constraints {
+                    if let Maximum::Unbounded = p1.maximize(&coeffs) {
+                        p.add(coeffs, b);
+                    }
+                }
+                PolyhedraState(Some(p.minimize())).checked()
+            }
+        }
+    }
+
+    fn assign(&mut self, k: String, a: &Aexpr<IntervalDomain>, v: IntervalDomain) {
+        let Some(e) = LinearExpr::from_aexpr(a) else {
+            return self.set(k, v)
+        };
+        if let Some(p) = &mut self.0 {
+            // tmp - a ∈ [0,0], then forget k and rename tmp as k
+            let tmp = from_linear(&LinearExpr::var(TMP_VAR));
+            let coeffs = from_linear(&e);
+            p.add(sum(&tmp, &scale(&coeffs, &Rational::from(-1))), Rational::from(e.constant.1));
+            p.add(sum(&coeffs, &scale(&tmp, &Rational::from(-1))), -Rational::from(e.constant.0));
+            self.0 = p.eliminate(&k).map(|p| p.rename(TMP_VAR, &k).minimize());
+        }
+    }
+    fn refine(&mut self, k: &str, v: IntervalDomain) {
+        match v {
+            IntervalDomain::Bottom => self.0 = None,
+            IntervalDomain::Top => (),
+            IntervalDomain::Range(l, u) => {
+                if let ExtendedNum::Num(u) = u {
+                    self.add_checked(u.checked_neg().and_then(|c| LinearExpr::var(k).add(LinearExpr::constant(c, c))));
+                }
+                if let ExtendedNum::Num(l) = l {
+                    self.add_checked(LinearExpr::var(k).neg().and_then(|e| e.add(LinearExpr::constant(l, l))));
+                }
+            }
+        }
+    }
+    fn assume(self, b: &Bexpr<IntervalDomain>) -> Self {
+        let s = assume_bexpr(self, b);
+        PolyhedraState(s.0.map(Polyhedron::minimize))
+    }
+}
+
+impl PartialEq for PolyhedraState {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+impl PartialOrd for PolyhedraState {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (&self.0, &other.0) {
+            (None, None) => Some(Ordering::Equal),
+            (None, Some(_)) => Some(Ordering::Less),
+            (Some(_), None) => Some(Ordering::Greater),
+            (Some(p1), Some(p2)) => {
+                let less = p2.constraints.iter().all(|(c, b)| p1.entails(c, b));
+                let greater = p1.constraints.iter().all(|(c, b)| p2.entails(c, b));
+                match (less, greater) {
+                    (true, true) => Some(Ordering::Equal),
+                    (true, false) => Some(Ordering::Less),
+                    (false, true) => Some(Ordering::Greater),
+                    (false, false) => None,
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for PolyhedraState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let state = s.parse::<HashMapState<IntervalDomain>>()?;
+        let mut poly = PolyhedraState::top();
+        for (x, v) in state.0.unwrap_or_default() {
+            poly.refine(&x, v);
+        }
+        Ok(poly)
+    }
+}
+
+fn format_linear(coeffs: &Coeffs) -> String {
+    let mut str = String::new();
+    for (i, (x, k)) in coeffs.iter().enumerate() {
+        let sign = match (i, k.signum()) {
+            (0, -1) => "-",
+            (0, _) => "",
+            (_, -1) => " - ",
+            (_, _) => " + ",
+        };
+        if k.abs() == Rational::from(1) {
+            str += &format!("{sign}{x}");
+        } else {
+            str += &format!("{sign}{}*{x}", k.abs());
+        }
+    }
+    str
+}
+
+impl Polyhedron {
+    // The constraints as strings, the opposite inequalities with the same bound become an equality
+    fn describe(&self) -> Vec<String> {
+        let p = self;
+        let mut constraints = vec![];
+        let sorted = p.constraints.iter().sorted_by_key(|(c, _)| (c.len(), c.keys().cloned().collect_vec()));
+        for (coeffs, b) in sorted {
+            let opposite = scale(coeffs, &Rational::from(-1));
+            let opposite_bound = p.constraints.get(&opposite);
+            let first_sign = coeffs.values().next().map_or(1, |k| k.signum());
+            match opposite_bound {
+                // a·x = b printed only once with positive leading coefficient
+                Some(ob) if *ob == -b => if first_sign > 0 {
+                    constraints.push(format!("{} = {b}", format_linear(coeffs)));
+                },
+                // -a·x <= b printed as a·x >= -b
+                _ if first_sign < 0 => constraints.push(format!("{} >= {}", format_linear(&opposite), -b)),
+                _ => constraints.push(format!("{} <= {b}", format_linear(coeffs))),
+            }
+        }
+        constraints
+    }
+}
+
+impl Display for PolyhedraState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(p) = &self.0 else {
+            return write!(f, "⊥")
+        };
+        let constraints = p.describe();
+        if constraints.is_empty() {
+            write!(f, "⊤")
+        } else {
+            write!(f, "{{{}}}", constraints.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coeffs(terms: &[(&str, Num)]) -> Coeffs {
+        terms.iter().map(|(x, k)| (x.to_string(), Rational::from(*k))).collect()
+    }
+    fn polyhedron(constraints: &[(&[(&str, Num)], Num)]) -> Polyhedron {
+        let mut p = Polyhedron::new();
+        for (terms, b) in constraints {
+            assert!(p.add(coeffs(terms), Rational::from(*b)));
+        }
+        p
+    }
+    fn ratio(num: Num, den: Num) -> Rational {
+        Rational::new(BigInt::from(num), BigInt::from(den)).unwrap()
+    }
+
+    #[test]
+    fn constraints_are_normalized() {
+        // 2/3 x + 4/3 y <= 5/3  =>  x + 2y <= floor(5/2)
+        let c = Coeffs::from([("x".to_string(), ratio(2, 3)), ("y".to_string(), ratio(4, 3))]);
+        match normalize(c, ratio(5, 3)) {
+            Normalized::Constraint(c, b) => {
+                assert_eq!(c, coeffs(&[("x", 1), ("y", 2)]));
+                assert_eq!(b, Rational::from(2));
+            },
+            _ => panic!("expected a constraint"),
+        }
+        assert!(matches!(normalize(coeffs(&[("x", 0)]), Rational::from(-1)), Normalized::Infeasible));
+        // Parallel constraints share the key and keep the tightest bound
+        let p = polyhedron(&[(&[("x", 2)], 4), (&[("x", 1)], 3)]);
+        assert_eq!(p.constraints, BTreeMap::from([(coeffs(&[("x", 1)]), Rational::from(2))]));
+    }
+
+    #[test]
+    fn projection_keeps_the_implied_constraints() {
+        let p = polyhedron(&[(&[("x", 1), ("y", -1)], 0), (&[("y", 1), ("z", -1)], 0), (&[("z", 1)], 5)]);
+        let q = p.eliminate("y").unwrap();
+        assert!(q.constraints.keys().all(|c| !c.contains_key("y")));
+        assert!(q.entails(&coeffs(&[("x", 1), ("z", -1)]), &Rational::from(0)));
+        assert!(q.entails(&coeffs(&[("x", 1)]), &Rational::from(5)));
+        assert!(!q.entails(&coeffs(&[("x", 1)]), &Rational::from(4)));
+        // x <= y - 1 and y <= x is empty
+        let p = polyhedron(&[(&[("x", 1), ("y", -1)], -1), (&[("y", 1), ("x", -1)], 0)]);
+        assert!(p.is_empty());
+    }
+
+    #[test]
+    fn hull_of_two_points_is_the_segment() {
+        let p1 = polyhedron(&[(&[("x", 1)], 0), (&[("x", -1)], 0), (&[("y", 1)], 0), (&[("y", -1)], 0)]);
+        let p2 = polyhedron(&[(&[("x", 1)], 2), (&[("x", -1)], -2), (&[("y", 1)], 4), (&[("y", -1)], -4)]);
+        let h = p1.hull(&p2);
+        assert!(h.entails(&coeffs(&[("x", 2), ("y", -1)]), &Rational::from(0)));
+        assert!(h.entails(&coeffs(&[("x", -2), ("y", 1)]), &Rational::from(0)));
+        assert_eq!(h.interval("x"), IntervalDomain::new(ExtendedNum::Num(0), ExtendedNum::Num(2)));
+        assert_eq!(h.interval("y"), IntervalDomain::new(ExtendedNum::Num(0), ExtendedNum::Num(4)));
+        assert_eq!(h.constraints.len(), 4);
+    }
+
+    #[test]
+    fn join_and_widening() {
+        let s1 = PolyhedraState(Some(polyhedron(&[(&[("x", 1)], 0), (&[("x", -1)], 0), (&[("x", 1), ("y", -1)], 0), (&[("y", 1), ("x", -1)], 0)])));
+        let s2 = PolyhedraState(Some(polyhedron(&[(&[("x", 1)], 1), (&[("x", -1)], -1), (&[("x", 1), ("y", -1)], 0), (&[("y", 1), ("x", -1)], 0)])));
+        let s = s1.clone().lub(&s2);
+        assert_eq!(s.to_string(), "{x >= 0, x <= 1, x - y = 0}");
+        let w = s1.widening(s);
+        assert_eq!(w.get("x"), IntervalDomain::new(ExtendedNum::Num(0), ExtendedNum::PosInf));
+        assert_eq!(w.get("y"), IntervalDomain::new(ExtendedNum::Num(0), ExtendedNum::PosInf));
+        assert!(s2 <= w);
+    }
+
+    #[test]
+    fn widening_keeps_the_implied_bounds() {
+        // i >= 0 is implied by 0 <= a <= 4*i, and also by 0 <= a <= 16*i
+        let p1 = polyhedron(&[(&[("a", -1)], 0), (&[("a", 1), ("i", -4)], 0)]);
+        let p2 = polyhedron(&[(&[("a", -1)], 0), (&[("a", 1), ("i", -16)], 0)]);
+        let w = PolyhedraState(Some(p1)).widening(PolyhedraState(Some(p2)));
+        assert_eq!(w.get("i"), IntervalDomain::new(ExtendedNum::Num(0), ExtendedNum::PosInf));
+    }
+}
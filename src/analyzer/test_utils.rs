@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+
+use crate::{parser::parse_string, types::{ast::Module, lit_interval::LitInterval}};
+
+use super::{analyzers::generic_analyzer::GenericAnalyzer, interprocedural::{analyze_program, with_summaries, ProcedureAnalysis}, types::{analyzer::{IterationOrder, IterationStrategy, StaticAnalyzer}, domain::AbstractDomain, program::{Label, Program}, state::AbstractState}};
+
+
+// Analysis of a source program from the top state, shared by the tests of the analyzer
+pub struct Analysis<B: Clone, S> {
+    // Control flow graph of the main program, with the calls replaced by the summaries
+    pub prog: Program<B>,
+    // The main program without the abstraction of the literals
+    pub concrete_prog: Program<LitInterval>,
+    pub states: HashMap<Label, S>,
+    pub procedures: Vec<ProcedureAnalysis<B, S>>,
+}
+
+pub fn analyze<B, S>(source: &str, iteration_strategy: IterationStrategy) -> Analysis<B, S>
+    where B: AbstractDomain, S: AbstractState<B> {
+    analyze_module(parse_string::<LitInterval>(source.to_string()).unwrap(), iteration_strategy)
+}
+
+pub fn analyze_module<B, S>(module: Module<LitInterval>, iteration_strategy: IterationStrategy) -> Analysis<B, S>
+    where B: AbstractDomain, S: AbstractState<B> {
+    let Module { procedures, main } = module;
+    let concrete_prog = Program::from(main.clone());
+    let prog = GenericAnalyzer::<B, S>::init(main);
+    let mut procedures = procedures.into_iter().map(ProcedureAnalysis::new).collect::<Vec<_>>();
+    let states = analyze_program(&prog, &mut procedures, S::top(), iteration_strategy, IterationOrder::RoundRobin);
+    let prog = with_summaries(&prog, &procedures);
+    Analysis { prog, concrete_prog, states, procedures }
+}
@@ -0,0 +1,100 @@
+use std::{cell::RefCell, collections::BTreeSet};
+
+use crate::types::{ast::{Aexpr, Bexpr, Num}, lit_interval::LitInterval};
+
+use super::{domains::extended_num::ExtendedNum, types::program::{Command, Program}};
+
+
+// Landing points for the widening of the interval domains: an unstable bound jumps to the
+// closest threshold before going to infinity. They belong to the thread running the analysis,
+// so that the analyses of the unit tests (run in parallel) do not see each other's thresholds
+thread_local! {
+    static THRESHOLDS: RefCell<BTreeSet<Num>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+pub fn add_thresholds(thresholds: impl IntoIterator<Item = Num>) {
+    THRESHOLDS.with_borrow_mut(|t| t.extend(thresholds));
+}
+
+// Smallest threshold >= n, or +inf
+pub fn threshold_above(n: ExtendedNum) -> ExtendedNum {
+    match n {
+        ExtendedNum::Num(n) => THRESHOLDS.with_borrow(|t| t.range(n..).next().map_or(ExtendedNum::PosInf, |t| ExtendedNum::Num(*t))),
+        inf => inf,
+    }
+}
+
+// Greatest threshold <= n, or -inf
+pub fn threshold_below(n: ExtendedNum) -> ExtendedNum {
+    match n {
+        ExtendedNum::Num(n) => THRESHOLDS.with_borrow(|t| t.range(..=n).next_back().map_or(ExtendedNum::NegInf, |t| ExtendedNum::Num(*t))),
+        inf => inf,
+    }
+}
+
+// Whether the bound can be the result of a widening
+pub fn is_widened_bound(n: ExtendedNum) -> bool {
+    match n {
+        ExtendedNum::Num(n) => THRESHOLDS.with_borrow(|t| t.contains(&n)),
+        _ => true,
+    }
+}
+
+// The literals of the tests of the program, with their neighbours since a strict comparison
+// like x < c is bounded by c - 1 (or c + 1)
+pub fn program_thresholds(prog: &Program<LitInterval>) -> BTreeSet<Num> {
+    fn aexpr_literals(a: &Aexpr<LitInterval>, lits: &mut BTreeSet<Num>) {
+        match a {
+            Aexpr::Lit(LitInterval(l, u)) => { lits.insert(*l); lits.insert(*u); },
+            Aexpr::Var(..) | Aexpr::PreOp(..) | Aexpr::PostOp(..) | Aexpr::Len(..) => (),
+            Aexpr::Index(_, i, _) => aexpr_literals(i, lits),
+            Aexpr::BinOp(_, a1, a2, _) => { aexpr_literals(a1, lits); aexpr_literals(a2, lits) },
+            Aexpr::Call(_, args, _) => args.iter().for_each(|a| aexpr_literals(a, lits)),
+        }
+    }
+    fn bexpr_literals(b: &Bexpr<LitInterval>, lits: &mut BTreeSet<Num>) {
+        match b {
+            Bexpr::True | Bexpr::False => (),
+            Bexpr::Equal(a1, a2, _) | Bexpr::LessEq(a1, a2, _) | Bexpr::Less(a1, a2, _) | Bexpr::NotEqual(a1, a2, _) =>
+                { aexpr_literals(a1, lits); aexpr_literals(a2, lits) },
+            Bexpr::Not(b) => bexpr_literals(b, lits),
+            Bexpr::And(b1, b2) | Bexpr::Or(b1, b2) => { bexpr_literals(b1, lits); bexpr_literals(b2, lits) },
+        }
+    }
+    let mut lits = BTreeSet::new();
+    for (_, cmd, _) in &prog.arcs {
+        if let Command::Test(_, b) = cmd {
+            bexpr_literals(b, &mut lits);
+        }
+    }
+    lits.iter()
+        .flat_map(|c| [c.checked_sub(1), Some(*c), c.checked_add(1)])
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{analyzer::{domains::interval_domain::IntervalDomain, states::hashmap_state::HashMapState, test_utils::analyze, types::analyzer::IterationStrategy}, parser::parse_string};
+
+    use super::*;
+
+    fn thresholds_of(source: &str) -> BTreeSet<Num> {
+        program_thresholds(&Program::from(parse_string::<LitInterval>(source.to_string()).unwrap().main))
+    }
+
+    #[test]
+    fn literals_of_the_tests() {
+        let thresholds = thresholds_of("x := 7; while x < 40 and y != 13 do { x := x + 1; if a[z + 5] <= [0,2] then skip; else skip; }");
+        assert_eq!(thresholds.into_iter().collect::<Vec<_>>(), [-1, 0, 1, 2, 3, 4, 5, 6, 12, 13, 14, 39, 40, 41]);
+    }
+
+    #[test]
+    fn widening_stops_at_the_harvested_thresholds() {
+        let source = "x := 0; while x < 40 do x := x + 1;";
+        add_thresholds(thresholds_of(source));
+        let analysis = analyze::<IntervalDomain, HashMapState<IntervalDomain>>(source, IterationStrategy::Widening { delay: 0 });
+        let head = analysis.prog.widening_points[0];
+        assert_eq!(analysis.states[&head].to_string(), "{x: [0,40]}");
+    }
+}
@@ -16,12 +16,29 @@ pub trait StaticAnalyzer<B: AbstractDomain, D: AbstractState<B>> {
         // println!("\nAbstract Program: {:?}\n\n", abs_prog);
         abs_prog
     }
-    fn analyze(p: Program<B>, init_state: D, iteration_strategy: IterationStrategy) -> HashMap<Label, D>;
+    fn analyze(p: Program<B>, init_state: D, iteration_strategy: IterationStrategy, iteration_order: IterationOrder) -> HashMap<Label, D>;
     
 } 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum IterationStrategy{
     Simple,
-    Widening,
-    WideningAndNarrowing,
+    // delay: number of visits of a widening point with a plain join before widening
+    Widening { delay: u32 },
+    WideningAndNarrowing { delay: u32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum IterationOrder{
+    // Every label is recomputed at each iteration, until nothing changes
+    RoundRobin,
+    // Only the successors of the changed labels, in weak topological order
+    Worklist,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum WideningPoints{
+    // The head of every loop
+    LoopHeads,
+    // A minimal set of labels that cuts every cycle of the control flow graph
+    FeedbackVertexSet,
 }
\ No newline at end of file
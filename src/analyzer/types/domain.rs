@@ -1,10 +1,11 @@
 use std::ops::{Add, Div, Mul, Sub};
 use std::fmt::{Display, Debug};
 use std::str::FromStr;
+use serde_json::{json, Value};
 use crate::types::ast::{Num, Operator};
 use crate::types::lit_interval::LitInterval;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Interval {
     OpenLeft (Num),
     OpenRight (Num),
@@ -25,6 +26,10 @@ impl Display for Interval {
     }
 }
 
+// Numbers that do not fit in 64 bits are strings in the JSON output
+pub fn num_to_json(n: Num) -> Value {
+    i64::try_from(n).map_or_else(|_| json!(n.to_string()), |n| json!(n))
+}
 
 pub trait AbstractDomain : Debug + Display + Copy                  // Utils
                            + From<Num> + From<Interval> + FromStr  // Conversions
@@ -44,6 +49,12 @@ pub trait AbstractDomain : Debug + Display + Copy                  // Utils
     fn lub(self, other: Self) -> Self;
     fn glb(self, other: Self) -> Self;
 
+    // Membership of n in the concretization
+    fn contains(&self, n: Num) -> bool;
+
+    // Structured value for the JSON output, null for bottom
+    fn to_json(&self) -> Value;
+
     // Alias function for arithmetic operators
     fn abstract_operator(op: &Operator, lhs: Self, rhs: Self) -> Self {
         let res = match op {
@@ -1,22 +1,52 @@
 use std::cmp::max;
 use iter_tools::Itertools;
-use crate::types::ast::{Statement, Aexpr, Var, Bexpr};
+use crate::types::{ast::{Statement, Aexpr, Var, Bexpr}, span::Span};
 
 #[derive(Debug, Clone)]
 pub struct Program<B: Clone> {
     // entry is the first, exit the last
     pub labels_num: Label,
     pub widening_points: Vec<Label>,
-    pub arcs: Vec<Arc<B>>
+    pub arcs: Vec<Arc<B>>,
+    // Labels of the statements of the AST in pre-order (without the compositions), a statement
+    // duplicated by the desugaring (e.g. repeat-until) appears once for every copy
+    pub statements: Vec<StatementLabels>,
 }
 
 pub type Label = u32;
 pub type Arc<B> = (Label, Command<B>, Label);
 
+// The calls are never inside the expressions of the commands: every call is split into a Call arc,
+// that evaluates the arguments without changing the state of the caller, and a Return arc
 #[derive(Debug, Clone)]
 pub enum Command<B> {
     Assignment(Var, Aexpr<B>),
-    Test(Bexpr<B>),
+    Test(TestKind, Bexpr<B>),
+    // Call of the procedure with the arguments
+    Call(Var, Vec<Aexpr<B>>),
+    // Assignment to the variable of the result of the procedure called by the previous arc
+    Return(Var, Var),
+    // a[i] := e, the span locates the statement for the out of bounds alarms
+    ArrayAssign(Var, Aexpr<B>, Aexpr<B>, Span),
+    // a := array(n)
+    NewArray(Var, Aexpr<B>),
+}
+
+// Labels before and after a statement, e.g. for a while the loop head and the exit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatementLabels {
+    pub span: Span,
+    pub entry: Label,
+    pub exit: Label,
+}
+
+// Where a test comes from, all of them filter the state in the same way
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestKind {
+    // Guard of the if/while statement at stm, taken is false for the negated guard (else branch or loop exit)
+    Guard { stm: Span, taken: bool },
+    Assert(Span),
+    Assume,
 }
 
 impl<B: Clone> Program<B>{
@@ -31,13 +61,58 @@ impl<B: Clone> Program<B>{
             // entry: 0,
             widening_points,
             arcs,
+            statements: vec![],
         }
     }
+
+    fn with_statement(mut self, span: Span, children: Vec<StatementLabels>) -> Self {
+        let exit = self.labels_num - 1;
+        self.statements = [vec![StatementLabels { span, entry: 0, exit }], children].concat();
+        self
+    }
     
 
     pub fn get_entering_arcs(self: &Self, label: Label) -> Vec<&Arc<B>>{
         self.arcs.iter().filter(|(_,_,l)|l==&label).collect()
     }
+
+    // A minimal feedback vertex set: starting from the loop heads, every label whose removal
+    // leaves the other ones cutting all the cycles is removed (in nested loops often only the inner heads remain)
+    pub fn feedback_vertex_set(&self) -> Vec<Label> {
+        let mut points = self.widening_points.clone();
+        points.sort();
+        points.dedup();
+        for l in points.clone() {
+            let without_l = points.iter().copied().filter(|p| *p != l).collect_vec();
+            if self.is_acyclic_without(&without_l) {
+                points = without_l;
+            }
+        }
+        points
+    }
+
+    // Whether the control flow graph without the labels in `removed` has no cycles
+    fn is_acyclic_without(&self, removed: &[Label]) -> bool {
+        // 0: not visited, 1: on the current path, 2: done
+        fn has_cycle<B: Clone>(prog: &Program<B>, l: Label, removed: &[Label], visited: &mut Vec<u8>) -> bool {
+            visited[l as usize] = 1;
+            for (_, _, next) in prog.arcs.iter().filter(|(l1, _, _)| *l1 == l) {
+                if removed.contains(next) {
+                    continue;
+                }
+                let state = visited[*next as usize];
+                if state == 1 || (state == 0 && has_cycle(prog, *next, removed, visited)) {
+                    return true;
+                }
+            }
+            visited[l as usize] = 2;
+            false
+        }
+        let mut visited = vec![0; self.labels_num as usize];
+        (0..self.labels_num)
+            .filter(|l| !removed.contains(l))
+            .all(|l| visited[l as usize] != 0 || !has_cycle(self, l, removed, &mut visited))
+    }
 }
 
 impl<B: Clone> From<Statement<B>> for Program<B>{
@@ -47,11 +122,55 @@ impl<B: Clone> From<Statement<B>> for Program<B>{
 }
 
 
+// The calls in the expressions are evaluated first by a chain of arcs starting from label 0 (see hoist_calls_aexpr)
 fn stm_to_program<B: Clone>(stm: Statement<B>) -> Program<B>{
     match stm {
-        Statement::Assign(x, a) => 
-            Program::new(vec![(0,Command::Assignment(x,*a),1)], vec![]),
-        Statement::Skip => Program::new(Vec::new(), vec![]),
+        Statement::Assign(x, a, span) => {
+            let mut arcs = vec![];
+            match *a {
+                // The result is assigned directly, without a temporary variable
+                Aexpr::Call(p, args, _) => {
+                    let args = args.into_iter().map(|a| hoist_calls_aexpr(a, &mut arcs)).collect();
+                    push_call(&mut arcs, p, args, x);
+                },
+                a => {
+                    let a = hoist_calls_aexpr(a, &mut arcs);
+                    let l = arcs.len() as Label;
+                    arcs.push((l, Command::Assignment(x, a), l + 1));
+                },
+            }
+            Program::new(arcs, vec![]).with_statement(span, vec![])
+        },
+        Statement::ArrayAssign(x, i, a, span) => {
+            let mut arcs = vec![];
+            let i = hoist_calls_aexpr(*i, &mut arcs);
+            let a = hoist_calls_aexpr(*a, &mut arcs);
+            let l = arcs.len() as Label;
+            arcs.push((l, Command::ArrayAssign(x, i, a, span), l + 1));
+            Program::new(arcs, vec![]).with_statement(span, vec![])
+        },
+        Statement::NewArray(x, n, span) => {
+            let mut arcs = vec![];
+            let n = hoist_calls_aexpr(*n, &mut arcs);
+            let l = arcs.len() as Label;
+            arcs.push((l, Command::NewArray(x, n), l + 1));
+            Program::new(arcs, vec![]).with_statement(span, vec![])
+        },
+        Statement::Skip(span) => Program::new(Vec::new(), vec![]).with_statement(span, vec![]),
+        Statement::Assert(b, span) => {
+            let mut arcs = vec![];
+            let b = hoist_calls_bexpr(*b, &mut arcs);
+            let l = arcs.len() as Label;
+            arcs.push((l, Command::Test(TestKind::Assert(span), b), l + 1));
+            Program::new(arcs, vec![]).with_statement(span, vec![])
+        },
+        Statement::Assume(b, span) => {
+            let mut arcs = vec![];
+            let b = hoist_calls_bexpr(*b, &mut arcs);
+            let l = arcs.len() as Label;
+            arcs.push((l, Command::Test(TestKind::Assume, b), l + 1));
+            Program::new(arcs, vec![]).with_statement(span, vec![])
+        },
         Statement::Compose(s1, s2) => {
             let mut p1 = stm_to_program(*s1);
             let p2 = stm_to_program(*s2);
@@ -59,20 +178,25 @@ fn stm_to_program<B: Clone>(stm: Statement<B>) -> Program<B>{
             let mut arcs2: Vec<Arc<B>> = shift_arcs(p2.arcs.clone(), offset,p2.labels_num-1,p2.labels_num-1+offset);
             arcs2.append(&mut p1.arcs);
             let widening_points = [p1.widening_points, p2.widening_points.iter().map(|x|x+offset).collect_vec()].concat();
-            Program::new(arcs2, widening_points)
+            let statements = [p1.statements, shift_statements(p2.statements, offset, p2.labels_num-1, p2.labels_num-1+offset)].concat();
+            Program { statements, ..Program::new(arcs2, widening_points) }
         },
-        Statement::IfThenElse(b, s1, s2) => {
+        Statement::IfThenElse(b, s1, s2, span) => {
             let p1 = stm_to_program(*s1);
             let p2 = stm_to_program(*s2);
 
-            let offset_p1 = if p1.labels_num > 1 { 1 } else { 0 };
+            // The guard is tested after its calls
+            let mut arcs = vec![];
+            let b = hoist_calls_bexpr(*b, &mut arcs);
+            let head = arcs.len() as Label;
+            let offset_p1 = if p1.labels_num > 1 { head + 1 } else { head };
             let offset_p2 = offset_p1 + p1.labels_num;
             let exit_label = offset_p2 + p2.labels_num - 1 ;
             
-            let mut arcs = vec![
-                (0,Command::Test(*b.clone()),if p1.labels_num > 1 { 1 } else { exit_label }),
-                (0,Command::Test(Bexpr::Not(b)), offset_p2)
-            ];
+            arcs.append(&mut vec![
+                (head,Command::Test(TestKind::Guard { stm: span, taken: true },b.clone()),if p1.labels_num > 1 { offset_p1 } else { exit_label }),
+                (head,Command::Test(TestKind::Guard { stm: span, taken: false },Bexpr::Not(Box::new(b))), offset_p2)
+            ]);
             let mut p1_arcs: Vec<Arc<B>> = shift_arcs(p1.arcs.clone(), offset_p1,p1.labels_num-1,exit_label);
             let mut p2_arcs: Vec<Arc<B>> = shift_arcs(p2.arcs.clone(), offset_p2,p2.labels_num-1,exit_label);
             arcs.append(&mut p1_arcs);
@@ -82,16 +206,24 @@ fn stm_to_program<B: Clone>(stm: Statement<B>) -> Program<B>{
                 p1.widening_points.iter().map(|x|x+offset_p1).collect_vec(),
                 p2.widening_points.iter().map(|x|x+offset_p2).collect_vec()
             ].concat();
-            Program::new(arcs, widening_points)
+            let statements = [
+                shift_statements(p1.statements, offset_p1, p1.labels_num-1, exit_label),
+                shift_statements(p2.statements, offset_p2, p2.labels_num-1, exit_label)
+            ].concat();
+            Program::new(arcs, widening_points).with_statement(span, statements)
         },
-        Statement::While(b, s) => {
+        Statement::While(b, s, span) => {
             let p1 = stm_to_program(*s);
-            let offset = 1;
-            let exit_label = p1.labels_num;
-            let mut arcs = vec![
-                (0,Command::Test(*b.clone()), if p1.labels_num == 1 { 0 } else { 1 }),
-                (0,Command::Test(Bexpr::Not(b)), exit_label)
-            ];
+            // The calls of the guard are evaluated again at every iteration, from the loop head 0
+            let mut arcs = vec![];
+            let b = hoist_calls_bexpr(*b, &mut arcs);
+            let head = arcs.len() as Label;
+            let offset = head + 1;
+            let exit_label = head + p1.labels_num;
+            arcs.append(&mut vec![
+                (head,Command::Test(TestKind::Guard { stm: span, taken: true },b.clone()), if p1.labels_num == 1 { 0 } else { offset }),
+                (head,Command::Test(TestKind::Guard { stm: span, taken: false },Bexpr::Not(Box::new(b))), exit_label)
+            ]);
             let mut p1_arcs: Vec<Arc<B>> = shift_arcs(p1.arcs.clone(), offset,p1.labels_num-1,0);
             arcs.append(&mut p1_arcs);
 
@@ -99,11 +231,76 @@ fn stm_to_program<B: Clone>(stm: Statement<B>) -> Program<B>{
                 vec![0],
                 p1.widening_points.iter().map(|x|x+offset).collect_vec()
             ].concat();
-            Program::new(arcs, widening_points)
+            let statements = shift_statements(p1.statements, offset, p1.labels_num-1, 0);
+            Program::new(arcs, widening_points).with_statement(span, statements)
+        },
+    }
+}
+
+// Every call in the expression, innermost first, is moved to a Call and a Return arc appended to the
+// chain of arcs (from label 0, the labels are consecutive) and replaced by the variable holding its result.
+// The calls are evaluated before the rest of the expression, like in the interpreter when the
+// arguments have no side effects
+fn hoist_calls_aexpr<B: Clone>(a: Aexpr<B>, arcs: &mut Vec<Arc<B>>) -> Aexpr<B> {
+    match a {
+        Aexpr::BinOp(op, a1, a2, span) => {
+            let a1 = hoist_calls_aexpr(*a1, arcs);
+            let a2 = hoist_calls_aexpr(*a2, arcs);
+            Aexpr::BinOp(op, Box::new(a1), Box::new(a2), span)
         },
+        Aexpr::Call(p, args, span) => {
+            let args = args.into_iter().map(|a| hoist_calls_aexpr(a, arcs)).collect();
+            let x = call_result_var(&p, span);
+            push_call(arcs, p, args, x.clone());
+            Aexpr::Var(x, span)
+        },
+        Aexpr::Index(x, i, span) => Aexpr::Index(x, Box::new(hoist_calls_aexpr(*i, arcs)), span),
+        a => a,
     }
 }
 
+fn hoist_calls_bexpr<B: Clone>(b: Bexpr<B>, arcs: &mut Vec<Arc<B>>) -> Bexpr<B> {
+    match b {
+        Bexpr::True | Bexpr::False => b,
+        Bexpr::Equal(a1, a2, span) => {
+            let a1 = hoist_calls_aexpr(*a1, arcs);
+            Bexpr::Equal(Box::new(a1), Box::new(hoist_calls_aexpr(*a2, arcs)), span)
+        },
+        Bexpr::LessEq(a1, a2, span) => {
+            let a1 = hoist_calls_aexpr(*a1, arcs);
+            Bexpr::LessEq(Box::new(a1), Box::new(hoist_calls_aexpr(*a2, arcs)), span)
+        },
+        Bexpr::Less(a1, a2, span) => {
+            let a1 = hoist_calls_aexpr(*a1, arcs);
+            Bexpr::Less(Box::new(a1), Box::new(hoist_calls_aexpr(*a2, arcs)), span)
+        },
+        Bexpr::NotEqual(a1, a2, span) => {
+            let a1 = hoist_calls_aexpr(*a1, arcs);
+            Bexpr::NotEqual(Box::new(a1), Box::new(hoist_calls_aexpr(*a2, arcs)), span)
+        },
+        Bexpr::Not(b) => Bexpr::Not(Box::new(hoist_calls_bexpr(*b, arcs))),
+        Bexpr::And(b1, b2) => {
+            let b1 = hoist_calls_bexpr(*b1, arcs);
+            Bexpr::And(Box::new(b1), Box::new(hoist_calls_bexpr(*b2, arcs)))
+        },
+        Bexpr::Or(b1, b2) => {
+            let b1 = hoist_calls_bexpr(*b1, arcs);
+            Bexpr::Or(Box::new(b1), Box::new(hoist_calls_bexpr(*b2, arcs)))
+        },
+    }
+}
+
+fn push_call<B>(arcs: &mut Vec<Arc<B>>, p: Var, args: Vec<Aexpr<B>>, x: Var) {
+    let l = arcs.len() as Label;
+    arcs.push((l, Command::Call(p.clone(), args), l + 1));
+    arcs.push((l + 1, Command::Return(x, p), l + 2));
+}
+
+// Temporary variable with the result of the call at span, not a valid identifier so it cannot clash with the program ones
+fn call_result_var(p: &str, span: Span) -> Var {
+    format!("{p}@{span}")
+}
+
 fn shift_arcs<B>(arcs: Vec<Arc<B>>, offset: Label, old_exit: Label, new_exit: Label) -> Vec<Arc<B>>{
     arcs.into_iter()
     .map(|(l1,c,l2)|{
@@ -116,9 +313,18 @@ fn shift_arcs<B>(arcs: Vec<Arc<B>>, offset: Label, old_exit: Label, new_exit: La
     .collect()
 }
 
+// Same relabelling of shift_arcs, the entry is the exit for the statements without arcs (skip)
+fn shift_statements(statements: Vec<StatementLabels>, offset: Label, old_exit: Label, new_exit: Label) -> Vec<StatementLabels>{
+    let shift = |l| if l == old_exit { new_exit } else { l + offset };
+    statements.into_iter()
+    .map(|s| StatementLabels { span: s.span, entry: shift(s.entry), exit: shift(s.exit) })
+    .collect()
+}
+
 pub trait ProgramInterface{
     fn get_end_label(&self)-> Label;
     fn get_loop_label(&self) -> &Vec<Label>;
+    fn get_statements(&self) -> &Vec<StatementLabels>;
 }
 impl<D: Clone> ProgramInterface for Program<D>  {
     fn get_end_label(&self)-> Label {
@@ -128,4 +334,30 @@ impl<D: Clone> ProgramInterface for Program<D>  {
     fn get_loop_label(&self) -> &Vec<Label> {
         &self.widening_points
     }
-}
\ No newline at end of file
+
+    fn get_statements(&self) -> &Vec<StatementLabels> {
+        &self.statements
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::{parser::parse_string, types::lit_interval::LitInterval};
+
+    use super::*;
+
+    fn program(source: &str) -> Program<LitInterval> {
+        Program::from(parse_string::<LitInterval>(source.to_string()).unwrap().main)
+    }
+
+    #[test]
+    fn feedback_vertex_set_of_the_loops() {
+        let nested = program("i := 0; while i <= 3 do { j := 0; while j <= 3 do j := j + 1; i := i + 1; }");
+        let [outer, inner] = nested.widening_points[..] else { panic!("expected two loop heads") };
+        assert_eq!(nested.feedback_vertex_set(), [inner]);
+        assert!(nested.is_acyclic_without(&[inner]));
+        assert!(!nested.is_acyclic_without(&[outer]));
+
+        let sequential = program("while i <= 3 do i := i + 1; while j <= 3 do j := j + 1;");
+        assert_eq!(sequential.feedback_vertex_set().len(), 2);
+    }
+}
@@ -1,6 +1,12 @@
 use std::fmt::{Display, Debug};
 
-pub trait AbstractState<B>: Debug + Display + PartialOrd + Clone {
+use serde_json::Value;
+
+use crate::{interpreter::types::State, types::ast::{Aexpr, Bexpr, Num}};
+
+use super::domain::AbstractDomain;
+
+pub trait AbstractState<B: AbstractDomain>: Debug + Display + PartialOrd + Clone {
     fn bottom() -> Self;
     fn top() -> Self;
     fn lub(self, other: &Self) -> Self;
@@ -9,4 +15,27 @@ pub trait AbstractState<B>: Debug + Display + PartialOrd + Clone {
     fn set(&mut self, k: String, v: B);
     fn widening(self, other:Self) -> Self;
     fn narrowing(self, other: Self) -> Self;
+    // Structured state for the JSON output, null for bottom
+    fn to_json(&self) -> Value;
+
+    // Hooks for relational states, the default behaviour is the non-relational one
+
+    // Assignment k := a, where v is the (already evaluated) abstract value of a
+    fn assign(&mut self, k: String, _a: &Aexpr<B>, v: B) {
+        self.set(k, v)
+    }
+    // Refinement of k with v (k ∈ v), must not forget any other information
+    fn refine(&mut self, k: &str, v: B) {
+        let d = self.get(k).glb(v);
+        self.set(k.to_string(), d)
+    }
+    // Additional filtering of the state, applied before the non-relational abstract test of b
+    fn assume(self, _b: &Bexpr<B>) -> Self {
+        self
+    }
+    // Membership of a concrete state in the concretization (only the variable values are checked,
+    // the relational states should check their constraints)
+    fn contains(&self, state: &State<Num>) -> bool {
+        *self != Self::bottom() && state.iter().all(|(x, n)| self.get(x).contains(*n))
+    }
 }
@@ -0,0 +1,128 @@
+use std::fmt::Display;
+
+use iter_tools::Itertools;
+
+use super::types::program::{Label, Program};
+
+
+// Weak topological order of the control flow graph (Bourdoncle): a sequence of labels and
+// components, every component is a strongly connected part of the graph with its head first
+#[derive(Debug, Clone)]
+pub enum WtoElement {
+    Vertex(Label),
+    Component(Label, Vec<WtoElement>),
+}
+
+pub fn weak_topological_order<B: Clone>(prog: &Program<B>) -> Vec<WtoElement> {
+    let mut builder = WtoBuilder {
+        prog,
+        dfn: vec![0; prog.labels_num as usize],
+        num: 0,
+        stack: vec![],
+    };
+    // The labels not reachable from the entry are ordered after the reachable ones
+    let mut order = vec![];
+    for root in 0..prog.labels_num {
+        if builder.dfn[root as usize] == 0 {
+            let mut partition = vec![];
+            builder.visit(root, &mut partition);
+            partition.reverse();
+            order.append(&mut partition);
+        }
+    }
+    order
+}
+
+// The labels in the order of the WTO
+pub fn flatten(order: &[WtoElement]) -> Vec<Label> {
+    order.iter()
+        .flat_map(|e| match e {
+            WtoElement::Vertex(l) => vec![*l],
+            WtoElement::Component(head, body) => [vec![*head], flatten(body)].concat(),
+        })
+        .collect()
+}
+
+struct WtoBuilder<'a, B: Clone> {
+    prog: &'a Program<B>,
+    // Depth first numbering, 0 for not visited and u32::MAX for the labels already placed
+    dfn: Vec<u32>,
+    num: u32,
+    stack: Vec<Label>,
+}
+
+impl<B: Clone> WtoBuilder<'_, B> {
+    fn successors(&self, l: Label) -> Vec<Label> {
+        self.prog.arcs.iter().filter(|(l1, _, _)| *l1 == l).map(|(_, _, l2)| *l2).collect()
+    }
+
+    // The elements are pushed in reverse order in the partition
+    fn visit(&mut self, v: Label, partition: &mut Vec<WtoElement>) -> u32 {
+        self.stack.push(v);
+        self.num += 1;
+        self.dfn[v as usize] = self.num;
+        let mut head = self.num;
+        let mut is_loop = false;
+        for w in self.successors(v) {
+            let min = if self.dfn[w as usize] == 0 { self.visit(w, partition) } else { self.dfn[w as usize] };
+            if min <= head {
+                head = min;
+                is_loop = true;
+            }
+        }
+        if head == self.dfn[v as usize] {
+            self.dfn[v as usize] = u32::MAX;
+            let mut element = self.stack.pop().unwrap();
+            if is_loop {
+                while element != v {
+                    self.dfn[element as usize] = 0;
+                    element = self.stack.pop().unwrap();
+                }
+                let component = self.component(v);
+                partition.push(component);
+            } else {
+                partition.push(WtoElement::Vertex(v));
+            }
+        }
+        head
+    }
+
+    fn component(&mut self, v: Label) -> WtoElement {
+        let mut partition = vec![];
+        for w in self.successors(v) {
+            if self.dfn[w as usize] == 0 {
+                self.visit(w, &mut partition);
+            }
+        }
+        partition.reverse();
+        WtoElement::Component(v, partition)
+    }
+}
+
+// "0 (1 2 3) 4"
+impl Display for WtoElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WtoElement::Vertex(l) => write!(f, "{l}"),
+            WtoElement::Component(head, body) if body.is_empty() => write!(f, "({head})"),
+            WtoElement::Component(head, body) => write!(f, "({head} {})", body.iter().join(" ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::parse_string, types::lit_interval::LitInterval};
+
+    use super::*;
+
+    #[test]
+    fn nested_loops_are_nested_components() {
+        let source = "i := 0; while i <= 3 do { j := 0; while j <= 3 do j := j + 1; i := i + 1; }";
+        let prog = Program::from(parse_string::<LitInterval>(source.to_string()).unwrap().main);
+        let order = weak_topological_order(&prog);
+        assert_eq!(order.iter().join(" "), "0 (1 2 (3 4) 5) 6");
+        assert_eq!(prog.widening_points, [1, 3]);
+        assert_eq!(flatten(&order), [0, 1, 2, 3, 4, 5, 6]);
+    }
+}
@@ -1,8 +1,8 @@
-use std::str::FromStr;
+use std::{str::FromStr, time::{Duration, SystemTime, UNIX_EPOCH}};
 
 use clap::{builder::{EnumValueParser, PossibleValue}, Arg, ArgAction, ArgMatches, Command, ValueEnum};
 
-use crate::{analyzer::types::analyzer::IterationStrategy, interpreter::types::State, types::ast::Num};
+use crate::{analyzer::{soundness::SoundnessCheck, types::analyzer::{IterationOrder, IterationStrategy, WideningPoints}}, interpreter::{int_semantics::IntSemantics, types::{State, Value}}, types::lit_interval::LitInterval};
 
 
 #[derive(Debug)]
@@ -13,11 +13,19 @@ pub struct ParserConfig {
     pub print_pretty_cst: bool,
     pub print_ast: bool,
     pub print_pretty_ast: bool,
+    // Rewrite or, < and != with not, and and <= (see ast_parser::desugar)
+    pub desugar: bool,
 }
 
 #[derive(Debug)]
 pub struct InterpreterConfiguration {
-    pub init_state: Option<State<Num>>,
+    pub init_state: Option<State<Value>>,
+    pub int_semantics: IntSemantics,
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+    pub trace: Option<String>,
+    // Graphviz file for the control flow graph
+    pub cfg_dot: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -25,11 +33,15 @@ pub enum Domain{
     Sign,
     ExtendedSign,
     BoundedInterval,
-    Congruence
+    Interval,
+    Congruence,
+    Octagon,
+    Polyhedra,
+    IntervalCongruence,
 }
 impl ValueEnum for Domain {
     fn value_variants<'a>() -> &'a [Self] {
-        &[Self::Sign, Self::ExtendedSign, Self::BoundedInterval, Self::Congruence]
+        &[Self::Sign, Self::ExtendedSign, Self::BoundedInterval, Self::Interval, Self::Congruence, Self::Octagon, Self::Polyhedra, Self::IntervalCongruence]
     }
 
     fn to_possible_value(&self) -> Option<PossibleValue> {
@@ -37,24 +49,59 @@ impl ValueEnum for Domain {
             Domain::Sign => Some(PossibleValue::new("sign")),
             Domain::ExtendedSign => Some(PossibleValue::new("extended-sign").alias("sign+")),
             Domain::BoundedInterval => Some(PossibleValue::new("bounded-interval")),
+            Domain::Interval => Some(PossibleValue::new("interval")),
             Domain::Congruence => Some(PossibleValue::new("cong")),
+            Domain::Octagon => Some(PossibleValue::new("octagon").alias("oct")),
+            Domain::Polyhedra => Some(PossibleValue::new("polyhedra").alias("poly")),
+            Domain::IntervalCongruence => Some(PossibleValue::new("interval*cong")),
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
 #[derive(Debug)]
 pub struct AnalyzerConfiguration{
     pub domain: Domain,
     pub domain_config: Option<String>,
     pub iteration_strategy: IterationStrategy,
+    pub widening_points: WideningPoints,
+    pub iteration_order: IterationOrder,
     pub init_state: Option<String>,
+    // Postcondition at the exit label for the backward analysis
+    pub post_state: Option<String>,
     pub print_iterations: bool,
+    // Widening thresholds harvested from the literals of the tests
+    pub auto_thresholds: bool,
+    // Print the alarms as JSON instead of a list
+    pub alarms_json: bool,
+    // Print the unreachable branches and the non-terminating loops
+    pub report_dead_code: bool,
+    // Print the state before and after every statement
+    pub annotate: bool,
+    pub format: OutputFormat,
+    // Graphviz file for the control flow graph annotated with the result
+    pub dot: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct SoundnessConfiguration {
+    pub analyzer: AnalyzerConfiguration,
+    pub check: SoundnessCheck,
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Config {
     InterpreterConfiguration{parser_configuration: ParserConfig, config: InterpreterConfiguration},
-    AnalyzerConfiguration{parser_configuration: ParserConfig, config: AnalyzerConfiguration}
+    DebuggerConfiguration{parser_configuration: ParserConfig, config: InterpreterConfiguration},
+    AnalyzerConfiguration{parser_configuration: ParserConfig, config: AnalyzerConfiguration},
+    SoundnessConfiguration{parser_configuration: ParserConfig, config: SoundnessConfiguration},
+    TraceDiffConfiguration{expected: String, actual: String},
 }
 
 
@@ -68,22 +115,71 @@ impl Config {
             Arg::new("pretty-ast").long("pretty-ast").short('A').help("Print pretty ast").action(ArgAction::SetTrue),
             Arg::new("cst")       .long("cst")       .short('c').help("Print raw ast")   .action(ArgAction::SetTrue),
             Arg::new("pretty-cst").long("pretty-cst").short('C').help("Print pretty ast").action(ArgAction::SetTrue),
+            Arg::new("desugar")   .long("desugar")              .help("Rewrite 'or', '<' and '!=' with 'not', 'and' and '<='").action(ArgAction::SetTrue),
         ];
 
 
-        let interpreter_cmd = Command::new("run")
-            .arg(Arg::new("state")     
+        let concrete_args = [
+            Arg::new("state")     
                 .long("state")
-                .help("Set initial state, must be in format <var-name>:<value>;<var-name>:<value>;...")
+                .help("Set initial state, must be in format <var-name>:<value>;<var-name>:<value>;... (arrays as [1,2,3])")
                 // .long_help("Set initial state, must be in format <var-name>:<value>;<var-name>:<value>;...")
-                .value_parser(parse_state::<Num>))
+                .value_parser(parse_state::<Value>),
+            Arg::new("int-semantics")
+                .long("int-semantics")
+                .help("Set the integer semantics: unbounded, checked, wrapping-N or saturating-N (N bits)")
+                .value_parser(IntSemantics::from_str)
+                .default_value("checked"),
+        ];
+
+
+        let interpreter_cmd = Command::new("run")
+            .args(concrete_args.clone())
+            .arg(Arg::new("max-steps")
+                .long("max-steps")
+                .help("Stop the execution after the given number of steps (statements and tests)")
+                .value_parser(clap::value_parser!(u64)))
+            .arg(Arg::new("timeout")
+                .long("timeout")
+                .help("Stop the execution after the given number of milliseconds")
+                .value_parser(clap::value_parser!(u64)))
+            .arg(Arg::new("trace")
+                .long("trace")
+                .help("Write the executed assignments and tests with the states before and after to the file (JSON lines)"))
+            .arg(Arg::new("cfg-dot")
+                .long("cfg-dot")
+                .help("Write the control flow graph of the program to the file (Graphviz)"))
             .args(parser_args.clone())
             .arg_required_else_help(true);
 
+        let debugger_cmd = Command::new("debug")
+            .about("Execute the program step by step with an interactive debugger")
+            .args(concrete_args)
+            .arg(Arg::new("max-steps")
+                .long("max-steps")
+                .help("Stop a command (step, next or continue) after the given number of steps, most likely in a loop that does not terminate")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("1000000"))
+            .args(parser_args.clone())
+            .arg_required_else_help(true);
+
+        let abstract_args = [
+            Arg::new("domain").long("domain").short('d').value_parser(EnumValueParser::<Domain>::new()).default_value("bounded-interval"),
+            Arg::new("widening") .short('W').help("Use widening") .action(ArgAction::SetTrue),
+            Arg::new("narrowing").short('N').help("Use narrowing").action(ArgAction::SetTrue).requires("widening"),
+            Arg::new("widening-delay").long("widening-delay").value_parser(clap::value_parser!(u32)).default_value("0")
+                .help("Number of visits of a widening point that use the join before the widening").requires("widening"),
+            Arg::new("widening-points").long("widening-points").value_parser(["loop-heads", "feedback"]).default_value("loop-heads")
+                .help("Labels where the widening is applied: every loop head or a minimal feedback vertex set of the CFG"),
+            Arg::new("iteration-order").long("iteration-order").value_parser(["worklist", "round-robin"]).default_value("worklist")
+                .help("Recompute only the successors of the changed labels (worklist) or every label at each iteration (round-robin)"),
+            Arg::new("thresholds").long("thresholds").short('T').value_parser(["auto"])
+                .help("Widening thresholds for the interval domains, \"auto\" uses the literals of the tests").requires("widening"),
+            Arg::new("config").long("conf").help("Set the configuration for the domain, e.g. \"[-10,10]\" for bounded-interval or \"thresholds=0,10,100\" for interval"),
+        ];
+
         let analyzer_cmd = Command::new("analyze")
-            .arg(Arg::new("domain").long("domain").short('d').value_parser(EnumValueParser::<Domain>::new()).default_value("bounded-interval"))
-            .arg(Arg::new("widening") .short('W').help("Use widening") .action(ArgAction::SetTrue))
-            .arg(Arg::new("narrowing").short('N').help("Use narrowing").action(ArgAction::SetTrue).requires("widening"))
+            .args(abstract_args.clone())
             .arg(Arg::new("state")     
                 .long("state")
                 .help("Set initial state, must be in format <var-name>:<value>;<var-name>:<value>;...")
@@ -91,16 +187,58 @@ impl Config {
                 // .if
                 // .value_parser(parse_abs_state::<BoundedInterval>)
             )
-            .arg(Arg::new("config").long("conf").help("Set the configuration for the domain"))
+            .arg(Arg::new("post")
+                .long("post")
+                .help("Postcondition at the exit (same format of --state), runs a backward analysis and reports a necessary precondition"))
             .arg(Arg::new("iter").long("iter").short('i').help("Print analyzer iterations").action(ArgAction::SetTrue))
+            .arg(Arg::new("alarms-json").long("alarms-json").help("Print the alarms (division by zero, uninitialized reads, indexes out of bounds) as JSON").action(ArgAction::SetTrue))
+            .arg(Arg::new("report-dead-code").long("report-dead-code").help("Report the unreachable branches, the loops whose body never runs and the loops that never terminate").action(ArgAction::SetTrue))
+            .arg(Arg::new("annotate").long("annotate").help("Print the program with the abstract state before and after every statement").action(ArgAction::SetTrue))
+            .arg(Arg::new("dot").long("dot").help("Write the control flow graph with the abstract state of every label to the file (Graphviz)"))
+            .arg(Arg::new("format").long("format").value_parser(["text", "json"]).default_value("text")
+                .help("Output format, json prints the program, the control flow graph and the state of every label"))
+            .args(parser_args.clone())
+            .arg_required_else_help(true);
+
+        let soundness_cmd = Command::new("check-soundness")
+            .about("Check that the states of random concrete executions are contained in the analysis result")
+            .args(abstract_args)
+            .arg(Arg::new("runs")
+                .long("runs")
+                .help("Number of executions")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("100"))
+            .arg(Arg::new("range")
+                .long("range")
+                .help("Range of the random initial values, in format [<min>,<max>]")
+                .value_parser(LitInterval::from_str)
+                .default_value("[-100,100]"))
+            .arg(Arg::new("max-steps")
+                .long("max-steps")
+                .help("Stop each execution after the given number of steps")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("10000"))
+            .arg(Arg::new("seed")
+                .long("seed")
+                .help("Seed of the random initial states (by default taken from the clock)")
+                .value_parser(clap::value_parser!(u64)))
             .args(parser_args)
             .arg_required_else_help(true);
             // .arg(Arg::new("lower").long("lower-bound").short('l').help("Lower bound").value_parser(clap::value_parser!(Num)).action(ArgAction::Set).required(true))
 
             
+        let trace_diff_cmd = Command::new("trace-diff")
+            .about("Find the first divergence between two traces of the same program (see run --trace)")
+            .arg(Arg::new("expected").required(true))
+            .arg(Arg::new("actual").required(true))
+            .arg_required_else_help(true);
+
         let matches = Command::new("While Interpreter")
             .subcommand(interpreter_cmd)
+            .subcommand(debugger_cmd)
             .subcommand(analyzer_cmd)
+            .subcommand(soundness_cmd)
+            .subcommand(trace_diff_cmd)
             .subcommand_required(true)
             .arg_required_else_help(true)
             .get_matches();
@@ -109,7 +247,23 @@ impl Config {
             Some(("run", sub_m)) => Config::InterpreterConfiguration { 
                 parser_configuration: ParserConfig::from(sub_m),
                 config: InterpreterConfiguration{
-                    init_state: sub_m.get_one::<State<Num>>("state").cloned(),
+                    init_state: sub_m.get_one::<State<Value>>("state").cloned(),
+                    int_semantics: sub_m.get_one::<IntSemantics>("int-semantics").cloned().unwrap_or(IntSemantics::Checked),
+                    max_steps: sub_m.get_one::<u64>("max-steps").cloned(),
+                    timeout: sub_m.get_one::<u64>("timeout").map(|ms| Duration::from_millis(*ms)),
+                    trace: sub_m.get_one::<String>("trace").cloned(),
+                    cfg_dot: sub_m.get_one::<String>("cfg-dot").cloned(),
+                }
+            },
+            Some(("debug", sub_m)) => Config::DebuggerConfiguration { 
+                parser_configuration: ParserConfig::from(sub_m),
+                config: InterpreterConfiguration{
+                    init_state: sub_m.get_one::<State<Value>>("state").cloned(),
+                    int_semantics: sub_m.get_one::<IntSemantics>("int-semantics").cloned().unwrap_or(IntSemantics::Checked),
+                    max_steps: sub_m.get_one::<u64>("max-steps").cloned(),
+                    timeout: None,
+                    trace: None,
+                    cfg_dot: None,
                 }
             },
             Some(("analyze", sub_m)) => Config::AnalyzerConfiguration{ 
@@ -117,27 +271,99 @@ impl Config {
                 config: AnalyzerConfiguration{
                     domain: sub_m.get_one::<Domain>("domain").cloned().unwrap_or(Domain::BoundedInterval),
                     domain_config: sub_m.get_one::<String>("config").cloned(),
-                    iteration_strategy: match (sub_m.get_flag("widening"), sub_m.get_flag("narrowing")) {
-                        (false, _) => IterationStrategy::Simple,
-                        (true, false) => IterationStrategy::Widening,
-                        (true, true) => IterationStrategy::WideningAndNarrowing,
-                    },
+                    iteration_strategy: iteration_strategy(sub_m),
+                    widening_points: widening_points(sub_m),
+                    iteration_order: iteration_order(sub_m),
                     init_state: sub_m.get_one::<String>("state").cloned(), //sub_m.get_one::<HashMapState<BoundedInterval>>("state").cloned(),
+                    post_state: sub_m.get_one::<String>("post").cloned(),
                     print_iterations: sub_m.get_flag("iter"),
+                    auto_thresholds: sub_m.contains_id("thresholds"),
+                    alarms_json: sub_m.get_flag("alarms-json"),
+                    report_dead_code: sub_m.get_flag("report-dead-code"),
+                    annotate: sub_m.get_flag("annotate"),
+                    format: output_format(sub_m),
+                    dot: sub_m.get_one::<String>("dot").cloned(),
                 }
             },
+            Some(("check-soundness", sub_m)) => Config::SoundnessConfiguration {
+                parser_configuration: ParserConfig::from(sub_m),
+                config: SoundnessConfiguration {
+                    analyzer: AnalyzerConfiguration {
+                        domain: sub_m.get_one::<Domain>("domain").cloned().unwrap_or(Domain::BoundedInterval),
+                        domain_config: sub_m.get_one::<String>("config").cloned(),
+                        iteration_strategy: iteration_strategy(sub_m),
+                        widening_points: widening_points(sub_m),
+                        iteration_order: iteration_order(sub_m),
+                        init_state: None,
+                        post_state: None,
+                        print_iterations: false,
+                        auto_thresholds: sub_m.contains_id("thresholds"),
+                        alarms_json: false,
+                        report_dead_code: false,
+                        annotate: false,
+                        format: OutputFormat::Text,
+                        dot: None,
+                    },
+                    check: SoundnessCheck {
+                        runs: *sub_m.get_one::<u64>("runs").unwrap(),
+                        range: *sub_m.get_one::<LitInterval>("range").unwrap(),
+                        max_steps: *sub_m.get_one::<u64>("max-steps").unwrap(),
+                        seed: sub_m.get_one::<u64>("seed").cloned().unwrap_or_else(|| {
+                            SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)
+                        }),
+                    },
+                }
+            },
+            Some(("trace-diff", sub_m)) => Config::TraceDiffConfiguration {
+                expected: sub_m.get_one::<String>("expected").cloned().unwrap(),
+                actual: sub_m.get_one::<String>("actual").cloned().unwrap(),
+            },
             _ => unreachable!(),
         }
     }
 
-    pub fn get_parser_conf(&self) -> &ParserConfig{
+    // None for the commands that do not read a program
+    pub fn get_parser_conf(&self) -> Option<&ParserConfig>{
         match self {
-            Config::InterpreterConfiguration { parser_configuration,.. } => parser_configuration,
-            Config::AnalyzerConfiguration { parser_configuration, .. } => parser_configuration,
+            Config::InterpreterConfiguration { parser_configuration,.. } => Some(parser_configuration),
+            Config::DebuggerConfiguration { parser_configuration,.. } => Some(parser_configuration),
+            Config::AnalyzerConfiguration { parser_configuration, .. } => Some(parser_configuration),
+            Config::SoundnessConfiguration { parser_configuration, .. } => Some(parser_configuration),
+            Config::TraceDiffConfiguration { .. } => None,
         }
     }
 }
 
+fn iteration_strategy(sub_m: &ArgMatches) -> IterationStrategy {
+    let delay = *sub_m.get_one::<u32>("widening-delay").unwrap();
+    match (sub_m.get_flag("widening"), sub_m.get_flag("narrowing")) {
+        (false, _) => IterationStrategy::Simple,
+        (true, false) => IterationStrategy::Widening { delay },
+        (true, true) => IterationStrategy::WideningAndNarrowing { delay },
+    }
+}
+
+fn iteration_order(sub_m: &ArgMatches) -> IterationOrder {
+    match sub_m.get_one::<String>("iteration-order").map(String::as_str) {
+        Some("round-robin") => IterationOrder::RoundRobin,
+        _ => IterationOrder::Worklist,
+    }
+}
+
+fn output_format(sub_m: &ArgMatches) -> OutputFormat {
+    match sub_m.get_one::<String>("format").map(String::as_str) {
+        Some("json") => OutputFormat::Json,
+        _ => OutputFormat::Text,
+    }
+}
+
+fn widening_points(sub_m: &ArgMatches) -> WideningPoints {
+    match sub_m.get_one::<String>("widening-points").map(String::as_str) {
+        Some("feedback") => WideningPoints::FeedbackVertexSet,
+        _ => WideningPoints::LoopHeads,
+    }
+}
+
 impl From<&ArgMatches> for ParserConfig {
     fn from(value: &ArgMatches) -> Self {
         ParserConfig{
@@ -150,6 +376,7 @@ impl From<&ArgMatches> for ParserConfig {
             print_pretty_cst:value.get_flag("pretty-cst"),
             print_ast: value.get_flag("ast"),
             print_pretty_ast:value.get_flag("pretty-ast"),
+            desugar: value.get_flag("desugar"),
         }
     }
 }
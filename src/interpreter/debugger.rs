@@ -0,0 +1,507 @@
+use std::{collections::{BTreeSet, VecDeque}, io::{self, BufRead, Write}};
+
+use crate::{parser::{parse_aexpr_string, parse_bexpr_string}, types::{ast::{Bexpr, Num, Statement, Var}, errors::{ParserError, RuntimeError}, span::{render_error, Span}}};
+
+use super::{int_semantics::IntSemantics, interpreter::{eval_aexpr, eval_bexpr, eval_statement, same_state, Context, Procedures}, types::{State, Value}};
+
+
+const HELP: &str = "\
+Commands:
+  step, s [n]             execute the next n statements (default 1)
+  next, n                 execute the next statement, without stopping inside loops and branches
+  continue, c             run until a breakpoint, a watchpoint or the end of the program
+                          (or until the state repeats at a loop head, or --max-steps steps)
+  back [n]                undo the last n executed statements (default 1, at most 1000)
+  break, b <line> [if <bexpr>]
+                          stop before the statements on the line (only when the condition holds)
+  delete, d [n]           delete the breakpoint n (all the breakpoints if omitted)
+  watch, w <var>          stop when the value of the variable changes
+  unwatch <var>           delete the watchpoint on the variable
+  print, p [<aexpr>]      evaluate the expression in the current state (the whole state if omitted)
+  info, i                 list the breakpoints and the watchpoints
+  list, l                 show the source around the current statement
+  help, h                 show this message
+  quit, q                 exit the debugger
+An empty line repeats the last command.";
+
+// Executed statements that can be undone with back, the oldest ones are forgotten
+const HISTORY_LENGTH: usize = 1000;
+
+struct Breakpoint {
+    line: usize,
+    cond: Option<Bexpr<Num>>,
+}
+
+// Point of the execution, used to go back
+struct Snapshot<'a> {
+    stack: Vec<&'a Statement<Num>>,
+    state: State<Value>,
+}
+
+enum Mode {
+    Step(usize),
+    Next,
+    Continue,
+}
+
+enum Stop {
+    Step,
+    Finished,
+    Breakpoint(usize),
+    Watchpoint(Var, Option<Value>),
+    Error(RuntimeError),
+    // The execution and the state at the head of the loop repeated
+    Diverges,
+    StepLimit(u64),
+}
+
+struct Debugger<'a> {
+    source: &'a str,
+    sem: IntSemantics,
+    // Steps of a single command, also the limit of the calls and of the statements run as a whole
+    max_steps: u64,
+    // The calls run as a single step
+    procedures: Procedures,
+    // Statements still to execute, the next one is on top
+    stack: Vec<&'a Statement<Num>>,
+    state: State<Value>,
+    history: VecDeque<Snapshot<'a>>,
+    // Some snapshots were dropped from the history
+    history_truncated: bool,
+    // Lines where a statement begins (the only ones where a breakpoint can stop)
+    lines: BTreeSet<usize>,
+    breakpoints: Vec<Option<Breakpoint>>,
+    watchpoints: Vec<Var>,
+}
+
+impl<'a> Debugger<'a> {
+    fn new(ast: &'a Statement<Num>, state: State<Value>, sem: IntSemantics, max_steps: u64, procedures: Procedures, source: &'a str) -> Self {
+        let mut lines = BTreeSet::new();
+        statement_lines(ast, &mut lines);
+        let mut dbg = Debugger {
+            source, sem, max_steps, procedures, state, lines,
+            stack: vec![ast],
+            history: VecDeque::new(),
+            history_truncated: false,
+            breakpoints: vec![],
+            watchpoints: vec![],
+        };
+        dbg.normalize();
+        dbg
+    }
+
+    // Split the compositions on top of the stack, so that the top is always a simple statement
+    fn normalize(&mut self) {
+        while let Some(Statement::Compose(s1, s2)) = self.stack.last() {
+            self.stack.pop();
+            self.stack.push(s2);
+            self.stack.push(s1);
+        }
+    }
+
+    fn context(&self) -> Context {
+        Context::new(self.sem, Some(self.max_steps), None).with_procedures(self.procedures.clone())
+    }
+
+    fn current(&self) -> Option<&'a Statement<Num>> {
+        self.stack.last().copied()
+    }
+
+    // Execute the statement on top of the stack, if/while only evaluate the guard
+    // On error the execution is left before the failing statement
+    fn step(&mut self) -> Result<(), RuntimeError> {
+        let Some(stm) = self.stack.pop() else { return Ok(()) };
+        let snapshot = Snapshot { stack: [self.stack.clone(), vec![stm]].concat(), state: self.state.clone() };
+        // The guards may have side effects on the state
+        let mut state = self.state.clone();
+        let result = match stm {
+            Statement::Assign(..) | Statement::Skip(..) | Statement::Assert(..) | Statement::Assume(..) |
+            Statement::ArrayAssign(..) | Statement::NewArray(..) => {
+                eval_statement(stm, state, &mut self.context())
+            },
+            Statement::IfThenElse(b, s1, s2, _) => eval_bexpr(b, &mut state, &mut self.context()).map(|t| {
+                self.stack.push(if t { s1 } else { s2 });
+                state
+            }),
+            Statement::While(b, body, _) => eval_bexpr(b, &mut state, &mut self.context()).map(|t| {
+                if t {
+                    self.stack.push(stm);
+                    self.stack.push(body);
+                }
+                state
+            }),
+            Statement::Compose(s1, s2) => {
+                self.stack.push(s2);
+                self.stack.push(s1);
+                Ok(state)
+            },
+        };
+        match result {
+            Ok(state) => {
+                self.state = state;
+                if self.history.len() == HISTORY_LENGTH {
+                    self.history.pop_front();
+                    self.history_truncated = true;
+                }
+                self.history.push_back(snapshot);
+                Ok(())
+            },
+            Err(err) => {
+                self.stack = snapshot.stack;
+                Err(err)
+            },
+        }
+    }
+
+    fn run(&mut self, mode: Mode) -> Stop {
+        // Only the statements pushed by the current one are above this depth
+        let depth = self.stack.len();
+        let mut steps = 0;
+        // Brent's cycle detection on the loop heads, as in the interpreter: the whole execution
+        // point (the statements still to execute) and the state must repeat
+        let mut saved: Option<(Vec<*const Statement<Num>>, State<Value>)> = None;
+        let (mut power, mut lambda) = (1u64, 0u64);
+        loop {
+            if self.stack.is_empty() {
+                return Stop::Finished
+            }
+            let prev = self.state.clone();
+            if let Err(err) = self.step() {
+                return Stop::Error(err)
+            }
+            steps += 1;
+            // Checked before splitting the compositions, which may increase the size again
+            let current_done = self.stack.len() < depth;
+            self.normalize();
+
+            if let Some(x) = self.watchpoints.iter().find(|x| prev.get(*x) != self.state.get(*x)) {
+                return Stop::Watchpoint(x.clone(), prev.get(x).cloned())
+            }
+            if self.stack.is_empty() {
+                return Stop::Finished
+            }
+            if let Some(i) = self.breakpoint_hit() {
+                return Stop::Breakpoint(i)
+            }
+            match mode {
+                Mode::Step(n) if steps >= n => return Stop::Step,
+                Mode::Next if current_done => return Stop::Step,
+                _ => (),
+            }
+            if steps as u64 >= self.max_steps {
+                return Stop::StepLimit(self.max_steps)
+            }
+            if let Some(Statement::While(..)) = self.current() {
+                let stack: Vec<_> = self.stack.iter().map(|s| *s as *const Statement<Num>).collect();
+                if saved.as_ref().is_some_and(|(saved_stack, saved_state)| *saved_stack == stack && same_state(saved_state, &self.state)) {
+                    return Stop::Diverges
+                }
+                lambda += 1;
+                if lambda == power {
+                    saved = Some((stack, self.state.clone()));
+                    (power, lambda) = (power * 2, 0);
+                }
+            }
+        }
+    }
+
+    fn back(&mut self, n: usize) {
+        if self.history.is_empty() {
+            match self.history_truncated {
+                true => println!("Cannot go further back, only the last {HISTORY_LENGTH} statements are kept"),
+                false => println!("Already at the beginning of the execution"),
+            }
+            return
+        }
+        for _ in 0..n {
+            let Some(snapshot) = self.history.pop_back() else { break };
+            self.stack = snapshot.stack;
+            self.state = snapshot.state;
+        }
+        self.print_position();
+    }
+
+    // The condition is evaluated on a copy of the state, an error counts as false
+    fn breakpoint_hit(&self) -> Option<usize> {
+        let line = self.current()?.span().line();
+        self.breakpoints.iter().enumerate().find_map(|(i, bp)| {
+            let bp = bp.as_ref()?;
+            let holds = match &bp.cond {
+                Some(b) => eval_bexpr(b, &mut self.state.clone(), &mut self.context()).unwrap_or(false),
+                None => true,
+            };
+            (bp.line == line && holds).then_some(i + 1)
+        })
+    }
+
+    fn add_breakpoint(&mut self, arg: &str) {
+        let (line, cond) = match arg.split_once(" if ") {
+            Some((line, cond)) => (line.trim(), Some(cond.trim())),
+            None => (arg.trim(), None),
+        };
+        let Ok(line) = line.parse::<usize>() else {
+            println!("Usage: break <line> [if <bexpr>]");
+            return
+        };
+        if !self.lines.contains(&line) {
+            println!("No statement begins at line {line}");
+            return
+        }
+        let cond = match cond.map(parse_bexpr_string::<Num>) {
+            Some(Ok(b)) => Some(b),
+            Some(Err(errors)) => return print_expr_errors(cond.unwrap(), errors),
+            None => None,
+        };
+        match &cond {
+            Some(b) => println!("Breakpoint {} at line {line} if {b}", self.breakpoints.len() + 1),
+            None => println!("Breakpoint {} at line {line}", self.breakpoints.len() + 1),
+        }
+        self.breakpoints.push(Some(Breakpoint { line, cond }));
+    }
+
+    fn delete_breakpoint(&mut self, arg: &str) {
+        if arg.is_empty() {
+            self.breakpoints.iter_mut().for_each(|bp| *bp = None);
+            println!("Deleted all the breakpoints");
+            return
+        }
+        match arg.parse::<usize>().ok().and_then(|i| self.breakpoints.get_mut(i.wrapping_sub(1))) {
+            Some(bp @ Some(_)) => {
+                *bp = None;
+                println!("Deleted breakpoint {arg}");
+            },
+            _ => println!("No breakpoint number {arg}"),
+        }
+    }
+
+    fn watch(&mut self, x: &str) {
+        if x.is_empty() || !x.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            println!("Usage: watch <var>");
+        } else if !self.watchpoints.iter().any(|y| y == x) {
+            self.watchpoints.push(x.to_string());
+            println!("Watching {x}");
+        }
+    }
+
+    fn unwatch(&mut self, x: &str) {
+        match self.watchpoints.iter().position(|y| y == x) {
+            Some(i) => { self.watchpoints.remove(i); },
+            None => println!("No watchpoint on {x}"),
+        }
+    }
+
+    // The expression is evaluated on a copy, the side effects are discarded
+    fn print(&self, arg: &str) {
+        if arg.is_empty() {
+            return self.print_state()
+        }
+        match parse_aexpr_string::<Num>(arg) {
+            Ok(a) => match eval_aexpr(&a, &mut self.state.clone(), &mut self.context()) {
+                Ok(n) => println!("{a} = {n}"),
+                Err(err) => println!("{}", err.render(arg, self.sem)),
+            },
+            Err(errors) => print_expr_errors(arg, errors),
+        }
+    }
+
+    fn print_state(&self) {
+        if self.state.is_empty() {
+            println!("(empty state)");
+        }
+        let mut vars = self.state.iter().collect::<Vec<_>>();
+        vars.sort_by_key(|(x, _)| *x);
+        for (x, n) in vars {
+            println!("{x} = {n}");
+        }
+    }
+
+    fn info(&self) {
+        println!("Breakpoints:");
+        for (i, bp) in self.breakpoints.iter().enumerate() {
+            match bp {
+                Some(Breakpoint { line, cond: Some(b) }) => println!("  {}: line {line} if {b}", i + 1),
+                Some(Breakpoint { line, cond: None }) => println!("  {}: line {line}", i + 1),
+                None => (),
+            }
+        }
+        println!("Watchpoints:");
+        for x in &self.watchpoints {
+            println!("  {x} = {}", self.state.get(x).map_or("undefined".to_string(), |n| n.to_string()));
+        }
+    }
+
+    fn list(&self) {
+        let current = self.current().map(|s| s.span().line());
+        let center = current.unwrap_or(1);
+        for (i, line) in self.source.lines().enumerate().skip(center.saturating_sub(4)).take(7) {
+            let marker = if Some(i + 1) == current { "=>" } else { "  " };
+            println!("{marker} {:>3} | {line}", i + 1);
+        }
+    }
+
+    // Show the next statement to be executed
+    fn print_position(&self) {
+        match self.current() {
+            Some(stm) => {
+                let line = stm.span().line();
+                let text = self.source.lines().nth(line.wrapping_sub(1)).unwrap_or("").trim();
+                println!("=> {line:>3} | {text}");
+            },
+            None => println!("The program terminated"),
+        }
+    }
+
+    fn report(&self, stop: Stop) {
+        match stop {
+            Stop::Step => (),
+            Stop::Finished => {
+                println!("The program terminated");
+                println!("FINAL STATE:");
+                return self.print_state()
+            },
+            Stop::Breakpoint(i) => println!("Breakpoint {i}"),
+            Stop::Watchpoint(x, old) => {
+                let show = |n: Option<&Value>| n.map_or("undefined".to_string(), |n| n.to_string());
+                println!("Watchpoint {x}: {} -> {}", show(old.as_ref()), show(self.state.get(&x)));
+            },
+            Stop::Error(err) => {
+                println!("{}", err.render(self.source, self.sem));
+                println!("The execution stopped before the failing statement");
+            },
+            Stop::Diverges => println!("The program definitely diverges, the state repeats at the loop head"),
+            Stop::StepLimit(n) => println!("Stopped after {n} steps, the program may not terminate"),
+        }
+        self.print_position();
+    }
+}
+
+// Interactive debugging session on the standard input
+pub fn debug(ast: &Statement<Num>, state: State<Value>, sem: IntSemantics, max_steps: u64, procedures: Procedures, source: &str) {
+    let mut dbg = Debugger::new(ast, state, sem, max_steps, procedures, source);
+    println!("Type 'help' for the list of commands");
+    dbg.print_position();
+
+    let mut last = String::new();
+    let stdin = io::stdin();
+    loop {
+        print!("(debug) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            println!();
+            break
+        }
+        if !line.trim().is_empty() {
+            last = line.trim().to_string();
+        }
+        let (cmd, arg) = last.split_once(' ').map_or((last.as_str(), ""), |(c, a)| (c, a.trim()));
+        let count = || if arg.is_empty() { Some(1) } else { arg.parse::<usize>().ok() };
+        match cmd {
+            "" => (),
+            "step" | "s" => match count() {
+                Some(n) => { let stop = dbg.run(Mode::Step(n)); dbg.report(stop) },
+                None => println!("Usage: step [n]"),
+            },
+            "next" | "n" => { let stop = dbg.run(Mode::Next); dbg.report(stop) },
+            "continue" | "c" => { let stop = dbg.run(Mode::Continue); dbg.report(stop) },
+            "back" => match count() {
+                Some(n) => dbg.back(n),
+                None => println!("Usage: back [n]"),
+            },
+            "break" | "b" => dbg.add_breakpoint(arg),
+            "delete" | "d" => dbg.delete_breakpoint(arg),
+            "watch" | "w" => dbg.watch(arg),
+            "unwatch" => dbg.unwatch(arg),
+            "print" | "p" => dbg.print(arg),
+            "info" | "i" => dbg.info(),
+            "list" | "l" => dbg.list(),
+            "help" | "h" => println!("{HELP}"),
+            "quit" | "q" => break,
+            _ => println!("Unknown command '{cmd}', type 'help' for the list of commands"),
+        }
+    }
+}
+
+fn print_expr_errors(expr: &str, errors: Vec<ParserError<Num>>) {
+    for err in errors {
+        let msg = format!("Syntax error: {err}");
+        match err.pos() {
+            Some(pos) => println!("{}", render_error(expr, Span::new(pos, (pos.0, pos.1 + 1)), &msg)),
+            None => println!("{msg}"),
+        }
+    }
+}
+
+fn statement_lines<N>(stm: &Statement<N>, lines: &mut BTreeSet<usize>) {
+    match stm {
+        Statement::Compose(s1, s2) => {
+            statement_lines(s1, lines);
+            statement_lines(s2, lines);
+        },
+        Statement::IfThenElse(_, s1, s2, span) => {
+            lines.insert(span.line());
+            statement_lines(s1, lines);
+            statement_lines(s2, lines);
+        },
+        Statement::While(_, body, span) => {
+            lines.insert(span.line());
+            statement_lines(body, lines);
+        },
+        Statement::Assign(_, _, span) | Statement::Skip(span) |
+        Statement::Assert(_, span) | Statement::Assume(_, span) |
+        Statement::ArrayAssign(_, _, _, span) | Statement::NewArray(_, _, span) => { lines.insert(span.line()); },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_string;
+
+    use super::*;
+
+    fn program(source: &str) -> Statement<Num> {
+        parse_string::<Num>(source.to_string()).unwrap().main
+    }
+
+    fn value(dbg: &Debugger, x: &str) -> Option<Value> {
+        dbg.state.get(x).cloned()
+    }
+
+    #[test]
+    fn history_keeps_only_the_last_statements() {
+        let source = "x := 0;\nwhile x <= 5000 do\n    x := x + 1;";
+        let ast = program(source);
+        let mut dbg = Debugger::new(&ast, State::new(), IntSemantics::Checked, 1_000_000, Procedures::default(), source);
+        assert!(matches!(dbg.run(Mode::Step(3001)), Stop::Step));
+        assert_eq!(value(&dbg, "x"), Some(Value::Int(1500.into())));
+        assert_eq!(dbg.history.len(), HISTORY_LENGTH);
+        dbg.back(2 * HISTORY_LENGTH);
+        assert_eq!(value(&dbg, "x"), Some(Value::Int(1000.into())));
+        assert!(dbg.history.is_empty());
+    }
+
+    #[test]
+    fn continue_stops_on_diverging_and_overlong_loops() {
+        let source = "x := 0;\nwhile x <= 10 do\n    skip;";
+        let ast = program(source);
+        let mut dbg = Debugger::new(&ast, State::new(), IntSemantics::Checked, 1_000_000, Procedures::default(), source);
+        assert!(matches!(dbg.run(Mode::Continue), Stop::Diverges));
+
+        let source = "x := 0;\nwhile 0 <= x do\n    x := x + 1;";
+        let ast = program(source);
+        let mut dbg = Debugger::new(&ast, State::new(), IntSemantics::Checked, 100, Procedures::default(), source);
+        assert!(matches!(dbg.run(Mode::Continue), Stop::StepLimit(100)));
+    }
+
+    #[test]
+    fn conditional_breakpoint() {
+        let source = "x := 0;\nwhile x <= 10 do\n    x := x + 1;\ny := x;";
+        let ast = program(source);
+        let mut dbg = Debugger::new(&ast, State::new(), IntSemantics::Checked, 1_000_000, Procedures::default(), source);
+        dbg.add_breakpoint("3 if x == 4");
+        assert!(matches!(dbg.run(Mode::Continue), Stop::Breakpoint(1)));
+        assert_eq!(value(&dbg, "x"), Some(Value::Int(4.into())));
+        assert!(matches!(dbg.run(Mode::Continue), Stop::Finished));
+        assert_eq!(value(&dbg, "y"), Some(Value::Int(11.into())));
+    }
+}
@@ -0,0 +1,110 @@
+use std::{fmt::Display, str::FromStr};
+
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+
+use crate::types::ast::Num;
+
+use super::types::Int;
+
+// Machine model used by the interpreter for the integer arithmetic
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntSemantics {
+    // Arbitrary precision integers
+    Unbounded,
+    // Num (i128) integers, overflows are runtime errors
+    Checked,
+    // N-bit two's complement integers with wrap-around
+    Wrapping(u32),
+    // N-bit two's complement integers clamped to the min/max value
+    Saturating(u32),
+}
+
+impl IntSemantics {
+    // Adapt an exact result to the machine model, None if it overflows in checked mode
+    pub fn fit(&self, n: Int) -> Option<Int> {
+        match self {
+            IntSemantics::Unbounded => Some(n),
+            IntSemantics::Checked => {
+                let (min, max) = (BigInt::from(Num::MIN), BigInt::from(Num::MAX));
+                if n < min || n > max { None } else { Some(n) }
+            },
+            IntSemantics::Wrapping(bits) => {
+                let modulus = BigInt::one() << *bits;
+                let half = BigInt::one() << (*bits - 1);
+                // Euclidean remainder in [0, 2^N), then shifted to [-2^(N-1), 2^(N-1))
+                let mut r = n % &modulus;
+                if r < BigInt::zero() {
+                    r += &modulus;
+                }
+                if r >= half { Some(r - modulus) } else { Some(r) }
+            },
+            IntSemantics::Saturating(bits) => {
+                let max = (BigInt::one() << (*bits - 1)) - 1;
+                let min = -(BigInt::one() << (*bits - 1));
+                Some(n.clamp(min, max))
+            },
+        }
+    }
+}
+
+impl FromStr for IntSemantics {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_bits = |n: &str| match n.parse::<u32>() {
+            Ok(b @ 1..=128) => Ok(b),
+            _ => Err(format!("Wrong number of bits '{n}', expected a number between 1 and 128")),
+        };
+        match s {
+            "unbounded" => Ok(IntSemantics::Unbounded),
+            "checked" => Ok(IntSemantics::Checked),
+            _ => match s.split_once('-') {
+                Some(("wrapping", n)) => Ok(IntSemantics::Wrapping(parse_bits(n)?)),
+                Some(("saturating", n)) => Ok(IntSemantics::Saturating(parse_bits(n)?)),
+                _ => Err(format!("Unknown integer semantics '{s}', expected one of: unbounded, checked, wrapping-N, saturating-N")),
+            }
+        }
+    }
+}
+
+impl Display for IntSemantics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntSemantics::Unbounded => write!(f, "unbounded"),
+            IntSemantics::Checked => write!(f, "checked"),
+            IntSemantics::Wrapping(n) => write!(f, "wrapping-{n}"),
+            IntSemantics::Saturating(n) => write!(f, "saturating-{n}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fit(sem: &str, n: i64) -> Option<Int> {
+        sem.parse::<IntSemantics>().unwrap().fit(Int::from(n))
+    }
+
+    #[test]
+    fn fit_in_the_machine_integers() {
+        assert_eq!(fit("wrapping-8", 127), Some(Int::from(127)));
+        assert_eq!(fit("wrapping-8", 128), Some(Int::from(-128)));
+        assert_eq!(fit("wrapping-8", -129), Some(Int::from(127)));
+        assert_eq!(fit("wrapping-8", 300), Some(Int::from(44)));
+        assert_eq!(fit("saturating-8", 300), Some(Int::from(127)));
+        assert_eq!(fit("saturating-8", -300), Some(Int::from(-128)));
+        assert_eq!(fit("checked", -300), Some(Int::from(-300)));
+        assert_eq!(IntSemantics::Checked.fit(Int::from(Num::MAX) + 1), None);
+        assert_eq!(IntSemantics::Unbounded.fit(Int::from(Num::MAX) + 1), Some(Int::from(Num::MAX) + 1));
+    }
+
+    #[test]
+    fn parse_semantics() {
+        assert_eq!("saturating-16".parse(), Ok(IntSemantics::Saturating(16)));
+        assert!("wrapping-0".parse::<IntSemantics>().is_err());
+        assert!("wrapping-129".parse::<IntSemantics>().is_err());
+        assert!("modular".parse::<IntSemantics>().is_err());
+    }
+}
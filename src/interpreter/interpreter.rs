@@ -1,88 +1,446 @@
-use crate::types::{ast::{Statement, Aexpr, Bexpr, Num, Operator}, errors::RuntimeError};
+use std::{collections::HashMap, rc::Rc, time::{Duration, Instant}};
 
-use super::types::State;
+use num_traits::{ToPrimitive, Zero};
 
-pub fn eval_statement(statement: &Statement<Num>, mut state: State<Num>) -> Result<State<Num>, RuntimeError> {
+use crate::types::{ast::{Statement, Aexpr, Bexpr, Num, Operator, PreOp, PostOp, Procedure, Var}, errors::{ExecutionLimit, RuntimeError}, span::Span};
+
+use super::{int_semantics::IntSemantics, trace::Trace, types::{Int, State, Value}};
+
+// Procedures of the program by name
+pub type Procedures = Rc<HashMap<Var, Procedure<Num>>>;
+
+pub fn procedure_table(procedures: Vec<Procedure<Num>>) -> Procedures {
+    Rc::new(procedures.into_iter().map(|p| (p.name.clone(), p)).collect())
+}
+
+// Nested calls allowed before stopping the execution (a deeper recursion would overflow the native stack)
+const MAX_CALL_DEPTH: usize = 1000;
+
+// Longest array that can be created, the elements are allocated at once
+const MAX_ARRAY_LENGTH: usize = 1 << 24;
+
+// Settings and counters of an execution
+pub struct Context {
+    pub sem: IntSemantics,
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+    pub trace: Option<Trace>,
+    procedures: Procedures,
+    // Procedures being executed with the span of their call, the innermost is the last
+    call_stack: Vec<(Var, Span)>,
+    start: Instant,
+    steps: u64,
+}
+
+impl Context {
+    pub fn new(sem: IntSemantics, max_steps: Option<u64>, timeout: Option<Duration>) -> Self {
+        Context { sem, max_steps, timeout, trace: None, procedures: Procedures::default(), call_stack: vec![], start: Instant::now(), steps: 0 }
+    }
+
+    pub fn with_trace(self, trace: Option<Trace>) -> Self {
+        Context { trace, ..self }
+    }
+
+    pub fn with_procedures(self, procedures: Procedures) -> Self {
+        Context { procedures, ..self }
+    }
+
+    // The limits are checked at every loop iteration, the only place where the execution can get stuck
+    fn check_limits(&self, state: &State<Value>, while_stm: &Statement<Num>) -> Result<(), RuntimeError> {
+        let limit = match (self.max_steps, self.timeout) {
+            (Some(max), _) if self.steps > max => ExecutionLimit::Steps(max),
+            (_, Some(t)) if self.start.elapsed() > t => ExecutionLimit::Timeout(t),
+            _ => return Ok(()),
+        };
+        Err(RuntimeError::StepLimitExceeded { limit, state: state.clone(), while_stm: Box::new(while_stm.clone()) })
+    }
+}
+
+pub fn eval_statement(statement: &Statement<Num>, mut state: State<Value>, ctx: &mut Context) -> Result<State<Value>, RuntimeError> {
     match statement {
-        Statement::Assign(x, aexpr) => {
-            state.insert(x.clone(), eval_aexpr(aexpr, &state)?);
+        Statement::Assign(x, aexpr, _) => {
+            ctx.steps += 1;
+            let before = ctx.trace.is_some().then(|| state.clone());
+            let n = eval_aexpr(aexpr, &mut state, ctx).map_err(|e| assigned_to(e, x.clone()))?;
+            state.insert(x.clone(), Value::Int(n));
+            if let (Some(trace), Some(before)) = (ctx.trace.as_mut(), before) {
+                trace.assign(statement, &before, &state);
+            }
+            Ok(state)
+        },
+        Statement::ArrayAssign(x, i, aexpr, span) => {
+            ctx.steps += 1;
+            let before = ctx.trace.is_some().then(|| state.clone());
+            let i = eval_aexpr(i, &mut state, ctx)?;
+            let n = eval_aexpr(aexpr, &mut state, ctx).map_err(|e| assigned_to(e, format!("{x}[{i}]")))?;
+            // The expressions cannot change an array, so the bounds are checked once both are evaluated
+            let index = check_index(x, i, get_array(x, &state, *span)?, *span)?;
+            if let Some(Value::Array(elems)) = state.get_mut(x) {
+                elems[index] = n;
+            }
+            if let (Some(trace), Some(before)) = (ctx.trace.as_mut(), before) {
+                trace.assign(statement, &before, &state);
+            }
+            Ok(state)
+        },
+        Statement::NewArray(x, n, span) => {
+            ctx.steps += 1;
+            let before = ctx.trace.is_some().then(|| state.clone());
+            let n = eval_aexpr(n, &mut state, ctx)?;
+            let len = match n.to_usize() {
+                Some(len) if len <= MAX_ARRAY_LENGTH => len,
+                _ => return Err(RuntimeError::InvalidArrayLength(n, *span)),
+            };
+            state.insert(x.clone(), Value::Array(vec![Int::zero(); len]));
+            if let (Some(trace), Some(before)) = (ctx.trace.as_mut(), before) {
+                trace.assign(statement, &before, &state);
+            }
+            Ok(state)
+        },
+        Statement::Skip(_) => {
+            ctx.steps += 1;
             Ok(state)
         },
-        Statement::Skip => Ok(state),
+        // The error points to the comparison that made the condition false, if any
+        Statement::Assert(b, span) => match eval_guard(b, *span, &mut state, ctx)? {
+            (true, _) => Ok(state),
+            (false, culprit) => Err(RuntimeError::AssertionFailed { state, span: culprit.unwrap_or(*span) }),
+        },
+        Statement::Assume(b, span) => match eval_guard(b, *span, &mut state, ctx)? {
+            (true, _) => Ok(state),
+            (false, culprit) => Err(RuntimeError::AssumptionViolated { state, span: culprit.unwrap_or(*span) }),
+        },
         Statement::Compose(stm1, stm2) =>{
-            state = eval_statement(&stm1, state)?;
-            state = eval_statement(&stm2, state)?;
+            state = eval_statement(stm1, state, ctx)?;
+            state = eval_statement(stm2, state, ctx)?;
             Ok(state)
         },
-        Statement::IfThenElse(b, stm1, stm2) => {
-            if eval_bexpr(b, &state)? {
-                eval_statement(&stm1, state)
+        Statement::IfThenElse(b, stm1, stm2, span) => {
+            if eval_guard(b, *span, &mut state, ctx)?.0 {
+                eval_statement(stm1, state, ctx)
             } else {
-                eval_statement(&stm2, state)
+                eval_statement(stm2, state, ctx)
             }
         },
-        Statement::While(b, stm) => {
-            let f_bottom = |s: State<Num>|{
-                if eval_bexpr(b, &s)? {
-                    Ok(None)
+        Statement::While(b, stm, span) => {
+            // The guard can update the state, so F⊥ returns the updated state in both cases
+            let f_bottom = |mut s: State<Value>, ctx: &mut Context|{
+                if eval_guard(b, *span, &mut s, ctx)?.0 {
+                    Ok((s, None))
                 } else {
-                    Ok(Some(s))
+                    Ok((s.clone(), Some(s)))
                 }
             };
-            let mut last_state: State<Num> = state.clone();
+            let mut last_state: State<Value> = state;
+
+            // Brent's cycle detection on the states at the loop head: the semantics is
+            // deterministic, so if a state repeats the loop definitely diverges
+            let mut saved: Option<State<Value>> = None;
+            let (mut power, mut lambda) = (1u64, 0u64);
             
             // let mut i=0;
             
             // check if (F^k ⊥) s = undef = (F⊥ o (stm^k-1)) s
-            while f_bottom(last_state.clone())?.is_none() {
+            loop {
+                ctx.check_limits(&last_state, statement)?;
+                if saved.as_ref().is_some_and(|saved| same_state(saved, &last_state)) {
+                    return Err(RuntimeError::Diverges { state: last_state, while_stm: Box::new(statement.clone()) });
+                }
+                lambda += 1;
+                if lambda == power {
+                    saved = Some(last_state.clone());
+                    (power, lambda) = (power * 2, 0);
+                }
+
+                let (s, res) = f_bottom(last_state, ctx)?;
+                if let Some(s) = res {
+                    return Ok(s);
+                }
                 // println!("ITER: {i}, state: {:?}",last_state); i+=1;
                 
                 // S[stm]^(k+1) s = S[stm](S[stm]^k s)
-                last_state = eval_statement(stm, last_state)?;
+                last_state = eval_statement(stm, s, ctx)?;
             }
-            f_bottom(last_state).map(|s|s.unwrap())
         },
     
     }
 }
 
+// The integers are compared first, they are few and change at almost every iteration of a loop,
+// the arrays (possibly long) only when all the integers are equal
+pub fn same_state(s1: &State<Value>, s2: &State<Value>) -> bool {
+    s1.len() == s2.len()
+        && s1.iter().all(|(x, v)| matches!(v, Value::Array(_)) || s2.get(x) == Some(v))
+        && s1 == s2
+}
 
-fn eval_bexpr(bexpr: &Bexpr<Num>, state: &State<Num>) -> Result<bool,RuntimeError> {
-    let b = match bexpr {
-        Bexpr::True => true,
-        Bexpr::False => false,
-        Bexpr::Equal(a1, a2) => 
-            eval_aexpr(a1, state)? == eval_aexpr(a2, state)?,
-        Bexpr::LessEq(a1, a2) => 
-            eval_aexpr(a1, state)? <= eval_aexpr(a2, state)?,
-        Bexpr::Not(b) => 
-            !eval_bexpr(b, state)?,
-        Bexpr::And(b1, b2) => 
-            eval_bexpr(b1, state)? && eval_bexpr(b2, state)?,
+// Evaluation of the guard of an if/while (or of an assertion), counted as a step and recorded in the trace,
+// with the span of the comparison that decided its value
+fn eval_guard(b: &Bexpr<Num>, span: Span, state: &mut State<Value>, ctx: &mut Context) -> Result<(bool, Option<Span>), RuntimeError> {
+    ctx.steps += 1;
+    let before = ctx.trace.is_some().then(|| state.clone());
+    let (res, culprit) = eval_bexpr_located(b, state, ctx)?;
+    if let (Some(trace), Some(before)) = (ctx.trace.as_mut(), before) {
+        trace.test(b, span, res, &before, state);
+    }
+    Ok((res, culprit))
+}
+
+// Both the boolean and the arithmetic evaluations can update the state (see docs-src/inc-dec-semantic.tex)
+pub fn eval_bexpr(bexpr: &Bexpr<Num>, state: &mut State<Value>, ctx: &mut Context) -> Result<bool,RuntimeError> {
+    eval_bexpr_located(bexpr, state, ctx).map(|(b, _)| b)
+}
+
+// The value with the span of the comparisons deciding it: the only false operand of a false and
+// (the only true operand of a true or), otherwise both the operands
+fn eval_bexpr_located(bexpr: &Bexpr<Num>, state: &mut State<Value>, ctx: &mut Context) -> Result<(bool, Option<Span>),RuntimeError> {
+    let res = match bexpr {
+        Bexpr::True => (true, None),
+        Bexpr::False => (false, None),
+        Bexpr::Equal(a1, a2, span) => 
+            (eval_aexpr(a1, state, ctx)? == eval_aexpr(a2, state, ctx)?, Some(*span)),
+        Bexpr::LessEq(a1, a2, span) => 
+            (eval_aexpr(a1, state, ctx)? <= eval_aexpr(a2, state, ctx)?, Some(*span)),
+        Bexpr::Not(b) => {
+            let (t, span) = eval_bexpr_located(b, state, ctx)?;
+            (!t, span)
+        },
+        Bexpr::And(b1, b2) | Bexpr::Or(b1, b2) => {
+            // No short-circuit: the second term is always evaluated (and-2 rule, and the same for or)
+            let (t1, span1) = eval_bexpr_located(b1, state, ctx)?;
+            let (t2, span2) = eval_bexpr_located(b2, state, ctx)?;
+            let res = if matches!(bexpr, Bexpr::And(..)) { t1 && t2 } else { t1 || t2 };
+            match (t1 == res, t2 == res) {
+                (true, false) => (res, span1),
+                (false, true) => (res, span2),
+                _ => (res, join(span1, span2)),
+            }
+        },
+        Bexpr::Less(a1, a2, span) => 
+            (eval_aexpr(a1, state, ctx)? < eval_aexpr(a2, state, ctx)?, Some(*span)),
+        Bexpr::NotEqual(a1, a2, span) => 
+            (eval_aexpr(a1, state, ctx)? != eval_aexpr(a2, state, ctx)?, Some(*span)),
     };
-    Ok(b)
+    Ok(res)
+}
+
+fn join(span1: Option<Span>, span2: Option<Span>) -> Option<Span> {
+    match (span1, span2) {
+        (Some(s1), Some(s2)) => Some(Span::new(s1.start, s2.end)),
+        (s1, s2) => s1.or(s2),
+    }
 }
 
-fn eval_aexpr(aexpr: &Aexpr<Num>, state: &State<Num>) -> Result<Num,RuntimeError> {
+pub fn eval_aexpr(aexpr: &Aexpr<Num>, state: &mut State<Value>, ctx: &mut Context) -> Result<Int,RuntimeError> {
+    let sem = ctx.sem;
     let num = match aexpr {
-        Aexpr::Lit(n) => *n,
-        Aexpr::Var(x) => 
-            match state.get(x) {
-                Some(n) => *n,
-                None => return Err(RuntimeError::VariableNotInitialized(x.clone())),
-            }
-        Aexpr::BinOp(op, a1, a2) =>{
-            let n1 = eval_aexpr(a1, state)?;
-            let n2 = eval_aexpr(a2, state)?;            
-            match op {
+        // The literals are Num, so they always fit (possibly wrapped or saturated)
+        Aexpr::Lit(n) => sem.fit(Int::from(*n)).expect("Num literal out of range"),
+        Aexpr::Var(x, span) => get_var(x, state, *span)?,
+        Aexpr::Index(x, i, span) => {
+            let i = eval_aexpr(i, state, ctx)?;
+            let elems = get_array(x, state, *span)?;
+            elems[check_index(x, i, elems, *span)?].clone()
+        },
+        Aexpr::Len(x, span) => {
+            let len = get_array(x, state, *span)?.len();
+            fit(Int::from(len), None, format!("len({x})"), *span, sem)?
+        },
+        Aexpr::BinOp(op, a1, a2, span) =>{
+            let n1 = eval_aexpr(a1, state, ctx)?;
+            let n2 = eval_aexpr(a2, state, ctx)?;
+            let operation = format!("{n1} {op} {n2}");
+            let n = match op {
                 Operator::Add => n1 + n2,
                 Operator::Sub => n1 - n2,
                 Operator::Mul => n1 * n2,
-                Operator::Div => return Err(RuntimeError::NotImplemented("Division not implemented".to_string())),
-            }
+                Operator::Div if n2.is_zero() => return Err(RuntimeError::DivisionByZero(*span)),
+                Operator::Div => n1 / n2,
+            };
+            fit(n, None, operation, *span, sem)?
         }
-        Aexpr::PreOp(_,_) => return Err(RuntimeError::NotImplemented("Prefix operators not implemented".to_string())),
-        Aexpr::PostOp(_,_) => return Err(RuntimeError::NotImplemented("Postfix operators not implemented".to_string())),
+        Aexpr::PreOp(op, x, span) => {
+            let n = get_var(x, state, *span)?;
+            let operation = format!("{op}{x} with {x} = {n}");
+            let n = match op {
+                PreOp::Inc => n + 1,
+                PreOp::Dec => n - 1,
+            };
+            let n = fit(n, Some(x), operation, *span, sem)?;
+            state.insert(x.clone(), Value::Int(n.clone()));
+            n
+        },
+        Aexpr::PostOp(op, x, span) => {
+            let n = get_var(x, state, *span)?;
+            let operation = format!("{x}{op} with {x} = {n}");
+            let updated = match op {
+                PostOp::Inc => n.clone() + 1,
+                PostOp::Dec => n.clone() - 1,
+            };
+            state.insert(x.clone(), Value::Int(fit(updated, Some(x), operation, *span, sem)?));
+            n
+        },
+        Aexpr::Call(p, args, span) => {
+            let procedures = Rc::clone(&ctx.procedures);
+            let Some(procedure) = procedures.get(p) else {
+                return Err(RuntimeError::UndefinedProcedure(p.clone(), *span))
+            };
+            if procedure.params.len() != args.len() {
+                return Err(RuntimeError::ArgumentCount { name: p.clone(), expected: procedure.params.len(), found: args.len(), span: *span })
+            }
+            let mut frame = State::new();
+            for (x, a) in procedure.params.iter().zip(args) {
+                let n = eval_aexpr(a, state, ctx)?;
+                frame.insert(x.clone(), Value::Int(n));
+            }
+            call(procedure, frame, *span, ctx)?
+        },
     };
     Ok(num)
 }
+
+// Execution of the body in a new frame with only the parameters, the errors inside report the call stack
+fn call(procedure: &Procedure<Num>, frame: State<Value>, span: Span, ctx: &mut Context) -> Result<Int, RuntimeError> {
+    if ctx.call_stack.len() >= MAX_CALL_DEPTH {
+        return Err(RuntimeError::CallDepthExceeded { depth: MAX_CALL_DEPTH, span })
+    }
+    ctx.call_stack.push((procedure.name.clone(), span));
+    let result = eval_statement(&procedure.body, frame, ctx)
+        .and_then(|s| get_var(&procedure.result, &s, procedure.span))
+        .map_err(|e| match e {
+            RuntimeError::InCall { .. } => e,
+            e => RuntimeError::InCall { stack: ctx.call_stack.clone(), error: Box::new(e) },
+        });
+    ctx.call_stack.pop();
+    result
+}
+
+fn fit(n: Int, var: Option<&String>, operation: String, span: Span, sem: IntSemantics) -> Result<Int, RuntimeError> {
+    sem.fit(n).ok_or(RuntimeError::Overflow { var: var.cloned(), operation, span })
+}
+
+// Overflows in the expression are reported on the assigned variable
+fn assigned_to(e: RuntimeError, x: String) -> RuntimeError {
+    match e {
+        RuntimeError::Overflow { var: None, operation, span } => RuntimeError::Overflow { var: Some(x), operation, span },
+        e => e,
+    }
+}
+
+fn get_var(x: &str, state: &State<Value>, span: Span) -> Result<Int, RuntimeError> {
+    match state.get(x) {
+        Some(Value::Int(n)) => Ok(n.clone()),
+        Some(Value::Array(_)) => Err(RuntimeError::NotAnInteger(x.to_string(), span)),
+        None => Err(RuntimeError::VariableNotInitialized(x.to_string(), span)),
+    }
+}
+
+fn get_array<'s>(x: &str, state: &'s State<Value>, span: Span) -> Result<&'s Vec<Int>, RuntimeError> {
+    match state.get(x) {
+        Some(Value::Array(elems)) => Ok(elems),
+        Some(Value::Int(_)) => Err(RuntimeError::NotAnArray(x.to_string(), span)),
+        None => Err(RuntimeError::VariableNotInitialized(x.to_string(), span)),
+    }
+}
+
+// Position of the element i of the array x
+fn check_index(x: &str, i: Int, elems: &[Int], span: Span) -> Result<usize, RuntimeError> {
+    match i.to_usize() {
+        Some(index) if index < elems.len() => Ok(index),
+        _ => Err(RuntimeError::IndexOutOfBounds { array: x.to_string(), index: i, length: elems.len(), span }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_string;
+
+    use super::*;
+
+    fn run(source: &str, ctx: &mut Context) -> Result<State<Value>, RuntimeError> {
+        let ast = parse_string::<Num>(source.to_string()).unwrap().main;
+        eval_statement(&ast, State::new(), ctx)
+    }
+
+    fn int(n: i32) -> Value {
+        Value::Int(n.into())
+    }
+
+    #[test]
+    fn division_and_increments() {
+        let mut ctx = Context::new(IntSemantics::Checked, None, None);
+        let state = run("x := 1; y := x++ + x; z := --x * 10; w := (0 - 7) / 2;", &mut ctx).unwrap();
+        assert_eq!([&state["x"], &state["y"], &state["z"], &state["w"]], [&int(1), &int(3), &int(10), &int(-3)]);
+        let res = run("x := 0; y := 1 / x;", &mut ctx);
+        assert!(matches!(res, Err(RuntimeError::DivisionByZero(span)) if span.line() == 1));
+    }
+
+    #[test]
+    fn overflows_follow_the_integer_semantics() {
+        let program = "x := 100; y := x + x;";
+        let mut ctx = Context::new(IntSemantics::Wrapping(8), None, None);
+        assert_eq!(run(program, &mut ctx).unwrap()["y"], int(-56));
+        let mut ctx = Context::new(IntSemantics::Saturating(8), None, None);
+        assert_eq!(run(program, &mut ctx).unwrap()["y"], int(127));
+        let mut ctx = Context::new(IntSemantics::Checked, None, None);
+        let res = run("x := 170141183460469231731687303715884105727; y := x++;", &mut ctx);
+        assert!(matches!(res, Err(RuntimeError::Overflow { var: Some(x), .. }) if x == "x"));
+    }
+
+    #[test]
+    fn errors_point_to_the_source() {
+        let mut ctx = Context::new(IntSemantics::Checked, None, None);
+        let res = run("x := 1;\nassert x <= 1 and x == 2;", &mut ctx);
+        assert!(matches!(res, Err(RuntimeError::AssertionFailed { span, .. }) if span == Span::new((2, 19), (2, 25))));
+        let res = run("x := 1;\ny := x + z;", &mut ctx);
+        assert!(matches!(res, Err(RuntimeError::VariableNotInitialized(z, span)) if z == "z" && span == Span::new((2, 10), (2, 11))));
+    }
+
+    #[test]
+    fn diverging_loops_are_detected() {
+        let mut ctx = Context::new(IntSemantics::Checked, None, None);
+        let res = run("x := 0; a := array(3); while x <= 1 do { a[x] := 1; x := 1 - x; }", &mut ctx);
+        assert!(matches!(res, Err(RuntimeError::Diverges { state, .. }) if state["a"] == Value::Array(vec![1.into(), 1.into(), 0.into()])));
+        let mut ctx = Context::new(IntSemantics::Checked, Some(1000), None);
+        let res = run("x := 0; while 0 <= x do x := x + 1;", &mut ctx);
+        assert!(matches!(res, Err(RuntimeError::StepLimitExceeded { limit: ExecutionLimit::Steps(1000), .. })));
+    }
+
+    #[test]
+    fn states_with_different_arrays_differ() {
+        let state = |x: i32, elems: &[i32]| State::from([
+            ("x".to_string(), Value::Int(x.into())),
+            ("a".to_string(), Value::Array(elems.iter().map(|&n| n.into()).collect())),
+        ]);
+        assert!(same_state(&state(1, &[0, 2]), &state(1, &[0, 2])));
+        assert!(!same_state(&state(1, &[0, 2]), &state(2, &[0, 2])));
+        assert!(!same_state(&state(1, &[0, 2]), &state(1, &[0, 3])));
+        assert!(!same_state(&state(1, &[]), &State::from([("x".to_string(), Value::Int(1.into()))])));
+    }
+
+    #[test]
+    fn procedure_calls() {
+        let procedures = "proc fact(n) returns r {\n    if n <= 0 then {r := 1;} else {r := n * fact(n - 1);}\n}\nproc div(a, b) returns q {\n    q := a / b;\n}\n";
+        let run_with_procedures = |main: &str| {
+            let module = parse_string::<Num>(format!("{procedures}{main}")).unwrap();
+            let mut ctx = Context::new(IntSemantics::Checked, None, None).with_procedures(procedure_table(module.procedures));
+            eval_statement(&module.main, State::new(), &mut ctx)
+        };
+        assert_eq!(run_with_procedures("x := fact(5);").unwrap()["x"], int(120));
+        let res = run_with_procedures("x := fact(5);\ny := div(x, 0);");
+        let Err(RuntimeError::InCall { stack, error }) = res else { panic!("{res:?}") };
+        assert_eq!(stack.iter().map(|(p, span)| (p.as_str(), span.line())).collect::<Vec<_>>(), [("div", 8)]);
+        assert!(matches!(*error, RuntimeError::DivisionByZero(span) if span.line() == 5));
+    }
+
+    #[test]
+    fn arrays_are_checked() {
+        let mut ctx = Context::new(IntSemantics::Checked, None, None);
+        let state = run("a := array(3);\na[1] := 4;\nx := a[1] + len(a);", &mut ctx).unwrap();
+        assert_eq!((&state["a"], &state["x"]), (&Value::Array(vec![0.into(), 4.into(), 0.into()]), &int(7)));
+        let res = run("a := array(3);\nx := a[3];", &mut ctx);
+        assert!(matches!(res, Err(RuntimeError::IndexOutOfBounds { index, length: 3, span, .. }) if index == 3.into() && span.line() == 2));
+        let res = run("a := array(0 - 1);", &mut ctx);
+        assert!(matches!(res, Err(RuntimeError::InvalidArrayLength(..))));
+    }
+}
@@ -1,2 +1,4 @@
 pub mod interpreter;
-pub mod types;
\ No newline at end of file
+pub mod int_semantics;
+pub mod types;pub mod debugger;
+pub mod trace;
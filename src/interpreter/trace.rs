@@ -0,0 +1,198 @@
+use std::{fs::File, io::{self, BufRead, BufReader, BufWriter, Write}};
+
+use num_traits::ToPrimitive;
+use serde_json::{json, Map, Value};
+
+use crate::types::{ast::{Bexpr, Num, Statement}, span::Span};
+
+use super::types::{Int, State, Value as RuntimeValue};
+
+
+// Execution trace, written as JSON lines: one line for every executed assignment and test like
+// {"step":1,"kind":"assign","statement":"x := 1","line":1,"column":1,"before":{},"after":{"x":1}}
+// (the tests have also the "result" of the guard, before the states)
+pub struct Trace {
+    out: BufWriter<File>,
+    step: u64,
+    // The first write error, reported at the end so that the execution is not interrupted
+    error: Option<io::Error>,
+}
+
+impl Trace {
+    pub fn create(path: &str) -> io::Result<Self> {
+        Ok(Trace { out: BufWriter::new(File::create(path)?), step: 0, error: None })
+    }
+
+    pub fn assign(&mut self, stm: &Statement<Num>, before: &State<RuntimeValue>, after: &State<RuntimeValue>) {
+        let statement = match stm {
+            Statement::Assign(x, a, _) => format!("{x} := {a}"),
+            Statement::ArrayAssign(x, i, a, _) => format!("{x}[{i}] := {a}"),
+            Statement::NewArray(x, n, _) => format!("{x} := array({n})"),
+            _ => return,
+        };
+        self.write("assign", statement, None, stm.span(), before, after);
+    }
+
+    pub fn test(&mut self, b: &Bexpr<Num>, span: Span, result: bool, before: &State<RuntimeValue>, after: &State<RuntimeValue>) {
+        self.write("test", b.to_string(), Some(result), span, before, after);
+    }
+
+    fn write(&mut self, kind: &str, statement: String, result: Option<bool>, span: Span, before: &State<RuntimeValue>, after: &State<RuntimeValue>) {
+        self.step += 1;
+        let mut entry = json!({
+            "step": self.step,
+            "kind": kind,
+            "statement": statement,
+            "line": span.start.0,
+            "column": span.start.1,
+        });
+        if let Some(result) = result {
+            entry["result"] = json!(result);
+        }
+        entry["before"] = state_to_json(before);
+        entry["after"] = state_to_json(after);
+        if self.error.is_none() {
+            self.error = writeln!(self.out, "{entry}").err();
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error {
+            Some(err) => Err(err),
+            None => self.out.flush(),
+        }
+    }
+}
+
+// The values that do not fit in a JSON number are written as strings, the arrays are JSON arrays
+fn state_to_json(state: &State<RuntimeValue>) -> Value {
+    let mut vars = state.iter().collect::<Vec<_>>();
+    vars.sort_by_key(|(x, _)| *x);
+    let int_to_json = |n: &Int| n.to_i64().map_or_else(|| json!(n.to_string()), |n| json!(n));
+    let map = vars.into_iter()
+        .map(|(x, v)| (x.clone(), match v {
+            RuntimeValue::Int(n) => int_to_json(n),
+            RuntimeValue::Array(elems) => Value::Array(elems.iter().map(int_to_json).collect()),
+        }))
+        .collect::<Map<_, _>>();
+    Value::Object(map)
+}
+
+
+pub fn read_trace(path: &str) -> Result<Vec<Value>, String> {
+    let file = File::open(path).map_err(|e| format!("Can't read from file: {path}, err {e}"))?;
+    BufReader::new(file).lines().enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|(i, line)| {
+            let line = line.map_err(|e| format!("Can't read from file: {path}, err {e}"))?;
+            serde_json::from_str(&line).map_err(|e| format!("{path}:{}: invalid trace entry, {e}", i + 1))
+        })
+        .collect()
+}
+
+// Description of the first point where the actual trace differs from the expected one
+pub fn first_divergence(expected: &[Value], actual: &[Value]) -> Option<String> {
+    for (i, (e, a)) in expected.iter().zip(actual).enumerate() {
+        let step = i + 1;
+        let same = |field: &str| e.get(field) == a.get(field);
+        if !["kind", "statement", "line", "column"].iter().all(|f| same(f)) {
+            return Some(format!(
+                "Step {step}: different statement executed\n  expected: {}\n  actual:   {}",
+                describe(e), describe(a)
+            ));
+        }
+        if !same("before") {
+            return Some(format!(
+                "Step {step}: different state before {}\n{}",
+                describe(e), state_diff(&e["before"], &a["before"])
+            ));
+        }
+        if !same("result") {
+            return Some(format!(
+                "Step {step}: different result of the test {}\n  expected: {}\n  actual:   {}",
+                describe(e), e["result"], a["result"]
+            ));
+        }
+        if !same("after") {
+            return Some(format!(
+                "Step {step}: different state after {}\n{}",
+                describe(e), state_diff(&e["after"], &a["after"])
+            ));
+        }
+    }
+    match expected.len().cmp(&actual.len()) {
+        std::cmp::Ordering::Equal => None,
+        std::cmp::Ordering::Greater => Some(format!(
+            "Step {}: the actual trace ends, the expected one continues with {}",
+            actual.len() + 1, describe(&expected[actual.len()])
+        )),
+        std::cmp::Ordering::Less => Some(format!(
+            "Step {}: the expected trace ends, the actual one continues with {}",
+            expected.len() + 1, describe(&actual[expected.len()])
+        )),
+    }
+}
+
+fn describe(entry: &Value) -> String {
+    let statement = entry["statement"].as_str().unwrap_or("?");
+    format!("{} '{statement}' at {}:{}", entry["kind"].as_str().unwrap_or("?"), entry["line"], entry["column"])
+}
+
+fn state_diff(expected: &Value, actual: &Value) -> String {
+    let empty = Map::new();
+    let (e, a) = (expected.as_object().unwrap_or(&empty), actual.as_object().unwrap_or(&empty));
+    let mut vars = e.keys().chain(a.keys()).collect::<Vec<_>>();
+    vars.sort();
+    vars.dedup();
+    let show = |v: Option<&Value>| v.map_or("undefined".to_string(), |v| v.to_string());
+    vars.into_iter()
+        .filter(|x| e.get(*x) != a.get(*x))
+        .map(|x| format!("  {x}: expected {}, actual {}", show(e.get(x)), show(a.get(x))))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{interpreter::{int_semantics::IntSemantics, interpreter::{eval_statement, Context}}, parser::parse_string};
+
+    use super::*;
+
+    // Trace of the program, written to a temporary file and read back
+    fn trace(source: &str, name: &str) -> Vec<Value> {
+        let path = std::env::temp_dir().join(format!("while-trace-{}-{name}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let ast = parse_string::<Num>(source.to_string()).unwrap().main;
+        let mut ctx = Context::new(IntSemantics::Checked, None, None).with_trace(Some(Trace::create(path).unwrap()));
+        eval_statement(&ast, State::new(), &mut ctx).unwrap();
+        ctx.trace.take().unwrap().finish().unwrap();
+        let entries = read_trace(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        entries
+    }
+
+    #[test]
+    fn assignments_and_tests_are_traced() {
+        let entries = trace("x := 1;\nif x <= 0 then y := 1; else y := 2;", "entries");
+        assert_eq!(entries, [
+            json!({"step": 1, "kind": "assign", "statement": "x := 1", "line": 1, "column": 1, "before": {}, "after": {"x": 1}}),
+            json!({"step": 2, "kind": "test", "statement": "(x <= 0)", "line": 2, "column": 1, "result": false, "before": {"x": 1}, "after": {"x": 1}}),
+            json!({"step": 3, "kind": "assign", "statement": "y := 2", "line": 2, "column": 29, "before": {"x": 1}, "after": {"x": 1, "y": 2}}),
+        ]);
+    }
+
+    #[test]
+    fn first_divergence_of_two_traces() {
+        let expected = trace("x := 1;\ny := x + 1;", "expected");
+        let actual = trace("x := 1;\ny := x + 2;", "actual");
+        assert_eq!(first_divergence(&expected, &expected), None);
+        assert_eq!(
+            first_divergence(&expected, &actual).unwrap(),
+            "Step 2: different statement executed\n  expected: assign 'y := (x + 1)' at 2:1\n  actual:   assign 'y := (x + 2)' at 2:1"
+        );
+        assert_eq!(
+            first_divergence(&expected, &expected[..1]).unwrap(),
+            "Step 2: the actual trace ends, the expected one continues with assign 'y := (x + 1)' at 2:1"
+        );
+    }
+}
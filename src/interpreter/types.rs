@@ -1,3 +1,49 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::{Debug, Display}, str::FromStr};
+
+use num_bigint::BigInt;
 
 pub type State<N> = HashMap<String, N>;
+
+// Runtime values are exact, the integer semantics decides how they fit in the machine
+pub type Int = BigInt;
+
+// Value of a variable: an integer or an array of integers
+#[derive(Clone, PartialEq)]
+pub enum Value {
+    Int(Int),
+    Array(Vec<Int>),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{n}"),
+            Value::Array(elems) => write!(f, "[{}]", elems.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", ")),
+        }
+    }
+}
+
+// The states in the messages are printed with Debug, so the values look like the integers did
+impl Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+// An integer or an array like [1,2,3]
+impl FromStr for Value {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let err = || format!("'{s}' is neither an integer nor an array");
+        match s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            Some(elems) if elems.trim().is_empty() => Ok(Value::Array(vec![])),
+            Some(elems) => elems.split(',')
+                .map(|n| n.trim().parse().map_err(|_| err()))
+                .collect::<Result<_, _>>()
+                .map(Value::Array),
+            None => s.parse().map(Value::Int).map_err(|_| err()),
+        }
+    }
+}
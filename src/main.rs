@@ -1,11 +1,12 @@
-use std::{collections::HashMap, fmt::Display, fs::File};
-use analyzer::{analyzers::generic_analyzer::GenericAnalyzer, domains::{bounded_interval_domain::BoundedIntervalDomain, congruence_domain::CongruenceDomain, extended_sign_domain::ExtendedSignDomain, sign_domain::SignDomain}, states::hashmap_state::HashMapState, types::{analyzer::StaticAnalyzer, domain::AbstractDomain, program::{Label, Program, ProgramInterface}, state::AbstractState}};
-use config::{AnalyzerConfiguration, Config};
-use interpreter::{types::State, interpreter::eval_statement};
-use parser::parse_file;
-use types::{ast::{Num, Statement}, errors::RuntimeError};
+use std::{collections::{BTreeSet, HashMap}, fmt::Display, str::FromStr};
+use analyzer::{alarms::{collect_alarms, Alarm}, arrays, assertions::{check_assertions, AssertionResult, AssertionStatus}, dead_code::{find_dead_code, DeadCode}, dot::{program_to_dot, LabelAnnotation}, interprocedural::{analyze_program, with_summaries, ProcedureAnalysis}, json_output::analysis_to_json, analyzers::generic_analyzer::GenericAnalyzer, domains::{bounded_interval_domain::BoundedIntervalDomain, congruence_domain::CongruenceDomain, extended_sign_domain::ExtendedSignDomain, interval_domain::IntervalDomain, product_domain::{IntervalCongruence, Product}, sign_domain::SignDomain}, states::{hashmap_state::HashMapState, octagon_state::OctagonState, polyhedra_state::PolyhedraState}, soundness::{check_soundness, SoundnessCheck, SoundnessReport}, thresholds::{add_thresholds, program_thresholds}, types::{analyzer::{StaticAnalyzer, WideningPoints}, domain::{AbstractDomain, Interval}, program::{Label, Program, ProgramInterface}, state::AbstractState}};
+use config::{AnalyzerConfiguration, Config, InterpreterConfiguration, OutputFormat};
+use serde_json::{json, Value};
+use interpreter::{debugger, interpreter::{eval_statement, procedure_table, Context}, trace::{first_divergence, read_trace, Trace}, types::{Int, State, Value as RuntimeValue}};
+use parser::parse_string;
+use types::{ast::{Module, Num, NumLiteral, Procedure}, errors::ParserError, span::{render_error, span_text, Span}};
 
-use crate::{analyzer::printers::print_stm_with_inv, types::lit_interval::LitInterval};
+use crate::{analyzer::printers::{print_stm_with_dead_code, print_stm_with_inv, print_stm_with_states}, types::lit_interval::LitInterval};
 
 
 mod types;
@@ -16,60 +17,167 @@ mod analyzer;
 mod config;
 
 
+// The recursive procedures are evaluated on the native stack, up to the call depth limit of the interpreter
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
 fn main() {
+    let handle = std::thread::Builder::new()
+        .name("main".to_string())
+        .stack_size(STACK_SIZE)
+        .spawn(run_command)
+        .unwrap();
+    if handle.join().is_err() {
+        std::process::exit(101)
+    }
+}
+
+fn run_command() {
     let config = Config::new();
 
-    let parser_config = config.get_parser_conf();
+    // trace-diff works only on the traces, without a program
+    if let Config::TraceDiffConfiguration { expected, actual } = &config {
+        return trace_diff(expected, actual)
+    }
+
+    let parser_config = config.get_parser_conf().expect("command without a program");
     std::env::set_var("print-token", parser_config.print_token.to_string());
     std::env::set_var("print-cst", parser_config.print_cst.to_string());
     std::env::set_var("print-ast", parser_config.print_ast.to_string());
     std::env::set_var("print-pretty-cst", parser_config.print_pretty_cst.to_string());
     std::env::set_var("print-pretty-ast", parser_config.print_pretty_ast.to_string());
+    std::env::set_var("desugar", parser_config.desugar.to_string());
 
 
     
-    let file = match File::open(&parser_config.filename){
-        Ok(f) => f,
+    let source = match std::fs::read_to_string(&parser_config.filename){
+        Ok(s) => s,
         Err(e) => panic!("Can't read from file: {}, err {}", parser_config.filename, e),
     };
     match config {
         Config::InterpreterConfiguration { config, .. } => {
-            let ast: Statement<Num> = match parse_file(file) {
+            let Module { procedures, main: ast } = match parse_string::<Num>(source.clone()) {
+                Ok(module) => module,
+                Err(errors) => exit_with_parser_errors(&source, errors)
+            };
+
+            let init_state = fit_init_state(&config);
+
+            if let Some(path) = &config.cfg_dot {
+                let dot = program_to_dot(&Program::from(ast.clone()), &HashMap::new());
+                if let Err(e) = std::fs::write(path, dot) {
+                    return println!("Can't write the dot file: {path}, err {e}");
+                }
+            }
+
+            let trace = match config.trace.as_deref().map(Trace::create).transpose() {
+                Ok(trace) => trace,
+                Err(e) => return println!("Can't write the trace file: {}, err {e}", config.trace.unwrap()),
+            };
+
+            let mut ctx = Context::new(config.int_semantics, config.max_steps, config.timeout)
+                .with_trace(trace)
+                .with_procedures(procedure_table(procedures));
+            match eval_statement(&ast, init_state, &mut ctx) {
+                Ok(state) => println!("FINAL STATE: {:?}", state),
+                Err(err) => println!("{}", err.render(&source, config.int_semantics)),
+            }
+            // The trace is kept also when the execution fails
+            if let Some(Err(e)) = ctx.trace.map(Trace::finish) {
+                println!("Can't write the trace file: {}, err {e}", config.trace.unwrap());
+            }
+        },
+        Config::DebuggerConfiguration { config, .. } => {
+            let Module { procedures, main: ast } = match parse_string::<Num>(source.clone()) {
+                Ok(module) => module,
+                Err(errors) => exit_with_parser_errors(&source, errors)
+            };
+            let init_state = fit_init_state(&config);
+
+            let max_steps = config.max_steps.unwrap_or(u64::MAX);
+            debugger::debug(&ast, init_state, config.int_semantics, max_steps, procedure_table(procedures), &source);
+        },
+        Config::SoundnessConfiguration { config, .. } => {
+            let ast: Module<LitInterval> = match parse_string(source.clone()) {
                 Ok(ast) => ast,
-                Err(err) => panic!("{err}")
+                Err(errors) => exit_with_parser_errors(&source, errors)
             };
 
-            let final_state = eval_statement(
-                &ast,
-                config.init_state.unwrap_or(State::new())
+            let check = &config.check;
+            let report = match config.analyzer.domain {
+                config::Domain::Sign => soundness::<SignDomain, HashMapState<_>>(ast, config.analyzer, check),
+                config::Domain::ExtendedSign => soundness::<ExtendedSignDomain, HashMapState<_>>(ast, config.analyzer, check),
+                config::Domain::BoundedInterval => soundness::<BoundedIntervalDomain, HashMapState<_>>(ast, config.analyzer, check),
+                config::Domain::Interval => soundness::<IntervalDomain, HashMapState<_>>(ast, config.analyzer, check),
+                config::Domain::Congruence => soundness::<CongruenceDomain, HashMapState<_>>(ast, config.analyzer, check),
+                config::Domain::Octagon => soundness::<IntervalDomain, OctagonState>(ast, config.analyzer, check),
+                config::Domain::Polyhedra => soundness::<IntervalDomain, PolyhedraState>(ast, config.analyzer, check),
+                config::Domain::IntervalCongruence => soundness::<Product<IntervalDomain, CongruenceDomain, IntervalCongruence>, HashMapState<_>>(ast, config.analyzer, check),
+            };
+            println!(
+                "Checked {} concrete states in {} runs (initial values in {}, seed {})",
+                report.checked_states, report.runs, Interval::from(check.range), check.seed
             );
-            match final_state {
-                Ok(state) => println!("FINAL STATE: {:?}", state),
-                Err(RuntimeError::VariableNotInitialized(x)) =>
-                    println!("Runtime error: variable '{}' used before initialization", x),
-                Err(RuntimeError::NotImplemented(str)) =>
-                    println!("{str}")
+            if report.counterexamples.is_empty() {
+                println!("No counterexample found");
+            } else {
+                println!("Found {} unsound label(s):", report.counterexamples.len());
+                for c in report.counterexamples {
+                    println!("{c}");
+                }
+                std::process::exit(1)
             }
         },
+        Config::TraceDiffConfiguration { .. } => unreachable!(),
         Config::AnalyzerConfiguration { config, .. } => {  
             std::env::set_var("print-iterations",config.print_iterations.to_string());
             // println!("{:?}",config);
 
-            let ast: Statement<LitInterval> = match parse_file(file) {
-                Ok(ast) => ast,
-                Err(err) => panic!("{err}")
+            let module: Module<LitInterval> = match parse_string(source.clone()) {
+                Ok(module) => module,
+                Err(errors) => exit_with_parser_errors(&source, errors)
             };
+            let ast = module.main.clone();
 
-            let (prog_int, result) = match config.domain {
-                config::Domain::Sign => analyze::<SignDomain>(ast.clone(), config),                
-                config::Domain::ExtendedSign => analyze::<ExtendedSignDomain>(ast.clone(), config),
-                config::Domain::BoundedInterval => analyze::<BoundedIntervalDomain>(ast.clone(), config),
-                config::Domain::Congruence => analyze::<CongruenceDomain>(ast.clone(), config),
+            let alarms_json = config.alarms_json;
+            let report_dead_code = config.report_dead_code;
+            let annotate = config.annotate;
+            let dot_path = config.dot.clone();
+            let AnalysisOutput { prog: prog_int, result, precondition, summaries, assertions, alarms, dead_code, json, dot } = match config.domain {
+                config::Domain::Sign => analyze::<SignDomain, HashMapState<_>>(module.clone(), config),                
+                config::Domain::ExtendedSign => analyze::<ExtendedSignDomain, HashMapState<_>>(module.clone(), config),
+                config::Domain::BoundedInterval => analyze::<BoundedIntervalDomain, HashMapState<_>>(module.clone(), config),
+                config::Domain::Interval => analyze::<IntervalDomain, HashMapState<_>>(module.clone(), config),
+                config::Domain::Congruence => analyze::<CongruenceDomain, HashMapState<_>>(module.clone(), config),
+                config::Domain::Octagon => analyze::<IntervalDomain, OctagonState>(module.clone(), config),
+                config::Domain::Polyhedra => analyze::<IntervalDomain, PolyhedraState>(module.clone(), config),
+                config::Domain::IntervalCongruence => analyze::<Product<IntervalDomain, CongruenceDomain, IntervalCongruence>, HashMapState<_>>(module.clone(), config),
             };
+            if let (Some(path), Some(dot)) = (dot_path, dot) {
+                if let Err(e) = std::fs::write(&path, dot) {
+                    eprintln!("Can't write the dot file: {path}, err {e}");
+                }
+            }
+            // The statement of an alarm is its source line
+            let statement = |a: &Alarm| source.lines().nth(a.span.line().wrapping_sub(1)).unwrap_or_default().trim().to_string();
+            if let Some(json) = json {
+                let mut json = Value::Object([("program".to_string(), json!(module.to_string()))].into_iter()
+                    .chain(json.as_object().cloned().unwrap_or_default())
+                    .collect());
+                json["assertions"] = assertions.iter()
+                    .map(|a| json!({"line": a.span.line(), "assertion": span_text(&source, a.span), "status": a.status.to_string()}))
+                    .collect();
+                json["alarms"] = alarms.iter().map(|a| a.to_json(&statement(a))).collect();
+                println!("{}", serde_json::to_string_pretty(&json).unwrap());
+                return
+            }
             println!("╔═════════════════╗");
             println!("║ Analyzer Result ║");
             println!("╚═════════════════╝");
-            println!("{}", print_stm_with_inv(ast));
+            if annotate {
+                println!("{}", print_stm_with_states(ast.clone(), prog_int.get_statements(), &result));
+            } else {
+                println!("{}", print_stm_with_inv(ast.clone()));
+            }
             println!();
             println!("LOOP INVARIANTS:");
             let mut loop_labels = prog_int.get_loop_label().clone();
@@ -81,6 +189,46 @@ fn main() {
 
             println!();
             println!("FINAL INVARIANT: {}", result.get(&prog_int.get_end_label()).unwrap());
+            if let Some((post, pre)) = precondition {
+                println!();
+                println!("NECESSARY PRECONDITION for {post} at the exit: {pre}");
+            }
+            if !summaries.is_empty() {
+                println!();
+                println!("PROCEDURE SUMMARIES:");
+                for s in &summaries {
+                    println!("{s}");
+                }
+            }
+            if !assertions.is_empty() {
+                println!();
+                println!("ASSERTIONS:");
+                for a in &assertions {
+                    println!("{:>4} | {:<8} | {}", a.span.line(), a.status.to_string(), span_text(&source, a.span));
+                }
+                let proved = assertions.iter().filter(|a| a.status == AssertionStatus::Proved).count();
+                println!("{proved}/{} assertion(s) proved", assertions.len());
+            }
+            if alarms_json {
+                let alarms = alarms.iter().map(|a| a.to_json(&statement(a))).collect::<Vec<_>>();
+                println!();
+                println!("{}", serde_json::to_string_pretty(&alarms).unwrap());
+            } else if !alarms.is_empty() {
+                println!();
+                println!("ALARMS:");
+                for a in &alarms {
+                    println!("{:>6} | label {:<3} | {} in `{}`", a.span.to_string(), a.label, a.kind, statement(a));
+                }
+            }
+            if report_dead_code {
+                println!();
+                if dead_code.is_empty() {
+                    println!("DEAD CODE: none");
+                } else {
+                    println!("DEAD CODE:");
+                    print!("{}", print_stm_with_dead_code(ast, &dead_code));
+                }
+            }
         },
     }
 
@@ -88,25 +236,201 @@ fn main() {
 
 }
 
+fn trace_diff(expected: &str, actual: &str) {
+    let (expected, actual) = match (read_trace(expected), read_trace(actual)) {
+        (Ok(e), Ok(a)) => (e, a),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("{err}");
+            std::process::exit(2)
+        },
+    };
+    match first_divergence(&expected, &actual) {
+        None => println!("The traces are identical ({} steps)", expected.len()),
+        Some(divergence) => {
+            println!("{divergence}");
+            std::process::exit(1)
+        },
+    }
+}
+
+// The abstract states depend on the domain, so they are parsed only here: a wrong one
+// is reported as clap does for the other arguments
+fn parse_state_arg<S: FromStr<Err: Display>>(s: &str, arg: &str) -> S {
+    s.parse().unwrap_or_else(|e| exit_with_invalid_arg(s, arg, e))
+}
+
+// Reported like the errors of clap on the other arguments
+fn exit_with_invalid_arg(value: &str, arg: &str, e: impl Display) -> ! {
+    eprintln!("error: invalid value '{value}' for '{arg}': {e}\n\nFor more information, try '--help'.");
+    std::process::exit(2)
+}
+
+fn exit_with_parser_errors<N: NumLiteral>(source: &str, errors: Vec<ParserError<N>>) -> ! {
+    for err in &errors {
+        let msg = format!("Syntax error: {err}");
+        match err.pos() {
+            Some(pos) => eprintln!("{}\n", render_error(source, Span::new(pos, (pos.0, pos.1 + 1)), &msg)),
+            None => eprintln!("{msg}\n"),
+        }
+    }
+    eprintln!("Found {} syntax error(s)", errors.len());
+    std::process::exit(1)
+}
+
+// The initial values are adapted to the machine model like any other value
+fn fit_init_state(config: &InterpreterConfiguration) -> State<RuntimeValue> {
+    let mut init_state = State::new();
+    for (x, v) in config.init_state.clone().unwrap_or_default() {
+        let fit = |n: Int| config.int_semantics.fit(n.clone()).ok_or(n);
+        let fitted = match &v {
+            RuntimeValue::Int(n) => fit(n.clone()).map(RuntimeValue::Int),
+            RuntimeValue::Array(elems) => elems.iter().cloned().map(fit).collect::<Result<_, _>>().map(RuntimeValue::Array),
+        };
+        match fitted {
+            Ok(v) => { init_state.insert(x, v); },
+            Err(n) => exit_with_invalid_arg(
+                &format!("{x}:{v}"),
+                "--state <state>",
+                format_args!("{n} does not fit in {} integers", config.int_semantics)
+            ),
+        }
+    }
+    init_state
+}
+
 fn to_boxed_state<D:Display + 'static>(r: HashMap<Label,D>)->HashMap<Label, Box<dyn Display>>{
     r.into_iter()
     .map(|(l,s)|(l, Box::new(s) as Box<dyn Display>))
     .collect::<HashMap<_,_>>()
 }
 
-fn analyze<D: AbstractDomain + 'static>(ast: Statement<LitInterval>, config: AnalyzerConfiguration) -> (Box<dyn ProgramInterface>, HashMap<Label, Box<dyn Display>>){
-    if let Err(e) = D::set_config(config.domain_config) {
+// Analysis from the top state checked against random concrete executions
+fn soundness<D, S>(module: Module<LitInterval>, config: AnalyzerConfiguration, check: &SoundnessCheck) -> SoundnessReport
+    where D: AbstractDomain, S: AbstractState<D> {
+    if let Err(e) = D::set_config(config.domain_config.clone()) {
         panic!("Failed configuration :{e}")
     }
+    let Module { procedures, main: ast } = module;
+    if config.auto_thresholds {
+        add_thresholds(program_thresholds(&Program::from(ast.clone())));
+    }
+
+    let concrete_prog = Program::from(ast.clone());
+    let mut prog: Program<D> = GenericAnalyzer::<_, S>::init(ast);
+    if let WideningPoints::FeedbackVertexSet = config.widening_points {
+        prog.widening_points = prog.feedback_vertex_set();
+    }
+    let mut analyses = procedure_analyses::<D, S>(&procedures, &config);
+    let result = analyze_program(&prog, &mut analyses, S::top(), config.iteration_strategy, config.iteration_order);
+    check_soundness(&concrete_prog, &procedures, &result, check)
+}
+
+// Control flow graphs of the procedures, prepared like the one of the main program
+fn procedure_analyses<D, S>(procedures: &[Procedure<LitInterval>], config: &AnalyzerConfiguration) -> Vec<ProcedureAnalysis<D, S>>
+    where D: AbstractDomain, S: AbstractState<D> {
+    procedures.iter().map(|p| {
+        if config.auto_thresholds {
+            add_thresholds(program_thresholds(&Program::from(p.body.clone())));
+        }
+        let mut analysis = ProcedureAnalysis::new(p.clone());
+        if let WideningPoints::FeedbackVertexSet = config.widening_points {
+            analysis.prog.widening_points = analysis.prog.feedback_vertex_set();
+        }
+        analysis
+    }).collect()
+}
+
+// Results of the analysis, independent of the domain
+struct AnalysisOutput {
+    prog: Box<dyn ProgramInterface>,
+    result: HashMap<Label, Box<dyn Display>>,
+    // (postcondition, necessary precondition at the entry)
+    precondition: Option<(Box<dyn Display>, Box<dyn Display>)>,
+    // One line for every procedure
+    summaries: Vec<String>,
+    // Of the procedures and of the main program
+    assertions: Vec<AssertionResult>,
+    alarms: Vec<Alarm>,
+    dead_code: DeadCode,
+    // Only with --format json
+    json: Option<Value>,
+    // Only with --dot
+    dot: Option<String>,
+}
 
-    let prog: Program<D> = GenericAnalyzer::<_, HashMapState<_>>::init(ast);
+fn analyze<D, S>(module: Module<LitInterval>, config: AnalyzerConfiguration) -> AnalysisOutput
+    where D: AbstractDomain + 'static, S: AbstractState<D> + FromStr<Err: Display> + 'static {
+    if let Err(e) = D::set_config(config.domain_config.clone()) {
+        panic!("Failed configuration :{e}")
+    }
+    let Module { procedures, main: ast } = module;
+    if config.auto_thresholds {
+        add_thresholds(program_thresholds(&Program::from(ast.clone())));
+    }
+
+    // The variables of the initial state are the only ones initialized at the entry
+    let initialized = config.init_state.iter()
+        .flat_map(|s| s.split(';').filter_map(|pair| pair.split_once(':')).map(|(x, _)| arrays::array_of(x).unwrap_or(x).to_string()))
+        .collect::<BTreeSet<_>>();
+
+    let concrete_prog = Program::from(ast.clone());
+    let mut prog: Program<D> = GenericAnalyzer::<_, S>::init(ast);
+    // The loop heads are still the labels shown as loop invariants
     let prog_int= Box::new(prog.clone()) as Box<dyn ProgramInterface>;
-    let result = to_boxed_state(GenericAnalyzer::analyze(
-        prog,
-        config.init_state
-            .map(|s|s.parse().unwrap())
-            .unwrap_or(HashMapState::top()),
-        config.iteration_strategy
-    ));
-    (prog_int, result)
+    if let WideningPoints::FeedbackVertexSet = config.widening_points {
+        prog.widening_points = prog.feedback_vertex_set();
+    }
+    let init_state = config.init_state.as_ref().map_or(S::top(), |s| parse_state_arg::<S>(s, "--state <state>"));
+    let post_state = config.post_state.as_ref().map(|s| parse_state_arg::<S>(s, "--post <post>"));
+    let mut procedures = procedure_analyses::<D, S>(&procedures, &config);
+    let result = analyze_program(
+        &prog,
+        &mut procedures,
+        init_state,
+        config.iteration_strategy,
+        config.iteration_order
+    );
+    // The calls return the outputs of the summaries also in the checks below
+    let prog = with_summaries(&prog, &procedures);
+    let precondition = post_state.map(|post| {
+        let backward = GenericAnalyzer::backward_analyze(&prog, &result, post.clone(), config.iteration_strategy);
+        (post, backward[&0].clone())
+    });
+    let json = (config.format == OutputFormat::Json).then(|| {
+        let mut json = analysis_to_json(&concrete_prog, &prog, prog_int.get_loop_label(), &result);
+        if let Some((post, pre)) = &precondition {
+            json["precondition"] = json!({"postcondition": post.to_json(), "precondition": pre.to_json()});
+        }
+        json["procedures"] = procedures.iter()
+            .map(|p| json!({"name": p.name, "params": p.params, "result": p.result, "input": p.input.to_json(), "output": p.output.to_json()}))
+            .collect();
+        json
+    });
+    let dot = config.dot.is_some().then(|| {
+        let annotations = result.iter()
+            .map(|(l, s)| (*l, LabelAnnotation { state: s.to_string(), bottom: *s == S::bottom() }))
+            .collect();
+        let mut graph = concrete_prog.clone();
+        graph.widening_points = prog.widening_points.clone();
+        program_to_dot(&graph, &annotations)
+    });
+    let precondition = precondition.map(|(post, pre)| (Box::new(post) as Box<dyn Display>, Box::new(pre) as Box<dyn Display>));
+    let summaries = procedures.iter()
+        .map(|p| match p.input == S::bottom() {
+            true => format!("{}({}) returns {}: never called", p.name, p.params.join(", "), p.result),
+            false => format!("{}({}) returns {}: {} -> {}", p.name, p.params.join(", "), p.result, p.input, p.output),
+        })
+        .collect();
+    // The procedures come before the main program in the source, so the lists stay sorted by position
+    let mut assertions = vec![];
+    let mut alarms = vec![];
+    for p in &procedures {
+        let prog = with_summaries(&p.prog, &procedures);
+        assertions.extend(check_assertions(&prog, &p.states));
+        alarms.extend(collect_alarms(&prog, &p.states, &p.params.iter().cloned().collect()));
+    }
+    assertions.extend(check_assertions(&prog, &result));
+    alarms.extend(collect_alarms(&prog, &result, &initialized));
+    let dead_code = find_dead_code(&prog, &result);
+    AnalysisOutput { prog: prog_int, result: to_boxed_state(result), precondition, summaries, assertions, alarms, dead_code, json, dot }
 }
\ No newline at end of file
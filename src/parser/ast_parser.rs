@@ -1,10 +1,102 @@
+use std::collections::HashMap;
+
 use crate::types::ast::{self, NumLiteral, PostOp, PreOp};
 use crate::types::cst;
+use crate::types::errors::ParserError;
+
+
 
+pub fn abstract_parse<N: NumLiteral>(cst: &cst::Module<N>) -> ast::Module<N> {
+    ast::Module {
+        procedures: cst.procedures.iter().map(parse_procedure).collect(),
+        main: parse_statements(&cst.main),
+    }
+}
 
+fn parse_procedure<N: NumLiteral>(cst: &cst::Procedure<N>) -> ast::Procedure<N> {
+    ast::Procedure {
+        name: cst.name.clone(),
+        params: cst.params.clone(),
+        result: cst.result.clone(),
+        body: parse_statements(&cst.body),
+        span: cst.span,
+    }
+}
 
-pub fn abstract_parse<N: NumLiteral>(cst: &cst::Statements<N>) -> ast::Statement<N> {
-    parse_statements(cst)
+// Every procedure must be declared once and called with as many arguments as its parameters
+pub fn check_procedures<N: NumLiteral>(module: &ast::Module<N>) -> Vec<ParserError<N>> {
+    let mut arity = HashMap::new();
+    let mut errors = vec![];
+    for p in &module.procedures {
+        if arity.insert(p.name.clone(), p.params.len()).is_some() {
+            errors.push(ParserError::DuplicateProcedure { pos: p.span.start, name: p.name.clone() });
+        }
+    }
+    for stm in module.procedures.iter().map(|p| &p.body).chain([&module.main]) {
+        check_statement(stm, &arity, &mut errors);
+    }
+    errors
+}
+
+fn check_statement<N>(stm: &ast::Statement<N>, arity: &HashMap<ast::Var, usize>, errors: &mut Vec<ParserError<N>>) {
+    match stm {
+        ast::Statement::Assign(_, a, _) | ast::Statement::NewArray(_, a, _) => check_aexpr(a, arity, errors),
+        ast::Statement::ArrayAssign(_, i, a, _) => {
+            check_aexpr(i, arity, errors);
+            check_aexpr(a, arity, errors);
+        },
+        ast::Statement::Skip(_) => (),
+        ast::Statement::Compose(s1, s2) => {
+            check_statement(s1, arity, errors);
+            check_statement(s2, arity, errors);
+        },
+        ast::Statement::IfThenElse(b, s1, s2, _) => {
+            check_bexpr(b, arity, errors);
+            check_statement(s1, arity, errors);
+            check_statement(s2, arity, errors);
+        },
+        ast::Statement::While(b, s, _) => {
+            check_bexpr(b, arity, errors);
+            check_statement(s, arity, errors);
+        },
+        ast::Statement::Assert(b, _) | ast::Statement::Assume(b, _) => check_bexpr(b, arity, errors),
+    }
+}
+
+fn check_bexpr<N>(b: &ast::Bexpr<N>, arity: &HashMap<ast::Var, usize>, errors: &mut Vec<ParserError<N>>) {
+    match b {
+        ast::Bexpr::True | ast::Bexpr::False => (),
+        ast::Bexpr::Equal(a1, a2, _) | ast::Bexpr::LessEq(a1, a2, _) |
+        ast::Bexpr::Less(a1, a2, _) | ast::Bexpr::NotEqual(a1, a2, _) => {
+            check_aexpr(a1, arity, errors);
+            check_aexpr(a2, arity, errors);
+        },
+        ast::Bexpr::Not(b) => check_bexpr(b, arity, errors),
+        ast::Bexpr::And(b1, b2) | ast::Bexpr::Or(b1, b2) => {
+            check_bexpr(b1, arity, errors);
+            check_bexpr(b2, arity, errors);
+        },
+    }
+}
+
+fn check_aexpr<N>(a: &ast::Aexpr<N>, arity: &HashMap<ast::Var, usize>, errors: &mut Vec<ParserError<N>>) {
+    match a {
+        ast::Aexpr::Lit(_) | ast::Aexpr::Var(..) | ast::Aexpr::PreOp(..) | ast::Aexpr::PostOp(..) | ast::Aexpr::Len(..) => (),
+        ast::Aexpr::Index(_, i, _) => check_aexpr(i, arity, errors),
+        ast::Aexpr::BinOp(_, a1, a2, _) => {
+            check_aexpr(a1, arity, errors);
+            check_aexpr(a2, arity, errors);
+        },
+        ast::Aexpr::Call(p, args, span) => {
+            match arity.get(p) {
+                None => errors.push(ParserError::UndefinedProcedure { pos: span.start, name: p.clone() }),
+                Some(n) if *n != args.len() =>
+                    errors.push(ParserError::ArgumentCount { pos: span.start, name: p.clone(), expected: *n, found: args.len() }),
+                Some(_) => (),
+            }
+            args.iter().for_each(|a| check_aexpr(a, arity, errors));
+        },
+    }
 }
 
 fn parse_statements<N: NumLiteral>(cst: &cst::Statements<N>) -> ast::Statement<N> {
@@ -20,26 +112,32 @@ fn parse_statements<N: NumLiteral>(cst: &cst::Statements<N>) -> ast::Statement<N
 
 fn parse_statement<N: NumLiteral>(cst: &cst::Statement<N>) -> ast::Statement<N> {
     match cst{
-        cst::Statement::<N>::Skip => 
-            ast::Statement::Skip,
-        cst::Statement::IfThenElse(b, s1, s2) => 
+        cst::Statement::<N>::Skip(span) => 
+            ast::Statement::Skip(*span),
+        cst::Statement::IfThenElse(b, s1, s2, span) => 
             ast::Statement::IfThenElse(
                 Box::new(parse_bexpr(b)),
                 Box::new(parse_statement(s1)),
-                Box::new(parse_statement(s2))
+                Box::new(parse_statement(s2)),
+                *span
             ),
-        cst::Statement::While(b, s) =>
+        cst::Statement::While(b, s, span) =>
             ast::Statement::While(
                 Box::new(parse_bexpr(b)),
-                Box::new(parse_statement(s))
+                Box::new(parse_statement(s)),
+                *span
             ),
         cst::Statement::Block(stms) =>
             parse_statements(stms),
             
         cst::Statement::AssignStm(ass_stm) => 
             parse_assign_statement(ass_stm),
+        cst::Statement::Assert(b, span) =>
+            ast::Statement::Assert(Box::new(parse_bexpr(b)), *span),
+        cst::Statement::Assume(b, span) =>
+            ast::Statement::Assume(Box::new(parse_bexpr(b)), *span),
         
-        cst::Statement::RepeatUntil(s, b) => {
+        cst::Statement::RepeatUntil(s, b, span) => {
             //repeat S until b <=> S;while(!b) do S
             let stm = parse_statement(s);
             ast::Statement::Compose(
@@ -48,24 +146,27 @@ fn parse_statement<N: NumLiteral>(cst: &cst::Statement<N>) -> ast::Statement<N>
                     Box::new(ast::Bexpr::Not(
                         Box::new(parse_bexpr(b))
                     )),
-                    Box::new(stm)
+                    Box::new(stm),
+                    *span
                 ))
             )
             
         }
-        cst::Statement::ForLoop(x, a1, b,upd_stm, s )=>{
+        cst::Statement::ForLoop(x, a1, b,upd_stm, s, span)=>{
             //for(x:=a1; b; y:= a2) stm => x:=a1; while(b){stm; y:=a2;}
             ast::Statement::Compose(
                 Box::new(ast::Statement::Assign(
                     x.clone(),
-                    Box::new(parse_aexpr(a1))
+                    Box::new(parse_aexpr(a1)),
+                    *span
                 )),
                 Box::new(ast::Statement::While(
                     Box::new(parse_bexpr(b)), 
                     Box::new(ast::Statement::Compose(
                         Box::new(parse_statement(s)),
                         Box::new(parse_assign_statement(upd_stm))
-                    ))
+                    )),
+                    *span
                 ))
             )
         }
@@ -75,73 +176,88 @@ fn parse_statement<N: NumLiteral>(cst: &cst::Statement<N>) -> ast::Statement<N>
 
 fn parse_assign_statement<N: NumLiteral>(cst: &cst::AssignStatements<N>) -> ast::Statement<N> {
     match cst {
-        cst::AssignStatements::Assign(x, a) => 
-            ast::Statement::Assign(x.clone(), Box::new(parse_aexpr(a))),
-        cst::AssignStatements::AddAssign(x, a) => 
+        cst::AssignStatements::Assign(x, a, span) => 
+            ast::Statement::Assign(x.clone(), Box::new(parse_aexpr(a)), *span),
+        cst::AssignStatements::AddAssign(x, a, span) => 
             ast::Statement::Assign(
                 x.clone(),
                 Box::new(ast::Aexpr::BinOp(
                     ast::Operator::Add,
-                    Box::new(ast::Aexpr::Var(x.clone())), 
-                    Box::new(parse_aexpr(a))
-                ))
+                    Box::new(ast::Aexpr::Var(x.clone(), *span)), 
+                    Box::new(parse_aexpr(a)),
+                    *span
+                )),
+                *span
             ),
-        cst::AssignStatements::SubAssign(x, a) => 
+        cst::AssignStatements::SubAssign(x, a, span) => 
             ast::Statement::Assign(
                 x.clone(),
                 Box::new(ast::Aexpr::BinOp(
                     ast::Operator::Sub,
-                    Box::new(ast::Aexpr::Var(x.clone())), 
-                    Box::new(parse_aexpr(a))
-                ))
+                    Box::new(ast::Aexpr::Var(x.clone(), *span)), 
+                    Box::new(parse_aexpr(a)),
+                    *span
+                )),
+                *span
             ),
-        cst::AssignStatements::MulAssign(x, a) => 
+        cst::AssignStatements::MulAssign(x, a, span) => 
             ast::Statement::Assign(
                 x.clone(),
                 Box::new(ast::Aexpr::BinOp(
                     ast::Operator::Mul,
-                    Box::new(ast::Aexpr::Var(x.clone())), 
-                    Box::new(parse_aexpr(a))
-                ))
+                    Box::new(ast::Aexpr::Var(x.clone(), *span)), 
+                    Box::new(parse_aexpr(a)),
+                    *span
+                )),
+                *span
             ),
+        cst::AssignStatements::ArrayAssign(x, i, a, span) =>
+            ast::Statement::ArrayAssign(x.clone(), Box::new(parse_aexpr(i)), Box::new(parse_aexpr(a)), *span),
+        cst::AssignStatements::NewArray(x, n, span) =>
+            ast::Statement::NewArray(x.clone(), Box::new(parse_aexpr(n)), *span),
     }
 }
-fn parse_aexpr<N: NumLiteral>(cst: &cst::Aexpr<N>) -> ast::Aexpr<N> {
+pub fn parse_aexpr<N: NumLiteral>(cst: &cst::Aexpr<N>) -> ast::Aexpr<N> {
     match cst {
-        cst::Aexpr::Add(a, t) => 
+        cst::Aexpr::Add(a, t, span) => 
             ast::Aexpr::BinOp(
                 ast::Operator::Add,
                 Box::new(parse_aexpr(a)),
-                Box::new(parse_term(t))
+                Box::new(parse_term(t)),
+                *span
             ),
-        cst::Aexpr::Sub(a, t) => 
+        cst::Aexpr::Sub(a, t, span) => 
             ast::Aexpr::BinOp(
                 ast::Operator::Sub,
                 Box::new(parse_aexpr(a)),
-                Box::new(parse_term(t))
+                Box::new(parse_term(t)),
+                *span
             ),
         cst::Aexpr::Term(t) => parse_term(t),
-        cst::Aexpr::Opposite(f) => ast::Aexpr::BinOp(
+        cst::Aexpr::Opposite(f, span) => ast::Aexpr::BinOp(
             ast::Operator::Sub,
             Box::new(ast::Aexpr::Lit(0.into())),
-            Box::new(parse_factor(&f))
+            Box::new(parse_factor(f)),
+            *span
         ),
         
     }
 }
 fn parse_term<N: NumLiteral>(cst: &cst::Term<N>) -> ast::Aexpr<N> {
     match cst {
-        cst::Term::Mul(t, f) => 
+        cst::Term::Mul(t, f, span) => 
             ast::Aexpr::BinOp(
                 ast::Operator::Mul,
                 Box::new(parse_term(t)),
-                Box::new(parse_factor(f))
+                Box::new(parse_factor(f)),
+                *span
             ),
-        cst::Term::Div(t, f) => 
+        cst::Term::Div(t, f, span) => 
             ast::Aexpr::BinOp(
                 ast::Operator::Div,
                 Box::new(parse_term(t)),
-                Box::new(parse_factor(f))
+                Box::new(parse_factor(f)),
+                *span
             ),
         cst::Term::Factor(f) => parse_factor(f),
     }
@@ -149,18 +265,21 @@ fn parse_term<N: NumLiteral>(cst: &cst::Term<N>) -> ast::Aexpr<N> {
 fn parse_factor<N: NumLiteral>(cst: &cst::Factor<N>) -> ast::Aexpr<N> {
     match cst {
         cst::Factor::Lit(n) => ast::Aexpr::Lit((*n).into()),
-        cst::Factor::Var(x) => ast::Aexpr::Var(x.clone()),
-        cst::Factor::PreInc(x) => ast::Aexpr::PreOp(PreOp::Inc, x.clone()),
-        cst::Factor::PostInc(x) => ast::Aexpr::PostOp(PostOp::Inc, x.clone()),
-        cst::Factor::PreDec(x) => ast::Aexpr::PreOp(PreOp::Dec,x.clone()),
-        cst::Factor::PostDec(x) => ast::Aexpr::PostOp(PreOp::Dec, x.clone()),
+        cst::Factor::Var(x, span) => ast::Aexpr::Var(x.clone(), *span),
+        cst::Factor::PreInc(x, span) => ast::Aexpr::PreOp(PreOp::Inc, x.clone(), *span),
+        cst::Factor::PostInc(x, span) => ast::Aexpr::PostOp(PostOp::Inc, x.clone(), *span),
+        cst::Factor::PreDec(x, span) => ast::Aexpr::PreOp(PreOp::Dec,x.clone(), *span),
+        cst::Factor::PostDec(x, span) => ast::Aexpr::PostOp(PreOp::Dec, x.clone(), *span),
         cst::Factor::Aexpr(a) => parse_aexpr(a),
+        cst::Factor::Call(p, args, span) => ast::Aexpr::Call(p.clone(), args.iter().map(parse_aexpr).collect(), *span),
+        cst::Factor::Index(x, i, span) => ast::Aexpr::Index(x.clone(), Box::new(parse_aexpr(i)), *span),
+        cst::Factor::Len(x, span) => ast::Aexpr::Len(x.clone(), *span),
     }
 }
 
 
 
-fn parse_bexpr<N: NumLiteral>(cst: &cst::Bexpr<N>) -> ast::Bexpr<N> {
+pub fn parse_bexpr<N: NumLiteral>(cst: &cst::Bexpr<N>) -> ast::Bexpr<N> {
     match cst {
         cst::Bexpr::And(b, ba) =>
             ast::Bexpr::And(
@@ -168,12 +287,10 @@ fn parse_bexpr<N: NumLiteral>(cst: &cst::Bexpr<N>) -> ast::Bexpr<N> {
                 Box::new(parse_bexpr_atomic(ba))
             ),
         cst::Bexpr::Or(b, ba) =>
-            ast::Bexpr::Not(
-                Box::new(ast::Bexpr::And(
-                    Box::new(ast::Bexpr::Not(Box::new(parse_bexpr(b)))),
-                    Box::new(ast::Bexpr::Not(Box::new(parse_bexpr_atomic(ba))))
-                ))
-            ),            
+            ast::Bexpr::Or(
+                Box::new(parse_bexpr(b)),
+                Box::new(parse_bexpr_atomic(ba))
+            ),
         cst::Bexpr::Atomic(ba) =>
             parse_bexpr_atomic(ba),
     }
@@ -182,50 +299,111 @@ fn parse_bexpr_atomic<N: NumLiteral>(cst: &cst::BexprAtomic<N>) -> ast::Bexpr<N>
     match cst {
         cst::BexprAtomic::<N>::True => ast::Bexpr::<N>::True,
         cst::BexprAtomic::<N>::False => ast::Bexpr::False,
-        cst::BexprAtomic::Equal(a1, a2) => 
+        cst::BexprAtomic::Equal(a1, a2, span) => 
             ast::Bexpr::Equal(
                 Box::new(parse_aexpr(a1)),
-                Box::new(parse_aexpr(a2))
+                Box::new(parse_aexpr(a2)),
+                *span
             ),
-        cst::BexprAtomic::LessEq(a1, a2) => 
+        cst::BexprAtomic::LessEq(a1, a2, span) => 
             ast::Bexpr::LessEq(
                 Box::new(parse_aexpr(a1)),
-                Box::new(parse_aexpr(a2))
+                Box::new(parse_aexpr(a2)),
+                *span
             ),
         cst::BexprAtomic::Not(ba) => 
             ast::Bexpr::Not(Box::new(parse_bexpr_atomic(ba))),
         cst::BexprAtomic::Bexpr(b) => parse_bexpr(b),
+        cst::BexprAtomic::NotEqual(a1, a2, span) => 
+            ast::Bexpr::NotEqual(
+                Box::new(parse_aexpr(a1)),
+                Box::new(parse_aexpr(a2)),
+                *span
+            ),
+        cst::BexprAtomic::Less(a1, a2, span) => 
+            ast::Bexpr::Less(
+                Box::new(parse_aexpr(a1)),
+                Box::new(parse_aexpr(a2)),
+                *span
+            ),
 
         //Desugar
-        cst::BexprAtomic::NotEqual(a1, a2) => {
-            ast::Bexpr::Not(
-                Box::new(ast::Bexpr::Equal(
-                    Box::new(parse_aexpr(a1)),
-                    Box::new(parse_aexpr(a2))
-                ))
-            )
-        }
-        cst::BexprAtomic::Less(a1, a2) => 
-            // a1<a2 <=> not(a2 <= a1)
-            ast::Bexpr::Not(
-                Box::new(ast::Bexpr::LessEq(
-                    Box::new(parse_aexpr(a2)),
-                    Box::new(parse_aexpr(a1)),
-                ))
-            ),
-        cst::BexprAtomic::GreaterEq(a1, a2) => 
+        cst::BexprAtomic::GreaterEq(a1, a2, span) => 
             // a1>=a2 <=> a2<=a1
             ast::Bexpr::LessEq(
                 Box::new(parse_aexpr(a2)),
                 Box::new(parse_aexpr(a1)),
+                *span
             ),
-        cst::BexprAtomic::Greater(a1, a2) => 
-            // a1>a2 <=> not(a1<=a2)
+        cst::BexprAtomic::Greater(a1, a2, span) => 
+            // a1>a2 <=> a2<a1
+            ast::Bexpr::Less(
+                Box::new(parse_aexpr(a2)),
+                Box::new(parse_aexpr(a1)),
+                *span
+            ),
+    }
+}
+
+// Rewriting of Or, Less and NotEqual with Not, And and LessEq, the core language of the
+// previous versions (shown with --desugar)
+pub fn desugar<N: Clone>(module: ast::Module<N>) -> ast::Module<N> {
+    ast::Module {
+        procedures: module.procedures.into_iter()
+            .map(|p| ast::Procedure { body: desugar_statement(p.body), ..p })
+            .collect(),
+        main: desugar_statement(module.main),
+    }
+}
+
+fn desugar_statement<N: Clone>(stm: ast::Statement<N>) -> ast::Statement<N> {
+    match stm {
+        ast::Statement::Compose(s1, s2) =>
+            ast::Statement::Compose(Box::new(desugar_statement(*s1)), Box::new(desugar_statement(*s2))),
+        ast::Statement::IfThenElse(b, s1, s2, span) => ast::Statement::IfThenElse(
+            Box::new(desugar_bexpr(*b)),
+            Box::new(desugar_statement(*s1)),
+            Box::new(desugar_statement(*s2)),
+            span
+        ),
+        ast::Statement::While(b, s, span) =>
+            ast::Statement::While(Box::new(desugar_bexpr(*b)), Box::new(desugar_statement(*s)), span),
+        ast::Statement::Assert(b, span) => ast::Statement::Assert(Box::new(desugar_bexpr(*b)), span),
+        ast::Statement::Assume(b, span) => ast::Statement::Assume(Box::new(desugar_bexpr(*b)), span),
+        ast::Statement::Assign(..) | ast::Statement::Skip(_) |
+        ast::Statement::ArrayAssign(..) | ast::Statement::NewArray(..) => stm,
+    }
+}
+
+fn desugar_bexpr<N: Clone>(b: ast::Bexpr<N>) -> ast::Bexpr<N> {
+    match b {
+        ast::Bexpr::True | ast::Bexpr::False | ast::Bexpr::Equal(..) | ast::Bexpr::LessEq(..) => b,
+        ast::Bexpr::Not(b) => ast::Bexpr::Not(Box::new(desugar_bexpr(*b))),
+        ast::Bexpr::And(b1, b2) => ast::Bexpr::And(Box::new(desugar_bexpr(*b1)), Box::new(desugar_bexpr(*b2))),
+        // b1 or b2 <=> not(not b1 and not b2)
+        ast::Bexpr::Or(b1, b2) =>
             ast::Bexpr::Not(
-                Box::new(ast::Bexpr::LessEq(
-                    Box::new(parse_aexpr(a1)),
-                    Box::new(parse_aexpr(a2))
-                )),
+                Box::new(ast::Bexpr::And(
+                    Box::new(ast::Bexpr::Not(Box::new(desugar_bexpr(*b1)))),
+                    Box::new(ast::Bexpr::Not(Box::new(desugar_bexpr(*b2))))
+                ))
             ),
+        // a1<a2 <=> not(a2 <= a1)
+        ast::Bexpr::Less(a1, a2, span) => ast::Bexpr::Not(Box::new(ast::Bexpr::LessEq(a2, a1, span))),
+        // a1!=a2 <=> not(a1 == a2)
+        ast::Bexpr::NotEqual(a1, a2, span) => ast::Bexpr::Not(Box::new(ast::Bexpr::Equal(a1, a2, span))),
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::{parser::parse_string, types::ast::Num};
+
+    use super::*;
+
+    #[test]
+    fn or_less_and_not_equal_are_kept() {
+        let module = parse_string::<Num>("assert x < 3 or y != 0;\nassert x > 5;".to_string()).unwrap();
+        assert_eq!(module.main.to_string(), "assert ((x < 3) or (y != 0));\nassert (5 < x);");
+        assert_eq!(desugar(module).main.to_string(), "assert (not ((not (not (3 <= x))) and (not (not (y == 0)))));\nassert (not (x <= 5));");
+    }
+}
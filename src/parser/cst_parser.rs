@@ -1,31 +1,128 @@
 use std::marker::PhantomData;
 
-use crate::types::{ast::NumLiteral, cst::{Aexpr, AssignStatements, Bexpr, BexprAtomic, Factor, Statement, Statements, Term}, errors::ParserError, tokens::Token};
-use super::lexer::Lexer;
+use crate::types::{ast::NumLiteral, cst::{Aexpr, AssignStatements, Bexpr, BexprAtomic, Factor, Module, Procedure, Statement, Statements, Term, Var}, errors::ParserError, span::Span, tokens::Token};
+use super::lexer::{Lexer, TokenPosition};
 
 
 
 pub struct ConcreteParser<N, L: Lexer<N>>{
     lexer: L,
-    num_lit: PhantomData<N>
+    num_lit: PhantomData<N>,
+    errors: Vec<ParserError<N>>,
+    // after an error, until a statement is parsed correctly
+    recovering: bool,
 }
 
 impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
-    pub fn new(lexer: L) -> Self { Self { lexer, num_lit: Default::default() } }
+    pub fn new(lexer: L) -> Self { Self { lexer, num_lit: Default::default(), errors: Vec::new(), recovering: false } }
 
+    // Span from start to the end of the last matched token
+    fn span_from(&self, start: TokenPosition) -> Span {
+        Span::new(start, self.lexer.last_end())
+    }
+
+
+    // Parse the whole program, reporting all the errors (lexical and syntactic) sorted by position
+    pub fn parse(mut self)->Result<Module<N>,Vec<ParserError<N>>>{
+        let mut procedures = vec![];
+        while let Some(Token::Proc) = self.lexer.peek() {
+            procedures.push(self.parse_procedure());
+        }
+        let mut ast = self.parse_statements();
+        // Unbalanced '}' at the top level: report each of them once and go on with the following statements
+        while self.lexer.peek().is_some() {
+            self.record_error(self.lexer.unexpected_error(vec![]));
+            self.skip_token();
+            if !matches!(self.lexer.peek(), None | Some(Token::CurlyClose)) {
+                let stms = self.parse_statements();
+                ast = append_statements(ast, stms);
+            }
+        }
 
-    pub fn parse(mut self)->Result<Statements<N>,ParserError<N>>{
-        let ast = self.parse_statements()?;
-        if let Some(_) = self.lexer.peek(){
-            Err(self.lexer.unexpected_error())
-        }else{
-            Ok(ast)
+        let mut errors = self.lexer.take_errors();
+        errors.append(&mut self.errors);
+        if errors.is_empty() {
+            Ok(Module { procedures, main: ast })
+        } else {
+            errors.sort_by_key(|e| e.pos().unwrap_or((usize::MAX, usize::MAX)));
+            Err(errors)
         }
     }
     
     
-    fn parse_statements(&mut self) -> Result<Statements<N>,ParserError<N>> {
-        let s = self.parse_statement()?;
+    // A wrong header is reported and skipped up to the body
+    fn parse_procedure(&mut self) -> Procedure<N> {
+        let start = self.lexer.next_start();
+        let (name, params, result) = match self.parse_procedure_header() {
+            Ok(header) => header,
+            Err(e) => {
+                self.record_error(e);
+                while !matches!(self.lexer.peek(), None | Some(Token::CurlyOpen)) {
+                    self.skip_token();
+                }
+                Default::default()
+            }
+        };
+        let span = self.span_from(start);
+        if let Err(e) = self.lexer.match_next(Token::CurlyOpen) {
+            self.record_error(e);
+        }
+        let body = self.parse_statements();
+        if let Err(e) = self.lexer.match_next(Token::CurlyClose) {
+            self.record_error(e);
+        }
+        Procedure { name, params, result, body, span }
+    }
+
+    fn parse_procedure_header(&mut self) -> Result<(Var, Vec<Var>, Var), ParserError<N>> {
+        self.lexer.match_next(Token::Proc)?;
+        let name = self.parse_id()?;
+        self.lexer.match_next(Token::BracketOpen)?;
+        let mut params = vec![];
+        if let Some(Token::Id(_)) = self.lexer.peek() {
+            params.push(self.parse_id()?);
+            while let Some(Token::Comma) = self.lexer.peek() {
+                self.lexer.match_next(Token::Comma)?;
+                params.push(self.parse_id()?);
+            }
+        }
+        self.lexer.match_next(Token::BracketClose)?;
+        self.lexer.match_next(Token::Returns)?;
+        let result = self.parse_id()?;
+        Ok((name, params, result))
+    }
+
+
+    // Parse a single expression, used to read expressions outside of a program
+    pub fn parse_single_aexpr(mut self) -> Result<Aexpr<N>,Vec<ParserError<N>>> {
+        let a = self.parse_aexpr();
+        self.finish(a)
+    }
+    pub fn parse_single_bexpr(mut self) -> Result<Bexpr<N>,Vec<ParserError<N>>> {
+        let b = self.parse_bexpr();
+        self.finish(b)
+    }
+    fn finish<T>(mut self, res: Result<T,ParserError<N>>) -> Result<T,Vec<ParserError<N>>> {
+        let res = match res {
+            Ok(_) if self.lexer.peek().is_some() => Err(self.lexer.unexpected_error(vec![])),
+            res => res,
+        };
+        let mut errors = self.lexer.take_errors();
+        match res {
+            Ok(t) if errors.is_empty() => Ok(t),
+            Ok(_) => Err(errors),
+            Err(e) => {
+                errors.push(e);
+                Err(errors)
+            },
+        }
+    }
+    
+    
+    // A wrong statement is reported and skipped (replaced by a skip) resynchronising
+    // the parser on the next ';', '}' or statement keyword (panic-mode recovery)
+    fn parse_statements(&mut self) -> Statements<N> {
+        let s = self.parse_statement_or_recover();
         let mut stms = Statements::<N>::Singleton(Box::new(s));
 
         loop {
@@ -33,22 +130,70 @@ impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
                 None => break,
                 Some(Token::CurlyClose) => break,
                 Some(_) => {
-                    let s = self.parse_statement()?;
+                    let s = self.parse_statement_or_recover();
                     stms = Statements::<N>::Composition(Box::new(stms), Box::new(s));
                 }
             }
         }
-        Ok(stms)
+        stms
+    }
+
+    fn parse_statement_or_recover(&mut self) -> Statement<N> {
+        let start = self.lexer.next_start();
+        let lexer_errors = self.lexer.errors_num();
+        match self.parse_statement() {
+            Ok(s) => {
+                self.recovering = false;
+                s
+            },
+            Err(e) => {
+                // While recovering, an error on the first token is most likely caused by the previous one,
+                // and a wrong symbol in the statement is already reported by the lexer
+                let after_lexer_error = self.lexer.errors_num() > lexer_errors;
+                if !(self.recovering && e.pos() == Some(start) || after_lexer_error) {
+                    self.record_error(e);
+                }
+                self.recovering = true;
+                self.synchronize();
+                Statement::Skip(self.span_from(start))
+            }
+        }
+    }
+
+    fn synchronize(&mut self) {
+        while let Some(tok) = self.lexer.peek() {
+            match tok {
+                Token::Semicolon => {
+                    self.skip_token();
+                    break
+                },
+                Token::CurlyClose | Token::Skip | Token::If | Token::While | Token::Repeat | Token::For |
+                Token::Assert | Token::Assume | Token::Proc => break,
+                _ => self.skip_token(),
+            }
+        }
+    }
+
+    fn skip_token(&mut self) {
+        if let Some(tok) = self.lexer.peek() {
+            // Cannot fail since it is the next token
+            let _ = self.lexer.match_next(tok);
+        }
+    }
+
+    fn record_error(&mut self, e: ParserError<N>) {
+        self.errors.push(e)
     }
 
 
 
     fn parse_statement(&mut self) -> Result<Statement<N>,ParserError<N>> {
+        let start = self.lexer.next_start();
         match self.lexer.peek() {
             Some(Token::Skip) => {
                 self.lexer.match_next(Token::Skip)?;
                 self.lexer.match_next(Token::Semicolon)?;
-                Ok(Statement::<N>::Skip)
+                Ok(Statement::<N>::Skip(self.span_from(start)))
             }
             Some(Token::Id(_)) => {
                 let ass_stm = self.parse_assign_statement()?;
@@ -59,26 +204,31 @@ impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
             Some(Token::If) => {
                 self.lexer.match_next(Token::If)?;
                 let b = self.parse_bexpr()?;
+                let span = self.span_from(start);
                 self.lexer.match_next(Token::Then)?;
                 let s1 = self.parse_statement()?;
                 self.lexer.match_next(Token::Else)?;
                 let s2 = self.parse_statement()?;
-                Ok(Statement::IfThenElse(Box::new(b), Box::new(s1), Box::new(s2)))
+                Ok(Statement::IfThenElse(Box::new(b), Box::new(s1), Box::new(s2), span))
             }
             Some(Token::While) => {
                 self.lexer.match_next(Token::While)?;
                 let b = self.parse_bexpr()?;
+                let span = self.span_from(start);
                 self.lexer.match_next(Token::Do)?;
                 let s = self.parse_statement()?;
-                Ok(Statement::While(Box::new(b), Box::new(s)))
+                Ok(Statement::While(Box::new(b), Box::new(s), span))
             }
             Some(Token::Repeat) => {
                 self.lexer.match_next(Token::Repeat)?;
                 let s = self.parse_statement()?;
+                // the loop header is the 'until b' part
+                let until_start = self.lexer.next_start();
                 self.lexer.match_next(Token::Until)?;
                 let b = self.parse_bexpr()?;
+                let span = self.span_from(until_start);
                 self.lexer.match_next(Token::Semicolon)?;
-                Ok(Statement::RepeatUntil(Box::new(s), Box::new(b)))
+                Ok(Statement::RepeatUntil(Box::new(s), Box::new(b), span))
             }
             Some(Token::For) => {
                 self.lexer.match_next(Token::For)?;
@@ -91,24 +241,54 @@ impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
                 self.lexer.match_next(Token::Semicolon)?;
                 let upd_stm = self.parse_assign_statement()?;
                 self.lexer.match_next(Token::BracketClose)?;
+                let span = self.span_from(start);
                 let s = self.parse_statement()?;
 
-                Ok(Statement::ForLoop(x, Box::new(a1), Box::new(b), Box::new(upd_stm), Box::new(s)))
+                Ok(Statement::ForLoop(x, Box::new(a1), Box::new(b), Box::new(upd_stm), Box::new(s), span))
+            }
+            Some(tok @ (Token::Assert | Token::Assume)) => {
+                self.lexer.match_next(tok.clone())?;
+                let b = self.parse_bexpr()?;
+                let span = self.span_from(start);
+                self.lexer.match_next(Token::Semicolon)?;
+                match tok {
+                    Token::Assert => Ok(Statement::Assert(Box::new(b), span)),
+                    _ => Ok(Statement::Assume(Box::new(b), span)),
+                }
             }
             Some(Token::CurlyOpen) => {
                 self.lexer.match_next(Token::CurlyOpen)?;
-                let stms = self.parse_statements()?;
+                let stms = self.parse_statements();
                 self.lexer.match_next(Token::CurlyClose)?;
                 Ok(Statement::Block(Box::new(stms)))
             },
-            _ => Err(self.lexer.unexpected_error())
+            _ => Err(self.lexer.unexpected_error(vec![
+                Token::Id(String::new()), Token::Skip, Token::If, Token::While,
+                Token::Repeat, Token::For, Token::Assert, Token::Assume, Token::CurlyOpen
+            ]))
         }
     }
     fn parse_assign_statement(&mut self) -> Result<AssignStatements<N>,ParserError<N>> {
+        let start = self.lexer.next_start();
         let x = self.parse_id()?;
         let stm_constructor = match self.lexer.peek() {
+            Some(Token::SquareOpen) => {
+                self.lexer.match_next(Token::SquareOpen)?;
+                let i = self.parse_aexpr()?;
+                self.lexer.match_next(Token::SquareClose)?;
+                self.lexer.match_next(Token::Assign)?;
+                let a = self.parse_aexpr()?;
+                return Ok(AssignStatements::ArrayAssign(x, Box::new(i), Box::new(a), self.span_from(start)))
+            },
             Some(Token::Assign) => {
                 self.lexer.match_next(Token::Assign)?;
+                if let Some(Token::Array) = self.lexer.peek() {
+                    self.lexer.match_next(Token::Array)?;
+                    self.lexer.match_next(Token::BracketOpen)?;
+                    let n = self.parse_aexpr()?;
+                    self.lexer.match_next(Token::BracketClose)?;
+                    return Ok(AssignStatements::NewArray(x, Box::new(n), self.span_from(start)))
+                }
                 AssignStatements::<N>::Assign
             },
             Some(Token::AddAssign) => {
@@ -123,27 +303,29 @@ impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
                 self.lexer.match_next(Token::MulAssign)?;
                 AssignStatements::<N>::MulAssign
             },
-            _ => return Err(self.lexer.unexpected_error()),
+            _ => return Err(self.lexer.unexpected_error(vec![
+                Token::Assign, Token::AddAssign, Token::SubAssign, Token::MulAssign, Token::SquareOpen
+            ])),
         };
         let a = self.parse_aexpr()?;
-        Ok(stm_constructor(x, Box::new(a)))
+        Ok(stm_constructor(x, Box::new(a), self.span_from(start)))
     }
 
     fn parse_id(&mut self) -> Result<String, ParserError<N>>{
         let x =  match self.lexer.peek(){
             Some(Token::Id(x)) => x,
-            _ => return Err(self.lexer.unexpected_error())
+            _ => return Err(self.lexer.unexpected_error(vec![Token::Id(String::new())]))
         };
         self.lexer.match_next(Token::Id(x.clone()))?;
         return Ok(x)
     }
     
     fn parse_aexpr(&mut self) -> Result<Aexpr<N>,ParserError<N>> {
+        let start = self.lexer.next_start();
         if let Some(Token::Minus) = self.lexer.peek() {
             self.lexer.match_next(Token::Minus)?;    
-            return Ok(Aexpr::<N>::Opposite(Box::new(
-                self.parse_factor()?
-            )));   
+            let f = self.parse_factor()?;
+            return Ok(Aexpr::<N>::Opposite(Box::new(f), self.span_from(start)));   
         }
 
         let t = self.parse_term()?;
@@ -153,12 +335,12 @@ impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
                 Some(Token::Plus) => {
                     self.lexer.match_next(Token::Plus)?;    
                     let t = self.parse_term()?;
-                    aexpr = Aexpr::<N>::Add(Box::new(aexpr), Box::new(t));
+                    aexpr = Aexpr::<N>::Add(Box::new(aexpr), Box::new(t), self.span_from(start));
                 },
                 Some(Token::Minus) => {
                     self.lexer.match_next(Token::Minus)?;    
                     let t = self.parse_term()?;
-                    aexpr = Aexpr::<N>::Sub(Box::new(aexpr), Box::new(t));
+                    aexpr = Aexpr::<N>::Sub(Box::new(aexpr), Box::new(t), self.span_from(start));
                 }
                 _ => break,
             }
@@ -166,6 +348,7 @@ impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
         return Ok(aexpr);
     }
     fn parse_term(&mut self) -> Result<Term<N>,ParserError<N>> {
+        let start = self.lexer.next_start();
         let f = self.parse_factor()?;
         let mut term = Term::<N>::Factor(Box::new(f));
         loop {
@@ -173,12 +356,12 @@ impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
                 Some(Token::Mul) => {
                     self.lexer.match_next(Token::Mul)?;     
                     let f = self.parse_factor()?;
-                    term = Term::<N>::Mul(Box::new(term), Box::new(f));
+                    term = Term::<N>::Mul(Box::new(term), Box::new(f), self.span_from(start));
                 }
                 Some(Token::Div) => {
                     self.lexer.match_next(Token::Div)?;     
                     let f = self.parse_factor()?;
-                    term = Term::<N>::Div(Box::new(term), Box::new(f));
+                    term = Term::<N>::Div(Box::new(term), Box::new(f), self.span_from(start));
                 }
                 _ => break,
             }
@@ -186,6 +369,7 @@ impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
         return Ok(term);
     }
     fn parse_factor(&mut self) -> Result<Factor<N>,ParserError<N>> {
+        let start = self.lexer.next_start();
         match self.lexer.peek() {
             Some(Token::Lit(n)) => {
                 self.lexer.match_next(Token::Lit(n))?;
@@ -196,13 +380,23 @@ impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
                 match self.lexer.peek() {
                     Some(Token::Inc) => {
                         self.lexer.match_next(Token::Inc)?;
-                        Ok(Factor::PostInc(x))
+                        Ok(Factor::PostInc(x, self.span_from(start)))
                     }
                     Some(Token::Dec) => {
                         self.lexer.match_next(Token::Dec)?;
-                        Ok(Factor::PostDec(x))
+                        Ok(Factor::PostDec(x, self.span_from(start)))
+                    }
+                    Some(Token::BracketOpen) => {
+                        let args = self.parse_arguments()?;
+                        Ok(Factor::Call(x, args, self.span_from(start)))
                     }
-                    _ => Ok(Factor::Var(x))
+                    Some(Token::SquareOpen) => {
+                        self.lexer.match_next(Token::SquareOpen)?;
+                        let i = self.parse_aexpr()?;
+                        self.lexer.match_next(Token::SquareClose)?;
+                        Ok(Factor::Index(x, Box::new(i), self.span_from(start)))
+                    }
+                    _ => Ok(Factor::Var(x, self.span_from(start)))
                 }
             },
             Some(Token::BracketOpen) => {
@@ -211,18 +405,43 @@ impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
                 self.lexer.match_next(Token::BracketClose)?;
                 Ok(Factor::Aexpr(Box::new(a)))
             }
+            Some(Token::Len) => {
+                self.lexer.match_next(Token::Len)?;
+                self.lexer.match_next(Token::BracketOpen)?;
+                let x = self.parse_id()?;
+                self.lexer.match_next(Token::BracketClose)?;
+                Ok(Factor::Len(x, self.span_from(start)))
+            }
             Some(Token::Inc) => {
                 self.lexer.match_next(Token::Inc)?;
-                Ok(Factor::PreInc(self.parse_id()?))
+                let x = self.parse_id()?;
+                Ok(Factor::PreInc(x, self.span_from(start)))
             }
             Some(Token::Dec) => {
                 self.lexer.match_next(Token::Dec)?;       
-                Ok(Factor::PreDec(self.parse_id()?))
+                let x = self.parse_id()?;
+                Ok(Factor::PreDec(x, self.span_from(start)))
             }
-            _ => Err(self.lexer.unexpected_error())
+            _ => Err(self.lexer.unexpected_error(vec![
+                Token::Id(String::new()), Token::Lit(N::from(0)), Token::BracketOpen,
+                Token::Len, Token::Inc, Token::Dec
+            ]))
         }    
     }
-    
+    // Arguments of a call, '(' [aexpr {',' aexpr}] ')'
+    fn parse_arguments(&mut self) -> Result<Vec<Aexpr<N>>,ParserError<N>> {
+        self.lexer.match_next(Token::BracketOpen)?;
+        let mut args = vec![];
+        if self.lexer.peek() != Some(Token::BracketClose) {
+            args.push(self.parse_aexpr()?);
+            while let Some(Token::Comma) = self.lexer.peek() {
+                self.lexer.match_next(Token::Comma)?;
+                args.push(self.parse_aexpr()?);
+            }
+        }
+        self.lexer.match_next(Token::BracketClose)?;
+        Ok(args)
+    }
     
     
     fn parse_bexpr(&mut self) -> Result<Bexpr<N>,ParserError<N>> {
@@ -267,42 +486,48 @@ impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
                 Ok(BexprAtomic::<N>::Bexpr(Box::new(b)))
             }
             Some(_) => {
+                let start = self.lexer.next_start();
                 let a1 = self.parse_aexpr()?;
                 match self.lexer.peek() {
                     Some(Token::Eq) => {
                         self.lexer.match_next(Token::Eq)?;
                         let a2 = self.parse_aexpr()?;
-                        Ok(BexprAtomic::<N>::Equal(Box::new(a1), Box::new(a2)))
+                        Ok(BexprAtomic::<N>::Equal(Box::new(a1), Box::new(a2), self.span_from(start)))
                     }
                     Some(Token::Neq) => {
                         self.lexer.match_next(Token::Neq)?;
                         let a2 = self.parse_aexpr()?;
-                        Ok(BexprAtomic::<N>::NotEqual(Box::new(a1), Box::new(a2)))
+                        Ok(BexprAtomic::<N>::NotEqual(Box::new(a1), Box::new(a2), self.span_from(start)))
                     }
                     Some (Token::Lte) => {
                         self.lexer.match_next(Token::Lte)?;
                         let a2 = self.parse_aexpr()?;
-                        Ok(BexprAtomic::<N>::LessEq(Box::new(a1), Box::new(a2)))
+                        Ok(BexprAtomic::<N>::LessEq(Box::new(a1), Box::new(a2), self.span_from(start)))
                     }
                     Some (Token::Lt) => {
                         self.lexer.match_next(Token::Lt)?;
                         let a2 = self.parse_aexpr()?;
-                        Ok(BexprAtomic::<N>::Less(Box::new(a1), Box::new(a2)))
+                        Ok(BexprAtomic::<N>::Less(Box::new(a1), Box::new(a2), self.span_from(start)))
                     }
                     Some (Token::Gte) => {
                         self.lexer.match_next(Token::Gte)?;
                         let a2 = self.parse_aexpr()?;
-                        Ok(BexprAtomic::<N>::GreaterEq(Box::new(a1), Box::new(a2)))
+                        Ok(BexprAtomic::<N>::GreaterEq(Box::new(a1), Box::new(a2), self.span_from(start)))
                     }
                     Some (Token::Gt) => {
                         self.lexer.match_next(Token::Gt)?;
                         let a2 = self.parse_aexpr()?;
-                        Ok(BexprAtomic::<N>::Greater(Box::new(a1), Box::new(a2)))
+                        Ok(BexprAtomic::<N>::Greater(Box::new(a1), Box::new(a2), self.span_from(start)))
                     }
-                    _ => Err(self.lexer.unexpected_error())
+                    _ => Err(self.lexer.unexpected_error(vec![
+                        Token::Eq, Token::Neq, Token::Lte, Token::Lt, Token::Gte, Token::Gt
+                    ]))
                 }
             },
-            _ => Err(self.lexer.unexpected_error())
+            None => Err(self.lexer.unexpected_error(vec![
+                Token::True, Token::False, Token::Not, Token::BracketOpen,
+                Token::Id(String::new()), Token::Lit(N::from(0))
+            ]))
         }  
     }
     
@@ -311,4 +536,36 @@ impl<N: NumLiteral, L: Lexer<N>> ConcreteParser<N, L> {
 
 
 
+}
+
+// Composition of two sequences of statements
+fn append_statements<N>(stms1: Statements<N>, stms2: Statements<N>) -> Statements<N> {
+    match stms2 {
+        Statements::Singleton(s) => Statements::Composition(Box::new(stms1), s),
+        Statements::Composition(stms, s) =>
+            Statements::Composition(Box::new(append_statements(stms1, *stms)), s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::parse_string, types::{ast::Num, errors::ParserError}};
+
+    fn error_positions(source: &str) -> Vec<Option<(usize, usize)>> {
+        let errors = parse_string::<Num>(source.to_string()).err().unwrap_or_default();
+        errors.iter().map(ParserError::pos).collect()
+    }
+
+    #[test]
+    fn reports_every_wrong_statement_once() {
+        assert_eq!(error_positions("x := 1 +;\ny := 2;\ny := * 2;\nz := 3;"), [Some((1, 9)), Some((3, 6))]);
+        assert_eq!(error_positions("x := 1;\n}\n}\ny := 2;"), [Some((2, 1)), Some((3, 1))]);
+        assert!(error_positions("x := 1; while x <= 3 do { x := x + 1; }").is_empty());
+    }
+
+    #[test]
+    fn lexical_errors_are_not_reported_again_by_the_parser() {
+        let errors = parse_string::<Num>("x := [0,10];\ny := 1 $ 2;\nz := 3;".to_string()).unwrap_err();
+        assert!(matches!(errors[..], [ParserError::InvalidLiteral { pos: (1, 6), .. }, ParserError::UnknownSymbol { pos: (2, 8), symbol: '$' }]));
+    }
 }
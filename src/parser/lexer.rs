@@ -3,6 +3,7 @@ use std::iter::Peekable;
 use std::fs::File;
 use crate::types::ast::NumLiteral;
 use crate::types::errors::ParserError;
+use crate::types::span::Span;
 use crate::types::tokens::Token;
 
 
@@ -12,14 +13,27 @@ pub type TokenPosition = (usize,usize);
 pub trait Lexer<N> { // N is the num literal type
     fn peek(&self) -> Option<Token<N>>;
     fn match_next(&mut self, tok: Token<N>)-> Result<(),ParserError<N>>;
-    fn unexpected_error(&self) -> ParserError<N>;
+    // Error on the next token, expected is the set of tokens that were valid in its place
+    fn unexpected_error(&self, expected: Vec<Token<N>>) -> ParserError<N>;
+    // Start of the next token (the end of the last one at the end of the input)
+    fn next_start(&self) -> TokenPosition;
+    // End (excluded) of the last matched token
+    fn last_end(&self) -> TokenPosition;
+    // Lexical errors found so far (the wrong symbols are skipped)
+    fn take_errors(&mut self) -> Vec<ParserError<N>>;
+    // Number of lexical errors found so far
+    fn errors_num(&self) -> usize;
 }
 
 
 
 pub struct MyLexer<'a, N> {
     chars: Peekable<Box<dyn Iterator<Item =(usize,usize,char)> + 'a>>,
-    peek: Option<(TokenPosition, Token<N>)>
+    peek: Option<(Span, Token<N>)>,
+    // end of the last scanned char and of the last matched token
+    scan_end: TokenPosition,
+    last_end: TokenPosition,
+    errors: Vec<ParserError<N>>,
 }
 
 impl<'a, N: NumLiteral> From<&'a str> for MyLexer<'a, N>{
@@ -35,10 +49,12 @@ impl<'a, N: NumLiteral> From<&'a str> for MyLexer<'a, N>{
         let mut lex =  MyLexer {
             peek: None,
             chars: b.peekable(),
+            scan_end: (1,1),
+            last_end: (1,1),
+            errors: Vec::new(),
         };
         
-        //FIXME
-        lex.peek = lex.scan().unwrap();
+        lex.peek = lex.next_token();
         return lex;
     }
 }
@@ -57,10 +73,12 @@ impl<'a, N: NumLiteral> From<File> for MyLexer<'a, N> {
         let mut lex =  MyLexer {
             peek: None,
             chars: b.peekable(),
+            scan_end: (1,1),
+            last_end: (1,1),
+            errors: Vec::new(),
         };
         
-        //FIXME
-        lex.peek = lex.scan().unwrap();
+        lex.peek = lex.next_token();
         return lex;
     }
     
@@ -68,8 +86,19 @@ impl<'a, N: NumLiteral> From<File> for MyLexer<'a, N> {
 
 
 impl<'a, N : NumLiteral> MyLexer<'a, N> {
-    fn scan(&mut self) -> Result<Option<(TokenPosition, Token<N>)>, ParserError<N>> {
-        while let Some(_) = self.chars.next_if(|(_,_,c)|c.is_ascii_whitespace()){}
+    fn next_char(&mut self) -> Option<(usize,usize,char)> {
+        self.next_char_if(|_|true)
+    }
+    fn next_char_if(&mut self, f: impl FnOnce(&(usize,usize,char)) -> bool) -> Option<(usize,usize,char)> {
+        let next = self.chars.next_if(f);
+        if let Some((l,c,_)) = next {
+            self.scan_end = (l, c+1);
+        }
+        next
+    }
+
+    fn scan(&mut self) -> Result<Option<(Span, Token<N>)>, ParserError<N>> {
+        while let Some(_) = self.next_char_if(|(_,_,c)|c.is_ascii_whitespace()){}
         
         // if let Some(_) = self.chars.next_if(|(_,_,c)|c==&'/') {
         //     match self.chars.next() {
@@ -84,35 +113,43 @@ impl<'a, N : NumLiteral> MyLexer<'a, N> {
         // }
 
         let start_pos: Option<TokenPosition> = self.chars.peek().map(|(l,c,_)|(l.to_owned(),c.to_owned()));
-        let tok = match self.chars.next() {
+        let tok = match self.next_char() {
+            // After a variable '[' opens an index, anywhere else an interval literal
+            Some((_, _, '[')) if matches!(self.peek, Some((_, Token::Id(_)))) => Token::SquareOpen,
+            Some((_, _, ']')) => Token::SquareClose,
             // FIXME horrible static pattern matching for dynamic literal
             Some((_, _, d@('0'..='9' | '['))) => {
                 let mut digits = d.to_string();
-                while let Some((_, _, d)) = self.chars.next_if(|(_, _, c)| c.is_ascii_digit() || c == &',' || c == &'-' || c == &']'){
-                    digits.push(d)
+                // Only the interval literals contain ',' and '-', so that they can separate the arguments of a call
+                let interval = d == '[';
+                while let Some((_, _, d)) = self.next_char_if(|(_, _, c)| c.is_ascii_digit() || (interval && (c == &',' || c == &'-' || c == &']'))){
+                    digits.push(d);
+                    if d == ']' {
+                        break
+                    }
                 }
                 match digits.parse() {
                     Ok(lit) => Token::Lit(lit),
-                    Err(_) => panic!("Error occurred while parsing a numeric literal")
+                    Err(_) => return Err(ParserError::InvalidLiteral { pos: start_pos.unwrap(), literal: digits })
                 }
                 // Token::Lit((digits).parse().unwrap())//By construction should be valid                
             }
             Some((_, _, c@('a'..='z' | 'A'..='Z' | '_'))) => {
                 let mut word = c.to_string();
-                while let Some((_, _, c)) = self.chars.next_if(|(_, _, c)| c.is_ascii_alphanumeric() || c == &'_'){
+                while let Some((_, _, c)) = self.next_char_if(|(_, _, c)| c.is_ascii_alphanumeric() || c == &'_'){
                     word.push(c)
                 }
                 match_keyword(&word).unwrap_or(Token::Id(word))
             }
-            Some((_, _, c@('='|'<'|'>'|'!'|'-'|'+'|'*'|'('|')'|'{'|'}'|':'|';'|'/'))) =>{
+            Some((_, _, c@('='|'<'|'>'|'!'|'-'|'+'|'*'|'('|')'|'{'|'}'|':'|';'|','|'/'))) =>{
                 let mut symbol = c.to_string();
                 let mut  last_valid_tok = match_symbol(&symbol);
 
-                while let Some((_, _, c@('='|'<'|'>'|'!'|'-'|'+'|'*'|'('|')'|'{'|'}'|':'|';'|'/'))) = self.chars.peek() {
+                while let Some((_, _, c@('='|'<'|'>'|'!'|'-'|'+'|'*'|'('|')'|'{'|'}'|':'|';'|','|'/'))) = self.chars.peek() {
                     symbol.push(c.clone());
                     match match_symbol(&symbol){
                         Some(tok) => {
-                            self.chars.next();
+                            self.next_char();
                             last_valid_tok = Some(tok);
                         },
                         None => break,
@@ -123,7 +160,7 @@ impl<'a, N : NumLiteral> MyLexer<'a, N> {
                 }
                 if symbol == "//" {
                     let (cur_line, _)= start_pos.unwrap();
-                    while let Some(_) = self.chars.next_if(|(l,_,_)|cur_line==*l){}
+                    while let Some(_) = self.next_char_if(|(l,_,_)|cur_line==*l){}
                     return self.scan()
                 }
                 match last_valid_tok  {
@@ -140,7 +177,17 @@ impl<'a, N : NumLiteral> MyLexer<'a, N> {
             }),
             None => return Ok(None),
         };
-        return Ok(Some((start_pos.unwrap(), tok)));
+        return Ok(Some((Span::new(start_pos.unwrap(), self.scan_end), tok)));
+    }
+
+    // Next valid token, the lexical errors are stored and the wrong symbols skipped
+    fn next_token(&mut self) -> Option<(Span, Token<N>)> {
+        loop {
+            match self.scan() {
+                Ok(tok) => return tok,
+                Err(e) => self.errors.push(e),
+            }
+        }
     }
 }
 
@@ -153,41 +200,56 @@ impl<'a,N: NumLiteral> Lexer<N> for MyLexer<'a,N> {
     
     fn match_next(&mut self, tok: Token<N>)-> Result<(),ParserError<N>> { 
         match &self.peek {
-            Some((pos, tok2)) => {
+            Some((span, tok2)) => {
                 if tok != *tok2 {
                     return Err(ParserError::UnexpectedToken {
-                        pos: pos.clone(),
-                        expected: Some(tok),
+                        pos: span.start,
+                        expected: vec![tok],
                         found: tok2.clone()
                     })
                 }
             }
-            None => return Err(ParserError::UnexpectedEOF),
+            None => return Err(ParserError::UnexpectedEOF { expected: vec![tok] }),
         }
         if std::env::var("print-token").is_ok_and(|s|s=="true") {
             print!("{:?} ", self.peek.as_ref().unwrap().1)
         }   
-        self.peek = self.scan()?;
+        self.last_end = self.peek.as_ref().unwrap().0.end;
+        self.peek = self.next_token();
         Ok(())
     }
-    fn unexpected_error(&self) -> ParserError<N> {
+    fn unexpected_error(&self, expected: Vec<Token<N>>) -> ParserError<N> {
         match &self.peek {
-            Some((pos, tok)) => {
+            Some((span, tok)) => {
                 return ParserError::UnexpectedToken {
-                    pos: pos.clone(),
-                    expected: None,
+                    pos: span.start,
+                    expected,
                     found: tok.clone()
                 }
             }
-            None => return ParserError::UnexpectedEOF,
+            None => return ParserError::UnexpectedEOF { expected },
         }   
     }
+
+    fn next_start(&self) -> TokenPosition {
+        self.peek.as_ref().map(|(span,_)|span.start).unwrap_or(self.last_end)
+    }
+    fn last_end(&self) -> TokenPosition {
+        self.last_end
+    }
+    fn take_errors(&mut self) -> Vec<ParserError<N>> {
+        std::mem::take(&mut self.errors)
+    }
+    fn errors_num(&self) -> usize {
+        self.errors.len()
+    }
     
 }
 
 fn match_symbol<N>(s: &str)-> Option<Token<N>>{
     match s{
         ";" => Some(Token::Semicolon),
+        "," => Some(Token::Comma),
         "(" => Some(Token::BracketOpen),
         ")" => Some(Token::BracketClose),
         "{" => Some(Token::CurlyOpen),
@@ -228,6 +290,12 @@ fn match_keyword<N>(kw: &str)->Option<Token<N>>{
         "until" => Some(Token::Until),
         "for" => Some(Token::For),
         "skip" => Some(Token::Skip),                    
+        "assert" => Some(Token::Assert),
+        "assume" => Some(Token::Assume),
+        "proc" => Some(Token::Proc),
+        "returns" => Some(Token::Returns),
+        "array" => Some(Token::Array),
+        "len" => Some(Token::Len),
         
         "not" => Some(Token::Not),
         "and" => Some(Token::And),
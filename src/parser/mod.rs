@@ -1,23 +1,32 @@
 use std::fs::File;
 
-use crate::{parser::{ast_parser::abstract_parse, cst_parser::ConcreteParser, lexer::Lexer}, types::{ast::{NumLiteral, Statement}, errors::ParserError}};
+use crate::{parser::{ast_parser::{abstract_parse, check_procedures, desugar}, cst_parser::ConcreteParser, lexer::Lexer}, types::{ast::{Aexpr, Bexpr, Module, NumLiteral}, errors::ParserError}};
 use self::lexer::MyLexer;
 
 mod cst_parser;
-mod ast_parser;
+pub(crate) mod ast_parser;
 mod lexer;
 
-#[allow(dead_code)]
-pub fn parse_string<N: NumLiteral>(str: String) -> Result<Statement<N>, ParserError<N>> {
+pub fn parse_string<N: NumLiteral>(str: String) -> Result<Module<N>, Vec<ParserError<N>>> {
     let lexer = MyLexer::from(str.as_str());
     return parse(lexer)
 }
-pub fn parse_file<N: NumLiteral>(file: File) -> Result<Statement<N>,ParserError<N>> {
+#[allow(dead_code)]
+pub fn parse_file<N: NumLiteral>(file: File) -> Result<Module<N>, Vec<ParserError<N>>> {
     let lexer = MyLexer::from(file);
     return parse(lexer)
 }
 
-fn parse<N: NumLiteral>(lexer: impl Lexer<N>)-> Result<Statement<N>,ParserError<N>>{   
+pub fn parse_aexpr_string<N: NumLiteral>(str: &str) -> Result<Aexpr<N>, Vec<ParserError<N>>> {
+    let cst = ConcreteParser::new(MyLexer::from(str)).parse_single_aexpr()?;
+    Ok(ast_parser::parse_aexpr(&cst))
+}
+pub fn parse_bexpr_string<N: NumLiteral>(str: &str) -> Result<Bexpr<N>, Vec<ParserError<N>>> {
+    let cst = ConcreteParser::new(MyLexer::from(str)).parse_single_bexpr()?;
+    Ok(ast_parser::parse_bexpr(&cst))
+}
+
+fn parse<N: NumLiteral>(lexer: impl Lexer<N>)-> Result<Module<N>, Vec<ParserError<N>>>{   
 
     if std::env::var("print-token").is_ok_and(|s|s=="true") {
         println!("╔════════╗");
@@ -48,6 +57,14 @@ fn parse<N: NumLiteral>(lexer: impl Lexer<N>)-> Result<Statement<N>,ParserError<
 
 
     let ast = abstract_parse(&cst);
+    let errors = check_procedures(&ast);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let ast = match std::env::var("desugar").is_ok_and(|s|s=="true") {
+        true => desugar(ast),
+        false => ast,
+    };
 
 
     if std::env::var("print-ast").is_ok_and(|s|s=="true") {
@@ -1,5 +1,7 @@
 use std::{fmt::{Display, Debug}, str::FromStr};
 
+use super::span::Span;
+
 
 pub type Var = String;
 pub type Num = i128;
@@ -15,13 +17,33 @@ pub enum Operator{
     Add,Sub,Mul,Div
 }
 
+// The spans locate the statements in the source (for if and while only the header)
 #[derive(Debug, Clone)]
 pub enum Statement<D> {
-    Assign (Var, Box<Aexpr<D>>),
-    Skip,
+    Assign (Var, Box<Aexpr<D>>, Span),
+    Skip (Span),
     Compose    (Box<Statement<D>>, Box<Statement<D>>),
-    IfThenElse (Box<Bexpr<D>>, Box<Statement<D>>, Box<Statement<D>>),
-    While      (Box<Bexpr<D>>, Box<Statement<D>>),
+    IfThenElse (Box<Bexpr<D>>, Box<Statement<D>>, Box<Statement<D>>, Span),
+    While      (Box<Bexpr<D>>, Box<Statement<D>>, Span),
+    // A false assertion stops the execution with an error, a false assumption discards it
+    Assert     (Box<Bexpr<D>>, Span),
+    Assume     (Box<Bexpr<D>>, Span),
+    // a[i] := e (the index is evaluated first) and a := array(n), that creates an array of n zeros
+    ArrayAssign (Var, Box<Aexpr<D>>, Box<Aexpr<D>>, Span),
+    NewArray   (Var, Box<Aexpr<D>>, Span),
+}
+
+impl<D> Statement<D> {
+    // Location of the statement (of the first one for a composition)
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Assign(_, _, span) | Statement::Skip(span) |
+            Statement::IfThenElse(_, _, _, span) | Statement::While(_, _, span) |
+            Statement::Assert(_, span) | Statement::Assume(_, span) |
+            Statement::ArrayAssign(_, _, _, span) | Statement::NewArray(_, _, span) => *span,
+            Statement::Compose(s1, _) => s1.span(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -31,25 +53,50 @@ pub enum PrePostOp {
 pub type PreOp = PrePostOp;
 pub type PostOp = PrePostOp;
 
+// The spans locate the expressions that can fail at runtime
 #[derive(Debug, Clone)]
 pub enum Aexpr<D>  {
     Lit  (D),
-    Var  (Var),
-    PreOp (PreOp, Var),
-    PostOp (PostOp, Var),
-    BinOp (Operator, Box<Aexpr<D>>, Box<Aexpr<D>>),
+    Var  (Var, Span),
+    PreOp (PreOp, Var, Span),
+    PostOp (PostOp, Var, Span),
+    BinOp (Operator, Box<Aexpr<D>>, Box<Aexpr<D>>, Span),
+    // Call of a procedure, the arguments are evaluated from left to right
+    Call (Var, Vec<Aexpr<D>>, Span),
+    // Element a[i] and length len(a) of an array
+    Index (Var, Box<Aexpr<D>>, Span),
+    Len (Var, Span),
 }
 
+// The spans of the comparisons cover both the operands
 #[derive(Debug, Clone)]
 pub enum Bexpr<D> {
     True,
     False,
-    Equal  (Box<Aexpr<D>>, Box<Aexpr<D>>),
-    LessEq (Box<Aexpr<D>>, Box<Aexpr<D>>),
+    Equal  (Box<Aexpr<D>>, Box<Aexpr<D>>, Span),
+    LessEq (Box<Aexpr<D>>, Box<Aexpr<D>>, Span),
     Not    (Box<Bexpr<D>>),
     And    (Box<Bexpr<D>>, Box<Bexpr<D>>),
+    // Also in the core language, so that they are not rewritten with Not (see ast_parser::desugar)
+    Or       (Box<Bexpr<D>>, Box<Bexpr<D>>),
+    Less     (Box<Aexpr<D>>, Box<Aexpr<D>>, Span),
+    NotEqual (Box<Aexpr<D>>, Box<Aexpr<D>>, Span),
 }
 
+// The procedures see only their parameters: no global variables, the result is the final value of `result`
+#[derive(Debug, Clone)]
+pub struct Procedure<D> {
+    pub name: Var,
+    pub params: Vec<Var>,
+    pub result: Var,
+    pub body: Statement<D>,
+    // Header of the declaration
+    pub span: Span,
+}
 
-
+#[derive(Debug, Clone)]
+pub struct Module<D> {
+    pub procedures: Vec<Procedure<D>>,
+    pub main: Statement<D>,
+}
 
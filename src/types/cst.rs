@@ -1,31 +1,36 @@
+use super::span::Span;
+
 pub type Var = String;
 
 #[derive(Debug, Clone)]
 pub enum Aexpr<N>  {
-    Add  (Box<Aexpr<N>>,Box<Term<N>>),
-    Sub  (Box<Aexpr<N>>, Box<Term<N>>),
+    Add  (Box<Aexpr<N>>,Box<Term<N>>, Span),
+    Sub  (Box<Aexpr<N>>, Box<Term<N>>, Span),
     Term (Box<Term<N>>),
 
     //syntactic sugars
-    Opposite (Box<Factor<N>>),
+    Opposite (Box<Factor<N>>, Span),
 }
 
 #[derive(Debug, Clone)]
 pub enum Term<N> {
-    Mul   (Box<Term<N>>, Box<Factor<N>>),
-    Div   (Box<Term<N>>, Box<Factor<N>>),
+    Mul   (Box<Term<N>>, Box<Factor<N>>, Span),
+    Div   (Box<Term<N>>, Box<Factor<N>>, Span),
     Factor(Box<Factor<N>>)
 }
 
 #[derive(Debug, Clone)]
 pub enum Factor<N> {
     Lit  (N),
-    Var  (Var),
-    PreInc (Var),
-    PostInc (Var),
-    PreDec (Var),
-    PostDec (Var),
-    Aexpr (Box<Aexpr<N>>)
+    Var  (Var, Span),
+    PreInc (Var, Span),
+    PostInc (Var, Span),
+    PreDec (Var, Span),
+    PostDec (Var, Span),
+    Aexpr (Box<Aexpr<N>>),
+    Call (Var, Vec<Aexpr<N>>, Span),
+    Index (Var, Box<Aexpr<N>>, Span),
+    Len (Var, Span),
 }
 
 
@@ -42,42 +47,64 @@ pub enum Bexpr<N> {
 pub enum BexprAtomic<N> {
     True,
     False,
-    Equal     (Box<Aexpr<N>>, Box<Aexpr<N>>),
-    LessEq    (Box<Aexpr<N>>, Box<Aexpr<N>>),
+    Equal     (Box<Aexpr<N>>, Box<Aexpr<N>>, Span),
+    LessEq    (Box<Aexpr<N>>, Box<Aexpr<N>>, Span),
     Not    (Box<BexprAtomic<N>>),
     Bexpr  (Box<Bexpr<N>>),
 
     //syntactic sugars
-    Less      (Box<Aexpr<N>>, Box<Aexpr<N>>),
-    GreaterEq (Box<Aexpr<N>>, Box<Aexpr<N>>),
-    Greater   (Box<Aexpr<N>>, Box<Aexpr<N>>),
-    NotEqual (Box<Aexpr<N>>, Box<Aexpr<N>>),
+    Less      (Box<Aexpr<N>>, Box<Aexpr<N>>, Span),
+    GreaterEq (Box<Aexpr<N>>, Box<Aexpr<N>>, Span),
+    Greater   (Box<Aexpr<N>>, Box<Aexpr<N>>, Span),
+    NotEqual (Box<Aexpr<N>>, Box<Aexpr<N>>, Span),
 }
 
 
+// Spans of conditional and loop statements cover only the header (keyword and guard)
 #[derive(Debug, Clone)]
 pub enum Statement<N> {
-    Skip,
-    IfThenElse (Box<Bexpr<N>>, Box<Statement<N>>, Box<Statement<N>>),
-    While      (Box<Bexpr<N>>, Box<Statement<N>>),
+    Skip (Span),
+    IfThenElse (Box<Bexpr<N>>, Box<Statement<N>>, Box<Statement<N>>, Span),
+    While      (Box<Bexpr<N>>, Box<Statement<N>>, Span),
     Block      (Box<Statements<N>>),
     AssignStm (Box<AssignStatements<N>>),
+    Assert (Box<Bexpr<N>>, Span),
+    Assume (Box<Bexpr<N>>, Span),
 
     //syntactic sugars
-    RepeatUntil(Box<Statement<N>>, Box<Bexpr<N>>),
-    ForLoop (Var, Box<Aexpr<N>>, Box<Bexpr<N>>,Box<AssignStatements<N>>,Box<Statement<N>>),
+    RepeatUntil(Box<Statement<N>>, Box<Bexpr<N>>, Span),
+    ForLoop (Var, Box<Aexpr<N>>, Box<Bexpr<N>>,Box<AssignStatements<N>>,Box<Statement<N>>, Span),
 }
 
 #[derive(Debug, Clone)]
 pub enum AssignStatements<N> {
-    Assign (Var, Box<Aexpr<N>>),
-    AddAssign (Var, Box<Aexpr<N>>),
-    SubAssign (Var, Box<Aexpr<N>>),
-    MulAssign (Var, Box<Aexpr<N>>),
+    Assign (Var, Box<Aexpr<N>>, Span),
+    AddAssign (Var, Box<Aexpr<N>>, Span),
+    SubAssign (Var, Box<Aexpr<N>>, Span),
+    MulAssign (Var, Box<Aexpr<N>>, Span),
+    // a[i] := e and a := array(n)
+    ArrayAssign (Var, Box<Aexpr<N>>, Box<Aexpr<N>>, Span),
+    NewArray (Var, Box<Aexpr<N>>, Span),
 }
 
 #[derive(Debug, Clone)]
 pub enum Statements<N> {
     Composition (Box<Statements<N>>, Box<Statement<N>>),
     Singleton (Box<Statement<N>>)
-}
\ No newline at end of file
+}
+// Procedure declarations come before the statements of the main program
+#[derive(Debug, Clone)]
+pub struct Module<N> {
+    pub procedures: Vec<Procedure<N>>,
+    pub main: Statements<N>,
+}
+
+// The span covers only the header 'proc name(params) returns result'
+#[derive(Debug, Clone)]
+pub struct Procedure<N> {
+    pub name: Var,
+    pub params: Vec<Var>,
+    pub result: Var,
+    pub body: Statements<N>,
+    pub span: Span,
+}
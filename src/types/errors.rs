@@ -1,32 +1,154 @@
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
-use super::{ast::NumLiteral, tokens::Token};
+use crate::interpreter::{int_semantics::IntSemantics, types::{Int, State, Value}};
 
+use super::{ast::{Num, NumLiteral, Statement}, span::{render_error, Span}, tokens::Token};
 
+
+// In the expected sets identifiers and literals are represented by a placeholder value (see Token::kind)
 #[derive(Debug)]
 pub enum ParserError<N> {
-    UnexpectedEOF,
+    UnexpectedEOF {expected: Vec<Token<N>>},
     UnknownSymbol{pos: (usize,usize), symbol: char},
-    UnexpectedToken {pos: (usize,usize), expected: Option<Token<N>>, found: Token<N>},
+    InvalidLiteral{pos: (usize,usize), literal: String},
+    UnexpectedToken {pos: (usize,usize), expected: Vec<Token<N>>, found: Token<N>},
+    // Checks of the procedures after the parsing
+    DuplicateProcedure {pos: (usize,usize), name: String},
+    UndefinedProcedure {pos: (usize,usize), name: String},
+    ArgumentCount {pos: (usize,usize), name: String, expected: usize, found: usize},
 }
 
 #[derive(Debug)]
 pub enum RuntimeError {
-    VariableNotInitialized(String),
-    NotImplemented(String)
+    VariableNotInitialized(String, Span),
+    DivisionByZero(Span),
+    // The result of the operation does not fit in the integers (var is the updated variable, if any)
+    Overflow { var: Option<String>, operation: String, span: Span },
+    // The execution was stopped in the loop while_stm, with the given state at the loop head
+    StepLimitExceeded { limit: ExecutionLimit, state: State<Value>, while_stm: Box<Statement<Num>> },
+    // The state at the head of the loop while_stm repeated
+    Diverges { state: State<Value>, while_stm: Box<Statement<Num>> },
+    AssertionFailed { state: State<Value>, span: Span },
+    // The execution does not satisfy an assume, so it is not one of the runs of interest
+    AssumptionViolated { state: State<Value>, span: Span },
+    UndefinedProcedure(String, Span),
+    ArgumentCount { name: String, expected: usize, found: usize, span: Span },
+    // Too many nested calls, most likely an infinite recursion
+    CallDepthExceeded { depth: usize, span: Span },
+    // Error inside a procedure, stack lists the calls (procedure and call site) from the outermost
+    InCall { stack: Vec<(String, Span)>, error: Box<RuntimeError> },
+    // An array used as an integer and vice versa
+    NotAnInteger(String, Span),
+    NotAnArray(String, Span),
+    IndexOutOfBounds { array: String, index: Int, length: usize, span: Span },
+    // Negative or too large length of a new array
+    InvalidArrayLength(Int, Span),
+}
+
+// Calls listed in the rendering of an error inside a procedure
+const CALL_STACK_SHOWN: usize = 10;
+
+#[derive(Debug)]
+pub enum ExecutionLimit {
+    Steps(u64),
+    Timeout(Duration),
+}
+
+impl RuntimeError {
+    // The error message with the location of the error in the source
+    pub fn render(&self, source: &str, sem: IntSemantics) -> String {
+        match self {
+            RuntimeError::VariableNotInitialized(x, span) =>
+                render_error(source, *span, &format!("Runtime error: variable '{x}' used before initialization")),
+            RuntimeError::DivisionByZero(span) =>
+                render_error(source, *span, "Runtime error: division by zero"),
+            RuntimeError::Overflow { var: Some(x), operation, span } =>
+                render_error(source, *span, &format!("Runtime error: integer overflow ({sem}) computing '{operation}' assigned to '{x}'")),
+            RuntimeError::Overflow { var: None, operation, span } =>
+                render_error(source, *span, &format!("Runtime error: integer overflow ({sem}) computing '{operation}'")),
+            RuntimeError::StepLimitExceeded { limit, state, while_stm } => {
+                let msg = match limit {
+                    ExecutionLimit::Steps(n) => format!("Runtime error: step limit of {n} exceeded in loop"),
+                    ExecutionLimit::Timeout(t) => format!("Runtime error: timeout of {}ms expired in loop", t.as_millis()),
+                };
+                format!("{}\nSTATE: {:?}", render_error(source, while_stm.span(), &msg), state)
+            },
+            RuntimeError::Diverges { state, while_stm } => {
+                let msg = "Runtime error: the program definitely diverges, the state repeats at the loop head";
+                format!("{}\nSTATE: {:?}", render_error(source, while_stm.span(), msg), state)
+            },
+            RuntimeError::AssertionFailed { state, span } =>
+                format!("{}\nSTATE: {:?}", render_error(source, *span, "Runtime error: assertion failed"), state),
+            RuntimeError::AssumptionViolated { state, span } =>
+                format!("{}\nSTATE: {:?}", render_error(source, *span, "Execution stopped: the assumption does not hold"), state),
+            RuntimeError::UndefinedProcedure(p, span) =>
+                render_error(source, *span, &format!("Runtime error: call of the undeclared procedure '{p}'")),
+            RuntimeError::ArgumentCount { name, expected, found, span } =>
+                render_error(source, *span, &format!("Runtime error: procedure '{name}' takes {expected} argument(s) but {found} were given")),
+            RuntimeError::CallDepthExceeded { depth, span } =>
+                render_error(source, *span, &format!("Runtime error: more than {depth} nested calls")),
+            RuntimeError::NotAnInteger(x, span) =>
+                render_error(source, *span, &format!("Runtime error: '{x}' is an array, not an integer")),
+            RuntimeError::NotAnArray(x, span) =>
+                render_error(source, *span, &format!("Runtime error: '{x}' is not an array")),
+            RuntimeError::IndexOutOfBounds { array, index, length, span } =>
+                render_error(source, *span, &format!("Runtime error: index {index} out of bounds for '{array}' of length {length}")),
+            RuntimeError::InvalidArrayLength(n, span) =>
+                render_error(source, *span, &format!("Runtime error: invalid array length {n}")),
+            RuntimeError::InCall { stack, error } => {
+                // The innermost calls are the interesting ones in a deep recursion
+                let mut calls = stack.iter().rev()
+                    .take(CALL_STACK_SHOWN)
+                    .map(|(p, span)| format!("    {p} called at {span}"))
+                    .collect::<Vec<_>>();
+                if stack.len() > CALL_STACK_SHOWN {
+                    calls.push(format!("    ... {} more call(s)", stack.len() - CALL_STACK_SHOWN));
+                }
+                format!("{}\nCALL STACK (innermost first):\n{}", error.render(source, sem), calls.join("\n"))
+            },
+        }
+    }
+}
+
+impl<N> ParserError<N> {
+    pub fn pos(&self) -> Option<(usize,usize)> {
+        match self {
+            ParserError::UnexpectedEOF { .. } => None,
+            ParserError::UnknownSymbol { pos, .. } |
+            ParserError::InvalidLiteral { pos, .. } |
+            ParserError::UnexpectedToken { pos, .. } |
+            ParserError::DuplicateProcedure { pos, .. } |
+            ParserError::UndefinedProcedure { pos, .. } |
+            ParserError::ArgumentCount { pos, .. } => Some(*pos),
+        }
+    }
 }
 
 impl<N: NumLiteral> Display for ParserError<N>{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let expected_str = |expected: &Vec<Token<N>>| match expected.as_slice() {
+            [tok] => tok.kind(),
+            toks => format!("one of {{{}}}", toks.iter().map(|t|t.kind()).collect::<Vec<_>>().join(", ")),
+        };
         match self {
-            ParserError::UnexpectedEOF => 
+            ParserError::UnexpectedEOF { expected } if expected.is_empty() =>
                 write!(f,"Unexpected EOF encountered"),
-            ParserError::UnknownSymbol { pos:(l,c), symbol } =>
-                write!(f,"Unknown symbol encountered: '{symbol}' at location {l}:{c}"),
-            ParserError::UnexpectedToken { pos:(l,c), expected: None, found } =>
-                write!(f,"Unexpected token encountered: {:?} at location {l}:{c}", found),
-            ParserError::UnexpectedToken { pos:(l,c), expected: Some(expected), found } =>
-                write!(f,"Expected token {:?} but found {:?} at location {l}:{c}", expected, found),
+            ParserError::UnexpectedEOF { expected } =>
+                write!(f,"Unexpected EOF encountered, expected {}", expected_str(expected)),
+            ParserError::UnknownSymbol { symbol, .. } =>
+                write!(f,"Unknown symbol encountered: '{symbol}'"),
+            ParserError::InvalidLiteral { literal, .. } =>
+                write!(f,"Invalid numeric literal: '{literal}'"),
+            ParserError::UnexpectedToken { expected, found, .. } if expected.is_empty() =>
+                write!(f,"Unexpected token encountered: {found}"),
+            ParserError::UnexpectedToken { expected, found, .. } =>
+                write!(f,"Expected {} but found {found}", expected_str(expected)),
+            ParserError::DuplicateProcedure { name, .. } =>
+                write!(f,"Procedure '{name}' is already declared"),
+            ParserError::UndefinedProcedure { name, .. } =>
+                write!(f,"Call of the undeclared procedure '{name}'"),
+            ParserError::ArgumentCount { name, expected, found, .. } =>
+                write!(f,"Procedure '{name}' takes {expected} argument(s) but {found} were given"),
         }
     }
-}
\ No newline at end of file
+}
@@ -3,6 +3,7 @@ pub mod cst;
 pub mod errors;
 pub mod tokens;
 pub mod lit_interval;
+pub mod span;
 pub mod printers {
     pub mod ast_printer;
     mod cst_printer;
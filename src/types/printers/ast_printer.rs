@@ -1,5 +1,5 @@
 use std::fmt::Display;
-use crate::types::ast::{Aexpr, Bexpr, Operator, PrePostOp, Statement};
+use crate::types::ast::{Aexpr, Bexpr, Module, Operator, PrePostOp, Procedure, Statement};
 
 impl Display for Operator{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -25,11 +25,15 @@ impl<N: Display> Display for Aexpr<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Aexpr::Lit(n) => write!(f, "{n}"),
-            Aexpr::Var(x) => write!(f, "{x}"),
-            Aexpr::PreOp(op, x) => write!(f, "{op}{x}"),
-            Aexpr::PostOp(op, x) => write!(f, "{x}{op}"),
-            Aexpr::BinOp(op, a1, a2) =>
+            Aexpr::Var(x, _) => write!(f, "{x}"),
+            Aexpr::PreOp(op, x, _) => write!(f, "{op}{x}"),
+            Aexpr::PostOp(op, x, _) => write!(f, "{x}{op}"),
+            Aexpr::BinOp(op, a1, a2, _) =>
                 write!(f, "({a1} {op} {a2})"),
+            Aexpr::Call(p, args, _) =>
+                write!(f, "{p}({})", args.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ")),
+            Aexpr::Index(x, i, _) => write!(f, "{x}[{i}]"),
+            Aexpr::Len(x, _) => write!(f, "len({x})"),
         }
     }
 }
@@ -41,14 +45,20 @@ impl<N: Display> Display for Bexpr<N> {
                 write!(f, "true"),
             Bexpr::False => 
                 write!(f, "false"),
-            Bexpr::Equal(a1, a2) => 
+            Bexpr::Equal(a1, a2, _) => 
                 write!(f, "({} == {})", a1, a2),
-            Bexpr::LessEq(a1, a2) => 
+            Bexpr::LessEq(a1, a2, _) => 
                 write!(f, "({} <= {})", a1, a2),
             Bexpr::Not(b) =>
                 write!(f, "(not {})", b),
             Bexpr::And(b1, b2) => 
                 write!(f, "({} and {})", b1, b2),
+            Bexpr::Or(b1, b2) => 
+                write!(f, "({} or {})", b1, b2),
+            Bexpr::Less(a1, a2, _) => 
+                write!(f, "({} < {})", a1, a2),
+            Bexpr::NotEqual(a1, a2, _) => 
+                write!(f, "({} != {})", a1, a2),
         }
     }
 }
@@ -56,21 +66,29 @@ impl<N: Display> Display for Bexpr<N> {
 impl<N: Display> Display for Statement<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Statement::Assign(x, a) => 
+            Statement::Assign(x, a, _) => 
                 write!(f, "{x}:= {a};"),
-            Statement::Skip => 
+            Statement::Skip(_) => 
                 write!(f, "skip;"),
+            Statement::Assert(b, _) =>
+                write!(f, "assert {b};"),
+            Statement::Assume(b, _) =>
+                write!(f, "assume {b};"),
+            Statement::ArrayAssign(x, i, a, _) =>
+                write!(f, "{x}[{i}]:= {a};"),
+            Statement::NewArray(x, n, _) =>
+                write!(f, "{x}:= array({n});"),
             Statement::Compose(s1, s2) => {
                 write!(f, "{s1}\n{s2}")
             }
-            Statement::IfThenElse(b, s1, s2) => {
+            Statement::IfThenElse(b, s1, s2, _) => {
                 writeln!(f, "if {b} then {{")?;
                 writeln!(f, "{}", add_tab(&s1.to_string()))?;
                 writeln!(f,"}} else {{")?;
                 writeln!(f, "{}", add_tab(&s2.to_string()))?;
                 write!(f,"}}")
             }
-            Statement::While(b, s) => {
+            Statement::While(b, s, _) => {
                 writeln!(f, "while {b} do {{")?;
                 writeln!(f, "{}", add_tab(&s.to_string()))?;
                 write!(f,"}}")
@@ -78,6 +96,23 @@ impl<N: Display> Display for Statement<N> {
         }
     }
 }
+impl<N: Display> Display for Procedure<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "proc {}({}) returns {} {{", self.name, self.params.join(", "), self.result)?;
+        writeln!(f, "{}", add_tab(&self.body.to_string()))?;
+        write!(f, "}}")
+    }
+}
+
+impl<N: Display> Display for Module<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for p in &self.procedures {
+            writeln!(f, "{p}")?;
+        }
+        write!(f, "{}", self.main)
+    }
+}
+
 fn add_tab(str: &str)-> String{
     let mut tabbed_string = str
         .lines()
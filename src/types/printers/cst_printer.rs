@@ -1,23 +1,23 @@
 use std::fmt::Display;
 
-use crate::types::cst::{Aexpr, Term, Factor, BexprAtomic, Bexpr, Statement, AssignStatements, Statements};
+use crate::types::cst::{Aexpr, Term, Factor, BexprAtomic, Bexpr, Statement, AssignStatements, Statements, Module, Procedure};
 
 
 
 impl<N: Display> Display for Aexpr<N> {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Aexpr::Add(a,t) => write!(fmt, "({a} + {t})"),
-            Aexpr::Sub(a, t) => write!(fmt, "({a} - {t})"),
+            Aexpr::Add(a,t,_) => write!(fmt, "({a} + {t})"),
+            Aexpr::Sub(a, t, _) => write!(fmt, "({a} - {t})"),
             Aexpr::Term( t) => write!(fmt, "{t}"),
-            Aexpr::Opposite(f) => write!(fmt, "-{f}"),        }
+            Aexpr::Opposite(f, _) => write!(fmt, "-{f}"),        }
     }
 }
 impl<N: Display> Display for Term<N> {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Term::Mul(t, f) => write!(fmt, "({t} * {f})"),
-            Term::Div(t, f) => write!(fmt, "({t} / {f})"),
+            Term::Mul(t, f, _) => write!(fmt, "({t} * {f})"),
+            Term::Div(t, f, _) => write!(fmt, "({t} / {f})"),
             Term::Factor(f) => write!(fmt, "{f}"),
         }
     }
@@ -26,12 +26,16 @@ impl<N: Display> Display for Factor<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self{
             Factor::Lit(n) => write!(f, "{n}"),
-            Factor::Var(x) => write!(f, "{x}"),
-            Factor::PreInc(x) => write!(f, "++{x}"), 
-            Factor::PostInc(x) => write!(f, "{x}++"),
-            Factor::PreDec(x) => write!(f, "--{x}"),
-            Factor::PostDec(x) => write!(f, "{x}--"),            
+            Factor::Var(x, _) => write!(f, "{x}"),
+            Factor::PreInc(x, _) => write!(f, "++{x}"), 
+            Factor::PostInc(x, _) => write!(f, "{x}++"),
+            Factor::PreDec(x, _) => write!(f, "--{x}"),
+            Factor::PostDec(x, _) => write!(f, "{x}--"),            
             Factor::Aexpr(a) => write!(f, "{a}"),
+            Factor::Call(p, args, _) =>
+                write!(f, "{p}({})", args.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ")),
+            Factor::Index(x, i, _) => write!(f, "{x}[{i}]"),
+            Factor::Len(x, _) => write!(f, "len({x})"),
         }
     }
 }
@@ -50,12 +54,12 @@ impl<N: Display> Display for BexprAtomic<N> {
         match self {
             BexprAtomic::True => write!(fmt, "true"),
             BexprAtomic::False => write!(fmt, "false"),
-            BexprAtomic::Equal(a1, a2) => write!(fmt, "({a1} == {a2})"),
-            BexprAtomic::NotEqual(a1, a2) => write!(fmt, "({a1} != {a2})"),
-            BexprAtomic::Less(a1, a2) => write!(fmt, "({a1} < {a2})"),
-            BexprAtomic::LessEq(a1, a2) => write!(fmt, "({a1} <= {a2})"),
-            BexprAtomic::Greater(a1, a2) => write!(fmt, "({a1} > {a2})"),
-            BexprAtomic::GreaterEq(a1, a2) => write!(fmt, "({a1} => {a2})"),
+            BexprAtomic::Equal(a1, a2, _) => write!(fmt, "({a1} == {a2})"),
+            BexprAtomic::NotEqual(a1, a2, _) => write!(fmt, "({a1} != {a2})"),
+            BexprAtomic::Less(a1, a2, _) => write!(fmt, "({a1} < {a2})"),
+            BexprAtomic::LessEq(a1, a2, _) => write!(fmt, "({a1} <= {a2})"),
+            BexprAtomic::Greater(a1, a2, _) => write!(fmt, "({a1} > {a2})"),
+            BexprAtomic::GreaterEq(a1, a2, _) => write!(fmt, "({a1} => {a2})"),
             BexprAtomic::Not(b) => write!(fmt, "(not {})", b),
             BexprAtomic::Bexpr(b) => write!(fmt, "({b})"),
         }
@@ -65,27 +69,29 @@ impl<N: Display> Display for BexprAtomic<N> {
 impl<N: Display> Display for Statement<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self{
-            Statement::Skip => write!(f, "skip;"),
+            Statement::Skip(_) => write!(f, "skip;"),
             Statement::AssignStm(s) =>  write!(f, "{s};"),
+            Statement::Assert(b, _) => write!(f, "assert {b};"),
+            Statement::Assume(b, _) => write!(f, "assume {b};"),
             Statement::Block(stms) => write!(f, "{stms}"),
-            Statement::IfThenElse(b, s1, s2) => {
+            Statement::IfThenElse(b, s1, s2, _) => {
                 writeln!(f, "if {b} then {{")?;
                 writeln!(f, "{}", add_tab(&s1.to_string()))?;
                 writeln!(f,"}} else {{")?;
                 writeln!(f, "{}", add_tab(&s2.to_string()))?;
                 write!(f,"}}")
             }
-            Statement::While(b, s) => { 
+            Statement::While(b, s, _) => { 
                 writeln!(f, "while {b} do {{")?;
                 writeln!(f, "{}", add_tab(&s.to_string()))?;
                 write!(f, "}}")
             }
-            Statement::RepeatUntil(s, b) => {
+            Statement::RepeatUntil(s, b, _) => {
                 writeln!(f, "repeat {{")?;
                 writeln!(f, "{}", add_tab(&s.to_string()))?;
                 write!(f, "}} until {b};")
             }
-            Statement::ForLoop(x, a, b, a_stm, s) => { 
+            Statement::ForLoop(x, a, b, a_stm, s, _) => { 
                 writeln!(f, "for ({x}:= {a}; {b}; {a_stm}) {{")?;
                 writeln!(f, "{}", add_tab(&s.to_string()))?;
                 write!(f, "}}")
@@ -96,10 +102,12 @@ impl<N: Display> Display for Statement<N> {
 impl<N: Display> Display for AssignStatements<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            AssignStatements::Assign(x, a) => write!(f, "{x}:= {a}"),
-            AssignStatements::AddAssign(x, a) => write!(f, "{x}+= {a}"),
-            AssignStatements::SubAssign(x, a) => write!(f, "{x}-= {a}"),
-            AssignStatements::MulAssign(x, a) => write!(f, "{x}*= {a}"),
+            AssignStatements::Assign(x, a, _) => write!(f, "{x}:= {a}"),
+            AssignStatements::AddAssign(x, a, _) => write!(f, "{x}+= {a}"),
+            AssignStatements::SubAssign(x, a, _) => write!(f, "{x}-= {a}"),
+            AssignStatements::MulAssign(x, a, _) => write!(f, "{x}*= {a}"),
+            AssignStatements::ArrayAssign(x, i, a, _) => write!(f, "{x}[{i}]:= {a}"),
+            AssignStatements::NewArray(x, n, _) => write!(f, "{x}:= array({n})"),
         }
     }
 }
@@ -111,6 +119,21 @@ impl<N: Display> Display for Statements<N> {
         }
     }
 }
+impl<N: Display> Display for Procedure<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "proc {}({}) returns {} {{", self.name, self.params.join(", "), self.result)?;
+        writeln!(f, "{}", add_tab(&self.body.to_string()))?;
+        write!(f, "}}")
+    }
+}
+impl<N: Display> Display for Module<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for p in &self.procedures {
+            writeln!(f, "{p}")?;
+        }
+        write!(f, "{}", self.main)
+    }
+}
 
 fn add_tab(str: &str)-> String{
     let mut tabbed_string = str
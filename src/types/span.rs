@@ -0,0 +1,74 @@
+use std::fmt::Display;
+
+// Position in the source code as (line, column), both starting from 1
+pub type Position = (usize, usize);
+
+// Region of the source code from start (included) to end (excluded)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Span { start, end }
+    }
+    pub fn line(&self) -> usize {
+        self.start.0
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.start.0, self.start.1)
+    }
+}
+
+// Source code covered by the span (until the end of the first line for spans on multiple lines)
+pub fn span_text(source: &str, span: Span) -> String {
+    let (line, col) = span.start;
+    let Some(src_line) = source.lines().nth(line.wrapping_sub(1)) else { return String::new() };
+    let len = if span.end.0 == line { span.end.1.saturating_sub(col) } else { usize::MAX };
+    src_line.chars().skip(col.saturating_sub(1)).take(len).collect()
+}
+
+// Render an error message with the offending source line and a caret under the span, like:
+//   Runtime error: division by zero
+//    --> 3:6
+//     |
+//   3 | y := x / z;
+//     |      ^^^^^
+pub fn render_error(source: &str, span: Span, msg: &str) -> String {
+    let (line, col) = span.start;
+    let Some(src_line) = source.lines().nth(line.wrapping_sub(1)) else {
+        return format!("{msg}\n --> {span}");
+    };
+    // Spans on multiple lines are underlined until the end of the first line
+    let end_col = if span.end.0 == line { span.end.1 } else { src_line.chars().count() + 1 };
+    let width = line.to_string().len();
+    let pad = " ".repeat(width);
+    let carets = "^".repeat(end_col.saturating_sub(col).max(1));
+    format!(
+        "{msg}\n{pad}--> {span}\n{pad} |\n{line} | {src_line}\n{pad} | {}{carets}",
+        " ".repeat(col.saturating_sub(1))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_with_carets() {
+        let source = "x := 1;\ny := x / z;";
+        let span = Span::new((2, 6), (2, 11));
+        assert_eq!(span_text(source, span), "x / z");
+        assert_eq!(
+            render_error(source, span, "Runtime error: division by zero"),
+            "Runtime error: division by zero\n --> 2:6\n  |\n2 | y := x / z;\n  |      ^^^^^"
+        );
+        // Out of the source only the position is shown
+        assert_eq!(render_error(source, Span::new((5, 1), (5, 2)), "error"), "error\n --> 5:1");
+    }
+}
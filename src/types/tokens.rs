@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 #[derive(Debug,Clone,PartialEq)]
 pub enum Token<N> {
     Id(String),
@@ -14,6 +16,12 @@ pub enum Token<N> {
     Repeat,
     Until,
     For,
+    Assert,
+    Assume,
+    Proc,
+    Returns,
+    Array,
+    Len,
 
     Assign,
     Plus,
@@ -41,8 +49,83 @@ pub enum Token<N> {
 
 
     Semicolon,
+    Comma,
     BracketOpen,
     BracketClose,
+    SquareOpen,
+    SquareClose,
     CurlyOpen,
     CurlyClose,
 }
+
+
+impl<N> Token<N> {
+    // Description of the kind of token, without the value of identifiers and literals
+    pub fn kind(&self) -> String {
+        match self {
+            Token::Id(_) => "identifier".to_string(),
+            Token::Lit(_) => "literal".to_string(),
+            tok => format!("'{}'", tok.symbol()),
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Token::Id(_) | Token::Lit(_) => "",
+            Token::True => "true",
+            Token::False => "false",
+            Token::Skip => "skip",
+            Token::If => "if",
+            Token::Then => "then",
+            Token::Else => "else",
+            Token::While => "while",
+            Token::Do => "do",
+            Token::Repeat => "repeat",
+            Token::Until => "until",
+            Token::For => "for",
+            Token::Assert => "assert",
+            Token::Assume => "assume",
+            Token::Proc => "proc",
+            Token::Returns => "returns",
+            Token::Array => "array",
+            Token::Len => "len",
+            Token::Assign => ":=",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Mul => "*",
+            Token::Div => "/",
+            Token::AddAssign => "+=",
+            Token::SubAssign => "-=",
+            Token::MulAssign => "*=",
+            Token::Inc => "++",
+            Token::Dec => "--",
+            Token::Eq => "==",
+            Token::Neq => "!=",
+            Token::Lt => "<",
+            Token::Lte => "<=",
+            Token::Gt => ">",
+            Token::Gte => ">=",
+            Token::Not => "not",
+            Token::And => "and",
+            Token::Or => "or",
+            Token::Semicolon => ";",
+            Token::Comma => ",",
+            Token::BracketOpen => "(",
+            Token::BracketClose => ")",
+            Token::SquareOpen => "[",
+            Token::SquareClose => "]",
+            Token::CurlyOpen => "{",
+            Token::CurlyClose => "}",
+        }
+    }
+}
+
+impl<N: Display> Display for Token<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Id(x) => write!(f, "identifier '{x}'"),
+            Token::Lit(n) => write!(f, "literal '{n}'"),
+            tok => write!(f, "{}", tok.kind()),
+        }
+    }
+}